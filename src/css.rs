@@ -1,390 +1,4049 @@
 use crate::cssom;
-use crate::parser;
+use crate::loader;
+use crate::shorthand;
+use crate::supports;
+use crate::tokenizer::{self, ComponentValue, ComponentValues, HashType, Token};
+use crate::variables;
+
+pub struct Parser<'a> {
+    values: ComponentValues,
+    pos: usize,
+    warnings: Warnings,
+    /// `@import` rules are only allowed at the top level of a stylesheet,
+    /// before all other rules.
+    imports_allowed: bool,
+    /// Rulesets of the imported stylesheets, in the order they are
+    /// imported.
+    imported: cssom::Rulesets,
+    loader: Option<&'a dyn loader::Loader>,
+    /// The URLs of the stylesheet being parsed and of the stylesheets
+    /// importing it, innermost last, to break cycles.
+    urls: Vec<String>,
+    /// All layers declared so far, in the order they are declared.
+    layers: Vec<cssom::Layer>,
+    /// The layer rulesets are put in, `None` outside of layers.
+    layer: Option<cssom::Layer>,
+    /// The selectors `&` refers to, `None` outside of nested rules.
+    nesting: Option<cssom::Selectors>,
+    /// Style rules nested in a declaration block, each made of its prelude
+    /// and its block.
+    nested_rules: Vec<ComponentValues>,
+}
 
-pub struct Parser {
-    parser: parser::Parser,
+/// Describes CSS the parser dropped while recovering from an error. Each
+/// warning carries the source text that was dropped.
+#[derive(PartialEq, Eq, Clone)]
+pub enum Warning {
+    InvalidDeclaration(String),
+    InvalidSelector(String),
+    InvalidRule(String),
+    UnsupportedAtRule(String),
+    /// An imported stylesheet, by its resolved URL, couldn’t be loaded.
+    FailedImport(String),
+    /// A stylesheet imports itself, directly or through other imports.
+    CyclicImport(String),
 }
 
-impl Parser {
+pub type Warnings = Vec<Warning>;
+
+impl<'a> Parser<'a> {
     pub fn new(input: &str) -> Self {
+        let mut parser = Self::from_values(tokenizer::parse_component_values(input));
+        parser.imports_allowed = true;
+
+        return parser;
+    }
+
+    fn from_values(values: ComponentValues) -> Self {
         return Self {
-            parser: parser::Parser::new(input),
+            values: values,
+            pos: 0,
+            warnings: Warnings::new(),
+            imports_allowed: false,
+            imported: cssom::Rulesets::new(),
+            loader: None,
+            urls: Vec::new(),
+            layers: Vec::new(),
+            layer: None,
+            nesting: None,
+            nested_rules: Vec::new(),
         };
     }
 
     pub fn parse(input: &str) -> cssom::Rulesets {
+        let (rulesets, _) = Self::parse_with_warnings(input);
+
+        return rulesets;
+    }
+
+    /// Parses a stylesheet from an origin other than the page itself, like
+    /// the user-agent stylesheet.
+    pub fn parse_with_origin(input: &str, origin: cssom::Origin) -> cssom::Rulesets {
+        return Self::parse(input)
+            .into_iter()
+            .map(|ruleset| ruleset.origin(origin))
+            .collect();
+    }
+
+    /// Parses a stylesheet along with the layers it declares, so other
+    /// stylesheets can be appended to it.
+    pub fn parse_with_layers(input: &str) -> cssom::Stylesheet {
+        let mut parser = Self::new(input);
+        let rulesets = parser.parse_stylesheet();
+
+        return cssom::Stylesheet::new(rulesets, parser.layers);
+    }
+
+    /// Parses a stylesheet without loading its imports, which are dropped.
+    pub fn parse_with_warnings(input: &str) -> (cssom::Rulesets, Warnings) {
+        let mut parser = Self::new(input);
+        let rulesets = parser.parse_stylesheet();
+
+        return (rulesets, parser.warnings);
+    }
+
+    /// Parses the stylesheet at `url`, loading the stylesheets it imports
+    /// relative to it.
+    pub fn parse_with_loader(
+        input: &str,
+        url: &str,
+        loader: &'a dyn loader::Loader,
+    ) -> (cssom::Rulesets, Warnings) {
         let mut parser = Self::new(input);
+        parser.loader = Some(loader);
+        parser.urls.push(url.to_owned());
+
+        let rulesets = parser.parse_stylesheet();
+
+        return (rulesets, parser.warnings);
+    }
+
+    /// Parses a stylesheet and puts the rulesets of its imports in front of
+    /// its own.
+    fn parse_stylesheet(&mut self) -> cssom::Rulesets {
+        let rulesets = self.parse_rulesets();
+        let mut imported = std::mem::take(&mut self.imported);
+        imported.extend(rulesets);
+
+        return imported
+            .into_iter()
+            .enumerate()
+            .map(|(order, ruleset)| ruleset.order(order))
+            .collect();
+    }
 
-        return parser.parse_rulesets();
+    pub fn warnings(&self) -> &Warnings {
+        return &self.warnings;
     }
 
     fn parse_rulesets(&mut self) -> cssom::Rulesets {
         let mut rulesets = cssom::Rulesets::new();
 
-        self.parser.consume_whitespace();
+        loop {
+            // CDO and CDC tokens are only allowed for legacy reasons and
+            // can be ignored at the top level
+            self.consume_while(&|value| {
+                return value.is_whitespace()
+                    || value.is_token(&Token::CDO)
+                    || value.is_token(&Token::CDC);
+            });
+
+            if self.eof() {
+                break;
+            }
 
-        while !self.parser.eof() {
-            rulesets.push(self.parse_ruleset());
-            self.parser.consume_whitespace();
-        }
+            if let Some(ComponentValue::Token(Token::AtKeyword(name))) = self.next_value().cloned()
+            {
+                if name.eq_ignore_ascii_case("import") {
+                    self.parse_import_rule();
+                    continue;
+                }
 
-        return rulesets;
-    }
+                // Layer statements may come before imports as well
+                if name.eq_ignore_ascii_case("layer") {
+                    for ruleset in self.parse_layer_rule() {
+                        let order = rulesets.len();
+                        rulesets.push(ruleset.order(order));
+                    }
 
-    fn parse_ruleset(&mut self) -> cssom::Ruleset {
-        let selectors = self.parse_selectors();
+                    continue;
+                }
+
+                if !name.eq_ignore_ascii_case("charset") {
+                    self.imports_allowed = false;
+                }
 
-        self.parser.consume_whitespace();
-        assert!(self.parser.next_char() == '{');
-        self.parser.consume_char();
-        self.parser.consume_whitespace();
+                if name.eq_ignore_ascii_case("media") {
+                    for ruleset in self.parse_media_rule() {
+                        let order = rulesets.len();
+                        rulesets.push(ruleset.order(order));
+                    }
 
-        let declarations = self.parse_declarations();
+                    continue;
+                }
 
-        self.parser.consume_whitespace();
-        assert!(self.parser.next_char() == '}');
-        self.parser.consume_char();
+                if name.eq_ignore_ascii_case("supports") {
+                    for ruleset in self.parse_supports_rule() {
+                        let order = rulesets.len();
+                        rulesets.push(ruleset.order(order));
+                    }
 
-        return cssom::Ruleset::new(selectors, declarations);
-    }
+                    continue;
+                }
 
-    fn parse_declarations(&mut self) -> cssom::Declarations {
-        let mut declarations = cssom::Declarations::new();
+                // Other at-rules aren’t supported yet and are skipped entirely
+                let warning = Warning::UnsupportedAtRule(format!("@{}", name));
+
+                self.consume_at_rule();
+                self.warnings.push(warning);
+                continue;
+            }
 
-        while self.parser.next_char() != '}' {
-            let (property, value) = self.parse_declaration();
-            declarations.insert(property, value);
-            self.parser.consume_whitespace();
+            self.imports_allowed = false;
+
+            for ruleset in self.parse_ruleset() {
+                let ruleset = match self.layer {
+                    Some(ref layer) => ruleset.layer(layer.clone()),
+                    None => ruleset,
+                };
+
+                let order = rulesets.len();
+                rulesets.push(ruleset.order(order));
+            }
         }
 
-        return declarations;
+        return rulesets;
     }
 
-    fn parse_declaration(&mut self) -> (String, cssom::Value) {
-        let property = self
-            .parser
-            .consume_while(&|next_char| next_char != ':')
+    /// Parses a ruleset, followed by the rulesets nested in it. Rulesets
+    /// with invalid selectors are consumed completely, but dropped.
+    fn parse_ruleset(&mut self) -> cssom::Rulesets {
+        let start = self.pos;
+        let selectors = self.parse_selectors();
+
+        let block_pos = self.values[start..]
+            .iter()
+            .position(|value| matches!(value, ComponentValue::Block(Token::OpenCurly, _)))
+            .map(|offset| start + offset);
+
+        let prelude_end = block_pos.unwrap_or(self.values.len());
+        let prelude = tokenizer::serialize(&self.values[start..prelude_end])
             .trim()
             .to_owned();
 
-        self.parser.consume_char();
-        self.parser.consume_whitespace();
+        // A ruleset without a declaration block isn’t a ruleset at all
+        if block_pos.is_none() {
+            self.pos = self.values.len();
+            self.warnings.push(Warning::InvalidRule(prelude));
 
-        let value = self.parse_value();
+            return cssom::Rulesets::new();
+        }
+
+        self.pos = prelude_end;
 
-        assert!(self.parser.next_char() == ';');
-        self.parser.consume_char();
+        let block = match self.consume_value() {
+            Some(ComponentValue::Block(_, block)) => block,
+            _ => unreachable!(),
+        };
 
-        return (property, value);
+        return self.parse_style_block(selectors, block, prelude);
     }
 
-    fn parse_value(&mut self) -> cssom::Value {
-        if self.parser.next_char().is_numeric() {
-            return cssom::Value::Numeric(self.parse_numeric_value());
-        }
+    /// Parses the block of a style rule into its ruleset, followed by the
+    /// rulesets of the rules nested in it. Declarations after nested rules
+    /// still belong to the outer ruleset.
+    fn parse_style_block(
+        &mut self,
+        selectors: Option<cssom::Selectors>,
+        block: ComponentValues,
+        prelude: String,
+    ) -> cssom::Rulesets {
+        let mut parser = Self::from_values(block);
+        let declarations = parser.parse_declarations();
+        self.warnings.append(&mut parser.warnings);
+
+        let selectors = match selectors {
+            Some(selectors) => selectors,
+            None => {
+                self.warnings.push(Warning::InvalidSelector(prelude));
+
+                return cssom::Rulesets::new();
+            }
+        };
+
+        // `&` can’t represent pseudo-elements, so parent selectors with one
+        // are left out of it
+        let parent: cssom::Selectors = selectors
+            .iter()
+            .filter(|selector| selector.pseudo_element.is_none())
+            .cloned()
+            .collect();
 
-        if self.parser.next_char() == '#' {
-            return cssom::Value::Color(self.parse_color_value());
+        let mut rulesets = vec![cssom::Ruleset::new(selectors, declarations)];
+
+        for rule in parser.nested_rules {
+            rulesets.extend(self.parse_nested_rule(&parent, rule));
         }
 
-        return cssom::Value::String(self.parse_string_value());
+        return rulesets;
     }
 
-    fn parse_color_value(&mut self) -> cssom::Color {
-        assert!(self.parser.next_char() == '#');
-        self.parser.consume_char();
+    /// Parses a style rule nested in the block of a rule with the given
+    /// selectors, which `&` refers to. Without any, the rule is dropped.
+    fn parse_nested_rule(
+        &mut self,
+        parent: &cssom::Selectors,
+        mut rule: ComponentValues,
+    ) -> cssom::Rulesets {
+        let block = match rule.pop() {
+            Some(ComponentValue::Block(_, block)) => block,
+            _ => unreachable!(),
+        };
 
-        let mut hex = self
-            .parser
-            .consume_while(&|next_char| next_char.is_ascii_hexdigit());
+        let prelude = tokenizer::serialize(&rule).trim().to_owned();
 
-        self.parser.consume_whitespace();
+        if parent.is_empty() {
+            self.warnings.push(Warning::InvalidRule(prelude));
 
-        assert!(hex.len() == 3 || hex.len() == 6);
+            return cssom::Rulesets::new();
+        }
 
-        // Shorthand #abc == #aabbcc
-        if hex.len() == 3 {
-            hex = hex
-                .chars()
-                .flat_map(|c| std::iter::repeat(c).take(2))
-                .collect();
+        if let Some(ComponentValue::Token(Token::AtKeyword(name))) = rule.first() {
+            return self.parse_nested_conditional_rule(parent, name, &rule[1..], block, prelude);
         }
 
-        let r = u8::from_str_radix(&hex[0..2], 16).unwrap();
-        let g = u8::from_str_radix(&hex[2..4], 16).unwrap();
-        let b = u8::from_str_radix(&hex[4..6], 16).unwrap();
+        let selectors = parse_nested_selector_list(&rule, parent);
 
-        return cssom::Color::new(r, g, b);
+        return self.parse_style_block(selectors, block, prelude);
     }
 
-    fn parse_numeric_value(&mut self) -> cssom::NumericValue {
-        let number = self
-            .parser
-            .consume_while(&|next_char| next_char.is_numeric())
-            .parse()
-            .unwrap();
+    /// Parses an `@media` or `@supports` rule nested in a style rule. Its
+    /// block is that of a style rule with the parent selectors, so
+    /// `.a { @media print { color: red } }` is `@media print { .a { color:
+    /// red } }`.
+    fn parse_nested_conditional_rule(
+        &mut self,
+        parent: &cssom::Selectors,
+        name: &str,
+        condition: &[ComponentValue],
+        block: ComponentValues,
+        prelude: String,
+    ) -> cssom::Rulesets {
+        if name.eq_ignore_ascii_case("media") {
+            let media = parse_media_query_list(condition);
+
+            return self
+                .parse_style_block(Some(parent.clone()), block, prelude)
+                .into_iter()
+                .map(|ruleset| ruleset.media(media.clone()))
+                .collect();
+        }
 
-        if number == 0 {
-            self.parser.consume_while(&|next_char| next_char != ';');
+        if !name.eq_ignore_ascii_case("supports") {
+            self.warnings
+                .push(Warning::UnsupportedAtRule(format!("@{}", name)));
 
-            return cssom::NumericValue::Zero;
+            return cssom::Rulesets::new();
         }
 
-        if self.parser.next_char() == '%' {
-            self.parser.consume_char();
+        let mut parser = Self::from_values(condition.to_vec());
+        let condition = parser.parse_supports_condition();
+        parser.consume_whitespace();
 
-            return cssom::NumericValue::Percentage(number);
-        }
+        let condition = match condition {
+            Some(condition) if parser.eof() => condition,
+            _ => {
+                self.warnings.push(Warning::InvalidRule(prelude));
+
+                return cssom::Rulesets::new();
+            }
+        };
 
-        assert!(self.parser.starts_with("px"));
-        self.parser.consume_char();
-        self.parser.consume_char();
+        let rulesets = self.parse_style_block(Some(parent.clone()), block, prelude);
 
-        return cssom::NumericValue::Px(number);
+        return match supports::evaluate(&condition) {
+            true => rulesets,
+            false => cssom::Rulesets::new(),
+        };
     }
 
-    fn parse_string_value(&mut self) -> String {
-        return self.parser.consume_while(&|next_char| next_char != ';');
-    }
+    /// Parses an `@import` rule, like `@import "base.css" layer(base)
+    /// screen;`, and loads the stylesheet it imports.
+    fn parse_import_rule(&mut self) {
+        let start = self.pos;
+        self.consume_value();
 
-    fn parse_selectors(&mut self) -> cssom::Selectors {
-        let mut selectors = cssom::Selectors::new();
+        let prelude = self.consume_while(&|value| {
+            return !value.is_token(&Token::Semicolon)
+                && !matches!(value, ComponentValue::Block(Token::OpenCurly, _));
+        });
+        let end = self.pos;
+
+        let terminated = match self.consume_value() {
+            Some(ComponentValue::Block(_, _)) => false,
+            _ => true,
+        };
 
-        while self.parser.next_char() != '{' {
-            selectors.push(self.parse_selector());
-            self.parser.consume_whitespace();
+        let import = match terminated && self.imports_allowed {
+            true => Self::from_values(prelude).parse_import_prelude(),
+            false => None,
+        };
 
-            if self.parser.next_char() == ',' {
-                self.parser.consume_char();
-                self.parser.consume_whitespace();
+        match import {
+            Some(import) => self.import(import),
+            None => {
+                let rule = tokenizer::serialize(&self.values[start..end]);
+                self.warnings
+                    .push(Warning::InvalidRule(rule.trim().to_owned()));
             }
         }
-
-        return selectors;
     }
 
-    fn parse_selector(&mut self) -> cssom::Selector {
-        let mut selector = cssom::Selector::new();
-        let tag = self.consume_identifier();
+    /// Loads and parses an imported stylesheet. Its layers are declared
+    /// where it is imported, inside the layer it is imported into.
+    fn import(&mut self, import: cssom::Import) {
+        let base = self.urls.last().map_or("", |url| url.as_ref());
+        let url = loader::resolve_url(base, &import.url);
 
-        if tag.len() > 0 {
-            selector = selector.tag(&tag);
+        if self.urls.contains(&url) {
+            self.warnings.push(Warning::CyclicImport(url));
+            return;
         }
 
-        while !self.parser.eof() && self.parser.next_char() != ',' && self.parser.next_char() != '{'
-        {
-            match self.parser.next_char() {
-                '.' => {
-                    self.parser.consume_char();
-                    selector = selector.class(&self.consume_identifier());
-                }
+        let source = match self.loader.and_then(|loader| loader.load(&url)) {
+            Some(source) => source,
+            None => {
+                self.warnings.push(Warning::FailedImport(url));
+                return;
+            }
+        };
 
-                '#' => {
-                    self.parser.consume_char();
-                    selector = selector.id(&self.consume_identifier());
-                }
+        let layer = match import.layer {
+            Some(ref name) => Some(self.declare_layer(name)),
+            None => self.layer.clone(),
+        };
 
-                '[' => {
-                    self.parser.consume_char();
-                    self.parser.consume_whitespace();
+        let mut parser = Self::new(&source);
+        parser.loader = self.loader;
+        parser.urls = self.urls.clone();
+        parser.urls.push(url);
+        parser.layers = std::mem::take(&mut self.layers);
+        parser.layer = layer;
 
-                    let name = self.parser.consume_while(&|next_char| {
-                        return next_char != '=' && !next_char.is_whitespace();
-                    });
+        let rulesets = parser.parse_stylesheet();
 
-                    self.parser.consume_whitespace();
-                    assert!(self.parser.next_char() == '=');
-                    self.parser.consume_char();
-                    self.parser.consume_whitespace();
-                    assert!(self.parser.next_char() == '"');
-                    self.parser.consume_char();
+        self.layers = parser.layers;
+        self.warnings.append(&mut parser.warnings);
 
-                    let value = self.parser.consume_while(&|next_char| next_char != '"');
+        for ruleset in rulesets {
+            self.imported.push(match import.media.is_empty() {
+                true => ruleset,
+                false => ruleset.media(import.media.clone()),
+            });
+        }
+    }
 
-                    self.parser.consume_char();
-                    self.parser.consume_whitespace();
-                    assert!(self.parser.next_char() == ']');
-                    self.parser.consume_char();
+    fn parse_import_prelude(&mut self) -> Option<cssom::Import> {
+        self.consume_whitespace();
 
-                    selector = selector.attr(&name, &value);
+        let url = match self.consume_value()? {
+            ComponentValue::Token(Token::String(url)) | ComponentValue::Token(Token::Url(url)) => {
+                url
+            }
+            ComponentValue::Function(name, arguments) if name.eq_ignore_ascii_case("url") => {
+                match tokenizer::trim_whitespace(&arguments) {
+                    [ComponentValue::Token(Token::String(url))] => url.clone(),
+                    _ => return None,
                 }
+            }
+            _ => return None,
+        };
 
-                _ if self.parser.next_char().is_whitespace() => {
-                    self.parser.consume_whitespace();
-                    assert!(self.parser.next_char() == ',' || self.parser.next_char() == '{');
-                }
+        self.consume_whitespace();
 
-                _ => assert!(false),
+        let layer = match self.next_value() {
+            Some(value)
+                if value
+                    .ident()
+                    .map_or(false, |ident| ident.eq_ignore_ascii_case("layer")) =>
+            {
+                Some(String::new())
+            }
+            Some(ComponentValue::Function(name, arguments))
+                if name.eq_ignore_ascii_case("layer") =>
+            {
+                Some(parse_layer_name(arguments)?)
             }
+            _ => None,
+        };
+
+        if layer.is_some() {
+            self.consume_value();
         }
 
-        return selector;
-    }
+        let media = parse_media_query_list(&self.values[self.pos..]);
 
-    fn consume_identifier(&mut self) -> String {
-        return self.parser.consume_while(&|next_char| {
-            // This isn’t spec-compliant, as identifiers must start with an
-            // alphabetic char -- we’ll ignore that for now.
-            return next_char.is_alphanumeric() || next_char == '-' || next_char == '_';
+        return Some(cssom::Import {
+            url: url,
+            layer: layer,
+            media: media,
         });
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Parses an `@media` rule into the rulesets nested in it, which
+    /// carry its media query list.
+    fn parse_media_rule(&mut self) -> cssom::Rulesets {
+        let start = self.pos;
+        self.consume_value();
 
-    #[test]
-    fn test_parser_parse_rulesets() {
-        let mut parser = Parser::new("ul { padding-left: 10px; } p { font-family: serif; }");
-        let rulesets = parser.parse_rulesets();
+        let prelude = self.consume_while(&|value| {
+            return !value.is_token(&Token::Semicolon)
+                && !matches!(value, ComponentValue::Block(Token::OpenCurly, _));
+        });
+        let end = self.pos;
 
-        assert!(rulesets.len() == 2);
-        assert!(rulesets[0].selectors[0] == cssom::Selector::new().tag("ul"));
-        assert!(rulesets[1].selectors[0] == cssom::Selector::new().tag("p"));
-        assert!(parser.parser.eof());
-    }
+        let block = match self.consume_value() {
+            Some(ComponentValue::Block(Token::OpenCurly, block)) => block,
+            _ => {
+                let rule = tokenizer::serialize(&self.values[start..end]);
+                self.warnings
+                    .push(Warning::InvalidRule(rule.trim().to_owned()));
 
-    #[test]
-    fn test_parser_parse_rulesets_whitespace() {
-        let mut parser = Parser::new("    div { color: red; }");
-        let rulesets = parser.parse_rulesets();
+                return cssom::Rulesets::new();
+            }
+        };
 
-        assert!(rulesets.len() == 1);
+        let media = parse_media_query_list(&prelude);
+        let layer = self.layer.clone();
+
+        return self
+            .parse_nested_rulesets(block, layer)
+            .into_iter()
+            .map(|ruleset| ruleset.media(media.clone()))
+            .collect();
     }
 
-    #[test]
-    fn test_parser_parse_ruleset() {
-        let mut parser = Parser::new("ul { padding-left: 10px; list-style: square; }");
-        let ruleset = parser.parse_ruleset();
+    /// Parses an `@supports` rule into the rulesets nested in it, if the
+    /// engine supports its condition.
+    fn parse_supports_rule(&mut self) -> cssom::Rulesets {
+        let start = self.pos;
+        self.consume_value();
 
-        assert!(ruleset.selectors == cssom::Selectors::from([cssom::Selector::new().tag("ul")]));
-        assert!(ruleset.declarations.len() == 2);
-        assert!(parser.parser.eof());
-    }
+        let prelude = self.consume_while(&|value| {
+            return !value.is_token(&Token::Semicolon)
+                && !matches!(value, ComponentValue::Block(Token::OpenCurly, _));
+        });
+        let end = self.pos;
 
-    #[test]
-    fn test_parser_parse_declarations() {
-        let mut parser = Parser::new("ul { padding-left: 10px; list-style: square; }");
-        parser.parser.set_pos(4);
-        let declarations = parser.parse_declarations();
+        let block = match self.consume_value() {
+            Some(ComponentValue::Block(Token::OpenCurly, block)) => Some(block),
+            _ => None,
+        };
 
-        assert!(declarations.len() == 2);
-        assert!(declarations["padding-left"] == cssom::Value::Numeric(cssom::NumericValue::Px(10)));
-        assert!(declarations["list-style"] == cssom::Value::String("square".to_owned()));
-    }
+        let mut parser = Self::from_values(prelude);
+        let condition = parser.parse_supports_condition();
+        parser.consume_whitespace();
 
-    #[test]
-    fn test_parser_parse_declaration() {
-        let mut parser = Parser::new("padding-left: 10px;");
-        let (property, value) = parser.parse_declaration();
+        let (block, condition) = match (block, condition) {
+            (Some(block), Some(condition)) if parser.eof() => (block, condition),
+            _ => {
+                let rule = tokenizer::serialize(&self.values[start..end]);
+                self.warnings
+                    .push(Warning::InvalidRule(rule.trim().to_owned()));
 
-        assert!(property == "padding-left");
-        assert!(value == cssom::Value::Numeric(cssom::NumericValue::Px(10)));
+                return cssom::Rulesets::new();
+            }
+        };
+
+        let layer = self.layer.clone();
+        let rulesets = self.parse_nested_rulesets(block, layer);
+
+        return match supports::evaluate(&condition) {
+            true => rulesets,
+            false => cssom::Rulesets::new(),
+        };
     }
 
-    #[test]
-    fn test_parser_parse_value() {
-        assert!(Parser::new("0").parse_value() == cssom::Value::Numeric(cssom::NumericValue::Zero));
-        assert!(Parser::new("auto").parse_value() == cssom::Value::String("auto".to_owned()));
+    /// Parses an `@layer` rule. The statement form, like `@layer reset,
+    /// components;`, only declares layers to fix their order, the block
+    /// form puts the rulesets in it in a layer, an anonymous one if it
+    /// has no name.
+    fn parse_layer_rule(&mut self) -> cssom::Rulesets {
+        let start = self.pos;
+        self.consume_value();
+
+        let prelude = self.consume_while(&|value| {
+            return !value.is_token(&Token::Semicolon)
+                && !matches!(value, ComponentValue::Block(Token::OpenCurly, _));
+        });
+        let end = self.pos;
+
+        let block = match self.consume_value() {
+            Some(ComponentValue::Block(Token::OpenCurly, block)) => Some(block),
+            _ => None,
+        };
+
+        let prelude = tokenizer::trim_whitespace(&prelude);
+        let names: Option<Vec<String>> = match prelude.is_empty() {
+            true => Some(Vec::new()),
+            false => prelude
+                .split(|value| value.is_token(&Token::Comma))
+                .map(parse_layer_name)
+                .collect(),
+        };
+
+        match (block, names) {
+            (None, Some(names)) if names.len() > 0 => {
+                for name in names {
+                    self.declare_layer(&name);
+                }
+
+                return cssom::Rulesets::new();
+            }
+            (Some(block), Some(names)) if names.len() <= 1 => {
+                self.imports_allowed = false;
+
+                let name = names.first().map_or("", |name| name.as_ref());
+                let layer = self.declare_layer(name);
+
+                return self.parse_nested_rulesets(block, Some(layer));
+            }
+            _ => {
+                self.imports_allowed = false;
+
+                let rule = tokenizer::serialize(&self.values[start..end]);
+                self.warnings
+                    .push(Warning::InvalidRule(rule.trim().to_owned()));
+
+                return cssom::Rulesets::new();
+            }
+        }
     }
 
-    #[test]
-    fn test_parser_parse_color_value() {
-        assert!(Parser::new("#aabbcc").parse_color_value() == cssom::Color::new(0xaa, 0xbb, 0xcc));
-        assert!(Parser::new("#abc").parse_color_value() == cssom::Color::new(0xaa, 0xbb, 0xcc));
+    /// Declares a layer inside the current one, by its dot-separated name,
+    /// and returns it. Layers that were declared before keep their
+    /// position, anonymous layers are new every time.
+    fn declare_layer(&mut self, name: &str) -> cssom::Layer {
+        let mut layer = self.layer.clone().unwrap_or(cssom::Layer::root());
+
+        for name in name.split('.') {
+            let existing = self.layers.iter().find(|other| {
+                return other.is_child_of(&layer)
+                    && !name.is_empty()
+                    && other.names.last().map_or(false, |other| other == name);
+            });
+
+            layer = match existing {
+                Some(existing) => existing.clone(),
+                None => {
+                    let position = self
+                        .layers
+                        .iter()
+                        .filter(|other| other.is_child_of(&layer))
+                        .count();
+                    let child = layer.child(name, position);
+                    self.layers.push(child.clone());
+
+                    child
+                }
+            };
+        }
+
+        return layer;
     }
 
-    #[test]
-    fn test_parser_parse_numeric_value() {
-        assert!(Parser::new("0").parse_numeric_value() == cssom::NumericValue::Zero);
-        assert!(Parser::new("10px").parse_numeric_value() == cssom::NumericValue::Px(10));
-        assert!(Parser::new("50%").parse_numeric_value() == cssom::NumericValue::Percentage(50));
+    /// Parses the rulesets in the block of a conditional or `@layer` rule,
+    /// putting them in the given layer.
+    fn parse_nested_rulesets(
+        &mut self,
+        block: ComponentValues,
+        layer: Option<cssom::Layer>,
+    ) -> cssom::Rulesets {
+        let mut parser = Self::from_values(block);
+        parser.layers = std::mem::take(&mut self.layers);
+        parser.layer = layer;
+
+        let rulesets = parser.parse_rulesets();
+
+        self.layers = parser.layers;
+        self.warnings.append(&mut parser.warnings);
+
+        return rulesets;
     }
 
-    #[test]
-    fn test_parser_parse_string_value() {
-        assert!(Parser::new("auto").parse_string_value() == "auto");
+    fn consume_at_rule(&mut self) {
+        while let Some(value) = self.consume_value() {
+            match value {
+                ComponentValue::Token(Token::Semicolon) => return,
+                ComponentValue::Block(Token::OpenCurly, _) => return,
+                _ => (),
+            }
+        }
     }
 
-    #[test]
-    fn test_parser_parse_selectors() {
-        let mut parser = Parser::new("ul, ol { padding-left: 10px; }");
-        let selectors = parser.parse_selectors();
+    /// Parses all declarations in a block. Invalid declarations are skipped
+    /// up to the next semicolon.
+    fn parse_declarations(&mut self) -> cssom::Declarations {
+        let mut declarations = cssom::Declarations::new();
 
-        assert!(selectors.len() == 2);
-        assert!(selectors[0] == cssom::Selector::new().tag("ul"));
-        assert!(selectors[1] == cssom::Selector::new().tag("ol"));
+        loop {
+            self.consume_while(&|value| {
+                return value.is_whitespace() || value.is_token(&Token::Semicolon);
+            });
 
-        assert!(
-            selectors
-                == cssom::Selectors::from([
-                    cssom::Selector::new().tag("ul"),
-                    cssom::Selector::new().tag("ol"),
-                ])
-        );
-    }
+            if self.eof() {
+                break;
+            }
 
-    #[test]
-    fn test_parser_parse_selector() {
-        let mut parser = Parser::new("ul, ol { padding-left: 10px; }");
-        let selector = parser.parse_selector();
+            let start = self.pos;
+            let end = self.declaration_value_end();
 
-        assert!(selector == cssom::Selector::new().tag("ul"));
+            if let Some(block_pos) = self.nested_rule_block(end) {
+                self.nested_rules
+                    .push(self.values[start..block_pos + 1].to_vec());
+                self.pos = block_pos + 1;
+                continue;
+            }
 
-        // Trims whitespace
-        let mut parser = Parser::new("ul { padding-left: 10px; }");
-        let selector = parser.parse_selector();
+            let mut parser = Self::from_values(self.values[start..end].to_vec());
+            self.pos = end;
 
-        assert!(selector == cssom::Selector::new().tag("ul"));
+            match parser.parse_expanded_declaration() {
+                Some(expanded) => {
+                    for declaration in expanded {
+                        declarations.push(declaration);
+                    }
+                }
+                None => {
+                    let source = tokenizer::serialize(&self.values[start..end]);
+                    self.warnings
+                        .push(Warning::InvalidDeclaration(source.trim().to_owned()));
+                }
+            }
+        }
+
+        return declarations;
     }
 
-    #[test]
-    fn test_parser_parse_selector_class() {
-        let mut parser = Parser::new("p.class1.class2 { color: #333; }");
-        let selector = parser.parse_selector();
-        let expected = cssom::Selector::new()
-            .tag("p")
-            .class("class1")
-            .class("class2");
+    /// Parses a declaration, expanding shorthand properties into their
+    /// longhands.
+    fn parse_expanded_declaration(&mut self) -> Option<Vec<cssom::Declaration>> {
+        let start = self.pos;
+        let property = self.parse_property_name()?;
+        let end = self.declaration_value_end();
 
-        assert!(selector == expected);
+        if variables::is_custom_property(&property)
+            || variables::contains_var(&self.values[self.pos..end])
+        {
+            return self.parse_unparsed_declaration(&property);
+        }
 
-        let mut parser = Parser::new(".class-1.class_2 { color: #333; }");
-        let selector = parser.parse_selector();
-        let expected = cssom::Selector::new().class("class-1").class("class_2");
+        if !shorthand::is_shorthand(&property) {
+            self.pos = start;
 
-        assert!(selector == expected);
-    }
+            return Some(Vec::from([self.parse_declaration()?]));
+        }
 
-    #[test]
-    fn test_parser_parse_selector_id() {
-        let mut parser = Parser::new("p#intro { font-weight: italic; }");
-        let selector = parser.parse_selector();
-        let expected = cssom::Selector::new().tag("p").id("intro");
+        self.consume_whitespace();
 
-        assert!(selector == expected);
+        let important = self.consume_important();
+        let end = self.declaration_value_end();
 
-        let mut parser = Parser::new("#intro { font-weight: italic; }");
-        let selector = parser.parse_selector();
-        let expected = cssom::Selector::new().id("intro");
+        if !self.values[self.pos..end].iter().all(is_valid_in_value) {
+            return None;
+        }
 
-        assert!(selector == expected);
+        let mut declarations = Vec::new();
+
+        for (longhand, values) in shorthand::expand(&property, &self.values[self.pos..end])? {
+            let value = Self::from_values(values).parse_value()?;
+
+            if !is_in_range(&longhand, &value) {
+                return None;
+            }
+
+            let declaration = cssom::Declaration::new(&longhand, value);
+
+            declarations.push(match important {
+                true => declaration.important(),
+                false => declaration,
+            });
+        }
+
+        self.pos = end;
+
+        return Some(declarations);
     }
 
-    #[test]
-    fn test_parser_parse_selector_attrs() {
-        let mut parser = Parser::new("button[aria-expanded=\"true\"]");
-        let selector = parser.parse_selector();
-        let expected = cssom::Selector::new()
-            .tag("button")
-            .attr("aria-expanded", "true");
+    /// Keeps the value of a custom property, or of a declaration using
+    /// `var()`, as component values until its computed value is known.
+    fn parse_unparsed_declaration(&mut self, property: &str) -> Option<Vec<cssom::Declaration>> {
+        let important = self.consume_important();
+        let end = self.declaration_value_end();
+        let values = tokenizer::trim_whitespace(&self.values[self.pos..end]).to_vec();
 
-        assert!(selector == expected);
+        self.pos = end;
 
-        let mut parser = Parser::new("button[ aria-expanded  =  \"true\" ]");
-        let selector = parser.parse_selector();
+        let value = match variables::is_custom_property(property) {
+            true if values.iter().all(is_valid_in_custom_property) => cssom::Value::Tokens(values),
+            false if !values.is_empty() && values.iter().all(is_valid_in_value) => {
+                cssom::Value::Pending(property.to_owned(), values)
+            }
+            _ => return None,
+        };
 
-        assert!(selector == expected);
+        let properties = match shorthand::is_shorthand(property) {
+            true => shorthand::longhand_names(property),
+            false => Vec::from([property.to_owned()]),
+        };
+
+        let declarations = properties
+            .iter()
+            .map(|property| {
+                let declaration = cssom::Declaration::new(property, value.clone());
+
+                return match important {
+                    true => declaration.important(),
+                    false => declaration,
+                };
+            })
+            .collect();
+
+        return Some(declarations);
+    }
+
+    /// Parses the value of a declaration once `var()` has been substituted.
+    /// `declared` is the property it was declared for, which is a shorthand
+    /// for the longhands it sets.
+    pub fn parse_substituted_value(
+        property: &str,
+        declared: &str,
+        values: ComponentValues,
+    ) -> Option<cssom::Value> {
+        let values = match property == declared {
+            true => values,
+            false => {
+                let (_, values) = shorthand::expand(declared, &values)?
+                    .into_iter()
+                    .find(|(longhand, _)| longhand == property)?;
+
+                values
+            }
+        };
+
+        return Self::from_values(values)
+            .parse_value()
+            .filter(|value| is_in_range(property, value));
+    }
+
+    fn parse_declaration(&mut self) -> Option<cssom::Declaration> {
+        let property = self.parse_property_name()?;
+
+        self.consume_whitespace();
+
+        let important = self.consume_important();
+        let value = self.parse_value()?;
+
+        if !is_in_range(&property, &value) {
+            return None;
+        }
+
+        // The last declaration in a block doesn’t need a semicolon
+        if !self.eof() && self.consume_value() != Some(ComponentValue::Token(Token::Semicolon)) {
+            return None;
+        }
+
+        let declaration = cssom::Declaration::new(&property, value);
+
+        return match important {
+            true => Some(declaration.important()),
+            false => Some(declaration),
+        };
+    }
+
+    /// Parses a property name and the colon following it.
+    fn parse_property_name(&mut self) -> Option<String> {
+        self.consume_whitespace();
+
+        let mut property = self.consume_identifier();
+
+        if property.len() == 0 {
+            return None;
+        }
+
+        // Custom property names are case-sensitive, all others aren’t
+        if !property.starts_with("--") {
+            property = property.to_ascii_lowercase();
+        }
+
+        self.consume_whitespace();
+
+        if self.consume_value() != Some(ComponentValue::Token(Token::Colon)) {
+            return None;
+        }
+
+        return Some(property);
+    }
+
+    /// Removes a trailing `!important` from the declaration value and
+    /// returns whether there was one.
+    fn consume_important(&mut self) -> bool {
+        let end = self.declaration_value_end();
+        let mut significant = (self.pos..end)
+            .rev()
+            .filter(|&index| !self.values[index].is_whitespace());
+
+        let (important, bang) = match (significant.next(), significant.next()) {
+            (Some(important), Some(bang)) => (important, bang),
+            _ => return false,
+        };
+
+        let is_important = self.values[important]
+            .ident()
+            .map_or(false, |ident| ident.eq_ignore_ascii_case("important"));
+
+        if !is_important || !self.values[bang].is_delim('!') {
+            return false;
+        }
+
+        self.values.drain(bang..=important);
+
+        return true;
+    }
+
+    fn parse_value(&mut self) -> Option<cssom::Value> {
+        self.consume_whitespace();
+
+        let end = self.declaration_value_end();
+        let values = self.values[self.pos..end].to_vec();
+
+        if values.is_empty() || !values.iter().all(is_valid_in_value) {
+            return None;
+        }
+
+        self.pos = end;
+
+        let mut values = Self::from_values(values).parse_comma_separated_values()?;
+
+        return match values.len() {
+            1 => values.pop(),
+            _ => Some(cssom::Value::CommaList(values)),
+        };
+    }
+
+    /// Parses values separated by commas up to the end of the input. Each
+    /// of them may be a list of values separated by whitespace.
+    fn parse_comma_separated_values(&mut self) -> Option<cssom::Values> {
+        let mut values = cssom::Values::new();
+
+        self.consume_whitespace();
+
+        if self.eof() {
+            return Some(values);
+        }
+
+        loop {
+            let mut items = cssom::Values::new();
+
+            self.consume_whitespace();
+
+            while !self.eof() && !self.next_is(&Token::Comma) {
+                items.push(self.parse_single_value()?);
+                self.consume_whitespace();
+            }
+
+            values.push(match items.len() {
+                0 => return None,
+                1 => items.pop().unwrap(),
+                _ => cssom::Value::SpaceList(items),
+            });
+
+            // Either a comma or the end of the input
+            if self.consume_value().is_none() {
+                break;
+            }
+        }
+
+        return Some(values);
+    }
+
+    fn parse_single_value(&mut self) -> Option<cssom::Value> {
+        let value = match self.next_value()? {
+            ComponentValue::Token(
+                Token::Number(_) | Token::Percentage(_) | Token::Dimension(..),
+            ) => return Some(cssom::Value::Numeric(self.parse_numeric_value()?)),
+
+            ComponentValue::Token(Token::Hash(..)) => {
+                return Some(cssom::Value::Color(self.parse_color_value()?));
+            }
+
+            ComponentValue::Function(name, _) if is_color_function(name) => {
+                return Some(cssom::Value::Color(self.parse_color_value()?));
+            }
+
+            ComponentValue::Token(Token::Ident(ident))
+                if ident.eq_ignore_ascii_case("currentcolor") =>
+            {
+                cssom::Value::CurrentColor
+            }
+
+            ComponentValue::Token(Token::Ident(ident)) => match cssom::Color::from_name(ident) {
+                Some(color) => cssom::Value::Color(color),
+                None => cssom::Value::Ident(ident.clone()),
+            },
+            ComponentValue::Token(Token::String(string)) => cssom::Value::String(string.clone()),
+            ComponentValue::Token(Token::Url(url)) => cssom::Value::Url(url.clone()),
+            ComponentValue::Token(Token::Delim(delim)) => cssom::Value::Delim(*delim),
+
+            ComponentValue::Function(name, arguments) if is_math_function(name) => {
+                let calculation = parse_math_function(name, arguments)?;
+                calculation.resolved_type()?;
+
+                cssom::Value::Numeric(cssom::NumericValue::Calc(Box::new(calculation)))
+            }
+
+            ComponentValue::Function(name, arguments) => {
+                let arguments =
+                    Self::from_values(arguments.clone()).parse_comma_separated_values()?;
+
+                // `url("a.png")` is the same as `url(a.png)`
+                match &arguments[..] {
+                    [cssom::Value::String(url)] if name.eq_ignore_ascii_case("url") => {
+                        cssom::Value::Url(url.clone())
+                    }
+                    _ => cssom::Value::Function(name.clone(), arguments),
+                }
+            }
+
+            _ => return None,
+        };
+
+        self.pos += 1;
+
+        return Some(value);
+    }
+
+    /// Parses a hex color, a named color or a color function.
+    fn parse_color_value(&mut self) -> Option<cssom::Color> {
+        let color = match self.consume_value()? {
+            ComponentValue::Token(Token::Hash(hex, _)) => parse_hex_color(&hex)?,
+            ComponentValue::Token(Token::Ident(name)) => cssom::Color::from_name(&name)?,
+            ComponentValue::Function(name, arguments) => parse_color_function(&name, &arguments)?,
+            _ => return None,
+        };
+
+        self.consume_whitespace();
+
+        return Some(color);
+    }
+
+    fn parse_numeric_value(&mut self) -> Option<cssom::NumericValue> {
+        let value = self.consume_value();
+
+        self.consume_whitespace();
+
+        let (number, unit) = match value {
+            Some(ComponentValue::Token(Token::Number(number))) => (number, ""),
+            Some(ComponentValue::Token(Token::Percentage(number))) => (number, "%"),
+            Some(ComponentValue::Token(Token::Dimension(number, ref unit))) => (number, &unit[..]),
+            _ => return None,
+        };
+
+        if number.value == 0.0 {
+            return Some(cssom::NumericValue::Zero);
+        }
+
+        if unit == "" {
+            return Some(cssom::NumericValue::Number(number.value));
+        }
+
+        if unit == "%" {
+            return Some(cssom::NumericValue::Percentage(number.value));
+        }
+
+        let unit = match unit.to_ascii_lowercase().as_ref() {
+            "px" => return Some(cssom::NumericValue::Px(number.value)),
+            "em" => cssom::LengthUnit::Em,
+            "rem" => cssom::LengthUnit::Rem,
+            "ex" => cssom::LengthUnit::Ex,
+            "ch" => cssom::LengthUnit::Ch,
+            "vw" => cssom::LengthUnit::Vw,
+            "vh" => cssom::LengthUnit::Vh,
+            "vmin" => cssom::LengthUnit::Vmin,
+            "vmax" => cssom::LengthUnit::Vmax,
+            "pt" => cssom::LengthUnit::Pt,
+            "cm" => cssom::LengthUnit::Cm,
+            "mm" => cssom::LengthUnit::Mm,
+            "in" => cssom::LengthUnit::In,
+            _ => return None,
+        };
+
+        return Some(cssom::NumericValue::Length(number.value, unit));
+    }
+
+    /// Parses the argument of a math function, like `100% - 2 * 16px`.
+    fn parse_calculation(&mut self) -> Option<cssom::Calculation> {
+        let calculation = self.parse_calculation_sum()?;
+
+        self.consume_whitespace();
+
+        if !self.eof() {
+            return None;
+        }
+
+        return Some(calculation);
+    }
+
+    fn parse_calculation_sum(&mut self) -> Option<cssom::Calculation> {
+        let mut sum = self.parse_calculation_product()?;
+
+        // `+` and `-` need whitespace around them, otherwise they are
+        // tokenized as the sign of the following number
+        loop {
+            let operator = match self.next_value() {
+                Some(value) if value.is_delim('+') => '+',
+                Some(value) if value.is_delim('-') => '-',
+                _ => break,
+            };
+
+            self.pos += 1;
+
+            let left = Box::new(sum);
+            let right = Box::new(self.parse_calculation_product()?);
+
+            sum = match operator {
+                '+' => cssom::Calculation::Add(left, right),
+                _ => cssom::Calculation::Subtract(left, right),
+            };
+        }
+
+        return Some(sum);
+    }
+
+    fn parse_calculation_product(&mut self) -> Option<cssom::Calculation> {
+        let mut product = self.parse_calculation_value()?;
+
+        loop {
+            let operator = match self.next_value() {
+                Some(value) if value.is_delim('*') => '*',
+                Some(value) if value.is_delim('/') => '/',
+                _ => break,
+            };
+
+            self.pos += 1;
+
+            let left = Box::new(product);
+            let right = Box::new(self.parse_calculation_value()?);
+
+            product = match operator {
+                '*' => cssom::Calculation::Multiply(left, right),
+                _ => cssom::Calculation::Divide(left, right),
+            };
+        }
+
+        return Some(product);
+    }
+
+    /// Parses a number, a length, a percentage, a nested math function or
+    /// an expression in parentheses, and the whitespace around it.
+    fn parse_calculation_value(&mut self) -> Option<cssom::Calculation> {
+        self.consume_whitespace();
+
+        let value = match self.next_value()? {
+            // Unitless zeros are numbers in math functions, not lengths
+            ComponentValue::Token(Token::Number(number)) => {
+                let number = number.value;
+                self.pos += 1;
+
+                cssom::Calculation::Value(cssom::NumericValue::Number(number))
+            }
+
+            ComponentValue::Token(Token::Percentage(_)) => match self.parse_numeric_value()? {
+                cssom::NumericValue::Zero => {
+                    cssom::Calculation::Value(cssom::NumericValue::Percentage(0.0))
+                }
+                value => cssom::Calculation::Value(value),
+            },
+
+            ComponentValue::Token(Token::Dimension(..)) => match self.parse_numeric_value()? {
+                cssom::NumericValue::Zero => {
+                    cssom::Calculation::Value(cssom::NumericValue::Px(0.0))
+                }
+                value => cssom::Calculation::Value(value),
+            },
+
+            ComponentValue::Block(Token::OpenParen, values) => {
+                let values = values.clone();
+                self.pos += 1;
+
+                Self::from_values(values).parse_calculation()?
+            }
+
+            ComponentValue::Function(name, arguments) if is_math_function(name) => {
+                let calculation = parse_math_function(name, arguments)?;
+                self.pos += 1;
+
+                calculation
+            }
+
+            _ => return None,
+        };
+
+        self.consume_whitespace();
+
+        return Some(value);
+    }
+
+    /// Parses a comma-separated selector list. If any of the selectors is
+    /// invalid, the whole list is.
+    fn parse_selectors(&mut self) -> Option<cssom::Selectors> {
+        let mut selectors = cssom::Selectors::new();
+
+        loop {
+            self.consume_whitespace();
+            selectors.push(self.parse_selector()?);
+            self.consume_whitespace();
+
+            if !self.next_is(&Token::Comma) {
+                break;
+            }
+
+            self.consume_value();
+        }
+
+        if !self.eof() && !self.next_is_declaration_block() {
+            return None;
+        }
+
+        return Some(selectors);
+    }
+
+    /// Parses a complex selector: compound selectors joined by
+    /// combinators.
+    fn parse_selector(&mut self) -> Option<cssom::Selector> {
+        let mut selector = self.parse_compound_selector()?;
+
+        loop {
+            let whitespace = self.consume_while(&|value| value.is_whitespace());
+
+            if self.eof() || self.next_is(&Token::Comma) || self.next_is_declaration_block() {
+                break;
+            }
+
+            let combinator = match self.next_value() {
+                Some(value) if value.is_delim('>') => cssom::Combinator::Child,
+                Some(value) if value.is_delim('+') => cssom::Combinator::NextSibling,
+                Some(value) if value.is_delim('~') => cssom::Combinator::SubsequentSibling,
+                _ if whitespace.len() > 0 => cssom::Combinator::Descendant,
+                _ => return None,
+            };
+
+            // Pseudo-elements end a selector
+            if selector.pseudo_element.is_some() {
+                return None;
+            }
+
+            if combinator != cssom::Combinator::Descendant {
+                self.consume_value();
+                self.consume_whitespace();
+            }
+
+            selector = selector.combine(combinator, self.parse_compound_selector()?);
+        }
+
+        return Some(selector);
+    }
+
+    fn parse_compound_selector(&mut self) -> Option<cssom::Selector> {
+        let mut selector = cssom::Selector::new();
+        let start = self.pos;
+
+        if self.next_value().map_or(false, |value| value.is_delim('*')) {
+            self.consume_value();
+            selector = selector.universal();
+        }
+
+        let tag = if selector.universal {
+            String::new()
+        } else {
+            self.consume_identifier()
+        };
+
+        if tag.len() > 0 {
+            selector = selector.tag(&tag);
+        }
+
+        loop {
+            match self.next_value() {
+                Some(ComponentValue::Token(Token::Delim('.'))) => {
+                    self.consume_value();
+                    let class = self.consume_identifier();
+
+                    if class.len() == 0 {
+                        return None;
+                    }
+
+                    selector = selector.class(&class);
+                }
+
+                Some(ComponentValue::Token(Token::Hash(id, HashType::Id))) => {
+                    selector = selector.id(id);
+                    self.consume_value();
+                }
+
+                // The nesting selector matches what the parent rule’s
+                // selectors match. Outside of nested rules, it is the root.
+                Some(ComponentValue::Token(Token::Delim('&'))) => {
+                    self.consume_value();
+                    selector = selector.pseudo_class(match self.nesting {
+                        Some(ref parent) => cssom::PseudoClass::Is(parent.clone()),
+                        None => cssom::PseudoClass::Root,
+                    });
+                }
+
+                Some(ComponentValue::Block(Token::OpenSquare, block)) => {
+                    let attr = Self::from_values(block.clone()).parse_attribute_selector()?;
+                    selector = selector.attr_selector(attr);
+                    self.consume_value();
+                }
+
+                Some(ComponentValue::Token(Token::Colon)) => {
+                    self.consume_value();
+
+                    // `::before`, or `:before` in the legacy single-colon syntax
+                    let legacy = !self.next_is(&Token::Colon);
+
+                    if !legacy {
+                        self.consume_value();
+                    }
+
+                    if let Some(pseudo_element) = self.parse_pseudo_element() {
+                        selector = selector.pseudo_element(pseudo_element);
+                        break;
+                    }
+
+                    if !legacy {
+                        return None;
+                    }
+
+                    selector = selector.pseudo_class(self.parse_pseudo_class()?);
+                }
+
+                _ => break,
+            }
+        }
+
+        // Empty selectors, like in `ul, , ol`, are invalid
+        if self.pos == start {
+            return None;
+        }
+
+        return Some(selector);
+    }
+
+    /// Parses a pseudo-element name, leaving the input untouched if the
+    /// next value isn’t one.
+    fn parse_pseudo_element(&mut self) -> Option<cssom::PseudoElement> {
+        let pseudo_element = match self.next_value().and_then(|value| value.ident()) {
+            Some(name) if name.eq_ignore_ascii_case("before") => cssom::PseudoElement::Before,
+            Some(name) if name.eq_ignore_ascii_case("after") => cssom::PseudoElement::After,
+            _ => return None,
+        };
+
+        self.consume_value();
+
+        return Some(pseudo_element);
+    }
+
+    /// Parses a pseudo-class following a colon.
+    fn parse_pseudo_class(&mut self) -> Option<cssom::PseudoClass> {
+        return match self.consume_value()? {
+            ComponentValue::Token(Token::Ident(name)) => match &name.to_ascii_lowercase()[..] {
+                "root" => Some(cssom::PseudoClass::Root),
+                "empty" => Some(cssom::PseudoClass::Empty),
+                "first-child" => Some(cssom::PseudoClass::FirstChild),
+                "last-child" => Some(cssom::PseudoClass::LastChild),
+                "only-child" => Some(cssom::PseudoClass::OnlyChild),
+                "first-of-type" => Some(cssom::PseudoClass::FirstOfType),
+                "last-of-type" => Some(cssom::PseudoClass::LastOfType),
+                "only-of-type" => Some(cssom::PseudoClass::OnlyOfType),
+                "hover" => Some(cssom::PseudoClass::Hover),
+                "active" => Some(cssom::PseudoClass::Active),
+                "focus" => Some(cssom::PseudoClass::Focus),
+                "focus-visible" => Some(cssom::PseudoClass::FocusVisible),
+                _ => None,
+            },
+
+            ComponentValue::Function(name, arguments) => match &name.to_ascii_lowercase()[..] {
+                "nth-child" => {
+                    let (nth, of) = parse_nth_arguments(&arguments, self.nesting.as_ref())?;
+                    Some(cssom::PseudoClass::NthChild(nth, of))
+                }
+                "nth-last-child" => {
+                    let (nth, of) = parse_nth_arguments(&arguments, self.nesting.as_ref())?;
+                    Some(cssom::PseudoClass::NthLastChild(nth, of))
+                }
+                "nth-of-type" => Some(cssom::PseudoClass::NthOfType(parse_nth(&arguments)?)),
+                "nth-last-of-type" => {
+                    Some(cssom::PseudoClass::NthLastOfType(parse_nth(&arguments)?))
+                }
+                "not" => Some(cssom::PseudoClass::Not(parse_selector_list(
+                    &arguments,
+                    false,
+                    self.nesting.as_ref(),
+                )?)),
+                "is" => Some(cssom::PseudoClass::Is(parse_selector_list(
+                    &arguments,
+                    true,
+                    self.nesting.as_ref(),
+                )?)),
+                "where" => Some(cssom::PseudoClass::Where(parse_selector_list(
+                    &arguments,
+                    true,
+                    self.nesting.as_ref(),
+                )?)),
+                "has" => Some(cssom::PseudoClass::Has(parse_relative_selector_list(
+                    &arguments,
+                    self.nesting.as_ref(),
+                )?)),
+                _ => None,
+            },
+
+            _ => None,
+        };
+    }
+
+    /// Parses the inside of an attribute selector, like `name="value" i`.
+    fn parse_attribute_selector(&mut self) -> Option<cssom::AttributeSelector> {
+        self.consume_whitespace();
+        let name = self.consume_identifier();
+
+        if name.len() == 0 {
+            return None;
+        }
+
+        self.consume_whitespace();
+
+        if self.eof() {
+            return Some(cssom::AttributeSelector::exists(&name));
+        }
+
+        let operator = match self.consume_value()? {
+            value if value.is_delim('=') => cssom::AttributeOperator::Equals,
+            ComponentValue::Token(Token::Delim(c)) => {
+                // All other operators are two delimiters, like `~=`
+                if !self.next_value().map_or(false, |value| value.is_delim('=')) {
+                    return None;
+                }
+
+                self.consume_value();
+
+                match c {
+                    '~' => cssom::AttributeOperator::Includes,
+                    '|' => cssom::AttributeOperator::DashMatch,
+                    '^' => cssom::AttributeOperator::Prefix,
+                    '$' => cssom::AttributeOperator::Suffix,
+                    '*' => cssom::AttributeOperator::Substring,
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+
+        self.consume_whitespace();
+
+        let value = match self.consume_value() {
+            Some(ComponentValue::Token(Token::String(value) | Token::Ident(value))) => value,
+            _ => return None,
+        };
+
+        self.consume_whitespace();
+
+        let flag = self.consume_identifier().to_ascii_lowercase();
+        let case_sensitivity = match &flag[..] {
+            "" => cssom::CaseSensitivity::Default,
+            "i" => cssom::CaseSensitivity::Insensitive,
+            "s" => cssom::CaseSensitivity::Sensitive,
+            _ => return None,
+        };
+
+        self.consume_whitespace();
+
+        if !self.eof() {
+            return None;
+        }
+
+        let attr = cssom::AttributeSelector::new(&name, operator, &value);
+
+        return Some(attr.case_sensitivity(case_sensitivity));
+    }
+
+    /// Parses a single media query, like `screen and (min-width: 600px)`
+    /// or `not print`. Queries starting with a parenthesis only consist of
+    /// a condition.
+    fn parse_media_query(&mut self) -> Option<cssom::MediaQuery> {
+        self.consume_whitespace();
+
+        let starts_with_condition = match self.next_value() {
+            Some(ComponentValue::Block(Token::OpenParen, _)) => true,
+            Some(value)
+                if value
+                    .ident()
+                    .map_or(false, |ident| ident.eq_ignore_ascii_case("not")) =>
+            {
+                let next = self.values[self.pos + 1..]
+                    .iter()
+                    .find(|value| !value.is_whitespace());
+                matches!(next, Some(ComponentValue::Block(Token::OpenParen, _)))
+            }
+            _ => false,
+        };
+
+        if starts_with_condition {
+            let condition = self.parse_media_condition(true)?;
+            return Some(cssom::MediaQuery::new(cssom::MediaType::All).condition(condition));
+        }
+
+        let mut media_type = self.consume_identifier().to_ascii_lowercase();
+        let mut negated = false;
+
+        if media_type == "not" || media_type == "only" {
+            negated = media_type == "not";
+            self.consume_whitespace();
+            media_type = self.consume_identifier().to_ascii_lowercase();
+        }
+
+        let media_type = match media_type.as_ref() {
+            "all" => cssom::MediaType::All,
+            "screen" => cssom::MediaType::Screen,
+            "print" => cssom::MediaType::Print,
+            "" | "not" | "only" | "and" | "or" | "layer" => return None,
+            _ => cssom::MediaType::Other(media_type),
+        };
+
+        let query = cssom::MediaQuery::new(media_type).negated(negated);
+
+        self.consume_whitespace();
+
+        if self.eof() {
+            return Some(query);
+        }
+
+        if !self.consume_identifier().eq_ignore_ascii_case("and") {
+            return None;
+        }
+
+        return Some(query.condition(self.parse_media_condition(false)?));
+    }
+
+    /// Parses a media condition: a negated condition in parentheses, or
+    /// conditions in parentheses joined by either `and` or `or`. After a
+    /// media type, `or` isn’t allowed.
+    fn parse_media_condition(&mut self, allow_or: bool) -> Option<cssom::MediaCondition> {
+        self.consume_whitespace();
+
+        if self
+            .next_value()
+            .and_then(|value| value.ident())
+            .map_or(false, |ident| ident.eq_ignore_ascii_case("not"))
+        {
+            self.consume_value();
+            self.consume_whitespace();
+
+            let condition = self.parse_media_in_parens()?;
+            return Some(cssom::MediaCondition::Not(Box::new(condition)));
+        }
+
+        let mut conditions = vec![self.parse_media_in_parens()?];
+        let mut operator = String::new();
+
+        loop {
+            self.consume_whitespace();
+
+            if self.eof() {
+                break;
+            }
+
+            let keyword = self.consume_identifier().to_ascii_lowercase();
+
+            let valid = match keyword.as_ref() {
+                "and" => operator != "or",
+                "or" => allow_or && operator != "and",
+                _ => false,
+            };
+
+            if !valid {
+                return None;
+            }
+
+            operator = keyword;
+            self.consume_whitespace();
+            conditions.push(self.parse_media_in_parens()?);
+        }
+
+        return match operator.as_ref() {
+            "and" => Some(cssom::MediaCondition::And(conditions)),
+            "or" => Some(cssom::MediaCondition::Or(conditions)),
+            _ => conditions.pop(),
+        };
+    }
+
+    /// Parses a condition or a media feature in parentheses. Anything else
+    /// in parentheses, or a function, is kept but never matches.
+    fn parse_media_in_parens(&mut self) -> Option<cssom::MediaCondition> {
+        let value = self.consume_value()?;
+
+        let values = match value {
+            ComponentValue::Block(Token::OpenParen, ref values) => values.clone(),
+            ComponentValue::Function(_, _) => {
+                let source = tokenizer::serialize(&[value]);
+                return Some(cssom::MediaCondition::Unknown(source));
+            }
+            _ => return None,
+        };
+
+        let mut parser = Self::from_values(values.clone());
+
+        if let Some(condition) = parser.parse_media_condition(true) {
+            return Some(condition);
+        }
+
+        let mut parser = Self::from_values(values);
+
+        if let Some(condition) = parser.parse_media_feature() {
+            parser.consume_whitespace();
+
+            if parser.eof() {
+                return Some(condition);
+            }
+        }
+
+        let source = tokenizer::serialize(&[value]);
+
+        return Some(cssom::MediaCondition::Unknown(source));
+    }
+
+    /// Parses a media feature, like `(color)`, `(min-width: 600px)`,
+    /// `(width >= 600px)` or `(400px < width <= 800px)`. The last one is
+    /// turned into two features joined by `and`.
+    fn parse_media_feature(&mut self) -> Option<cssom::MediaCondition> {
+        self.consume_whitespace();
+
+        let name = self.consume_identifier().to_ascii_lowercase();
+
+        if !name.is_empty() {
+            self.consume_whitespace();
+
+            if self.eof() {
+                let feature = cssom::MediaFeature::Boolean(name);
+                return Some(cssom::MediaCondition::Feature(feature));
+            }
+
+            let (name, comparison) = if self.next_is(&Token::Colon) {
+                self.consume_value();
+
+                if let Some(name) = name.strip_prefix("min-") {
+                    (name.to_owned(), cssom::Comparison::GreaterOrEqual)
+                } else if let Some(name) = name.strip_prefix("max-") {
+                    (name.to_owned(), cssom::Comparison::LessOrEqual)
+                } else {
+                    (name, cssom::Comparison::Equal)
+                }
+            } else {
+                (name, self.parse_media_comparison()?)
+            };
+
+            let value = self.parse_media_value()?;
+            let feature = cssom::MediaFeature::Range(name, comparison, value);
+
+            return Some(cssom::MediaCondition::Feature(feature));
+        }
+
+        let value = self.parse_media_value()?;
+        let comparison = self.parse_media_comparison()?.reversed();
+
+        self.consume_whitespace();
+        let name = self.consume_identifier().to_ascii_lowercase();
+
+        if name.is_empty() {
+            return None;
+        }
+
+        let feature = cssom::MediaFeature::Range(name.clone(), comparison, value);
+
+        self.consume_whitespace();
+
+        if self.eof() {
+            return Some(cssom::MediaCondition::Feature(feature));
+        }
+
+        // Both comparisons of a range have to point in the same direction
+        let other_comparison = self.parse_media_comparison()?;
+
+        let is_less = |comparison: cssom::Comparison| {
+            return comparison == cssom::Comparison::Less
+                || comparison == cssom::Comparison::LessOrEqual;
+        };
+        let is_greater = |comparison: cssom::Comparison| {
+            return comparison == cssom::Comparison::Greater
+                || comparison == cssom::Comparison::GreaterOrEqual;
+        };
+
+        if !(is_less(other_comparison) && is_greater(comparison))
+            && !(is_greater(other_comparison) && is_less(comparison))
+        {
+            return None;
+        }
+
+        let other_value = self.parse_media_value()?;
+        let other_feature = cssom::MediaFeature::Range(name, other_comparison, other_value);
+
+        return Some(cssom::MediaCondition::And(vec![
+            cssom::MediaCondition::Feature(feature),
+            cssom::MediaCondition::Feature(other_feature),
+        ]));
+    }
+
+    /// Parses `=`, `<`, `<=`, `>` or `>=` and the whitespace after it.
+    fn parse_media_comparison(&mut self) -> Option<cssom::Comparison> {
+        self.consume_whitespace();
+
+        let comparison = match self.consume_value() {
+            Some(ComponentValue::Token(Token::Delim('='))) => {
+                return Some(cssom::Comparison::Equal)
+            }
+            Some(ComponentValue::Token(Token::Delim('<'))) => cssom::Comparison::Less,
+            Some(ComponentValue::Token(Token::Delim('>'))) => cssom::Comparison::Greater,
+            _ => return None,
+        };
+
+        if !self.next_is(&Token::Delim('=')) {
+            return Some(comparison);
+        }
+
+        self.consume_value();
+
+        return match comparison {
+            cssom::Comparison::Less => Some(cssom::Comparison::LessOrEqual),
+            _ => Some(cssom::Comparison::GreaterOrEqual),
+        };
+    }
+
+    /// Parses the value of a media feature: a length, a number, a ratio
+    /// like `16/9`, a resolution or a keyword.
+    fn parse_media_value(&mut self) -> Option<cssom::MediaValue> {
+        self.consume_whitespace();
+
+        let value = match self.next_value()? {
+            ComponentValue::Token(Token::Ident(ident)) => {
+                let value = cssom::MediaValue::Ident(ident.to_ascii_lowercase());
+                self.consume_value();
+                self.consume_whitespace();
+
+                return Some(value);
+            }
+            ComponentValue::Token(Token::Dimension(number, unit)) => {
+                let dppx = match unit.to_ascii_lowercase().as_ref() {
+                    "dppx" | "x" => Some(number.value),
+                    "dpi" => Some(number.value / 96.0),
+                    "dpcm" => Some(number.value * 2.54 / 96.0),
+                    _ => None,
+                };
+
+                if let Some(dppx) = dppx {
+                    self.consume_value();
+                    self.consume_whitespace();
+
+                    return Some(cssom::MediaValue::Resolution(dppx));
+                }
+
+                self.parse_numeric_value()?
+            }
+            _ => self.parse_numeric_value()?,
+        };
+
+        return match value {
+            cssom::NumericValue::Number(number) if self.next_is(&Token::Delim('/')) => {
+                self.consume_value();
+                self.consume_whitespace();
+
+                match self.consume_value() {
+                    Some(ComponentValue::Token(Token::Number(other))) => {
+                        self.consume_whitespace();
+                        Some(cssom::MediaValue::Ratio(number, other.value))
+                    }
+                    _ => None,
+                }
+            }
+            cssom::NumericValue::Number(number) => Some(cssom::MediaValue::Number(number)),
+            cssom::NumericValue::Zero if self.next_is(&Token::Delim('/')) => None,
+            cssom::NumericValue::Percentage(_) => None,
+            value => Some(cssom::MediaValue::Length(value)),
+        };
+    }
+
+    /// Parses a supports condition: a negated condition in parentheses, or
+    /// conditions in parentheses joined by either `and` or `or`.
+    fn parse_supports_condition(&mut self) -> Option<cssom::SupportsCondition> {
+        self.consume_whitespace();
+
+        if self
+            .next_value()
+            .and_then(|value| value.ident())
+            .map_or(false, |ident| ident.eq_ignore_ascii_case("not"))
+        {
+            self.consume_value();
+            self.consume_whitespace();
+
+            let condition = self.parse_supports_in_parens()?;
+            return Some(cssom::SupportsCondition::Not(Box::new(condition)));
+        }
+
+        let mut conditions = vec![self.parse_supports_in_parens()?];
+        let mut operator = String::new();
+
+        loop {
+            self.consume_whitespace();
+
+            if self.eof() {
+                break;
+            }
+
+            let keyword = self.consume_identifier().to_ascii_lowercase();
+
+            let valid = match keyword.as_ref() {
+                "and" => operator != "or",
+                "or" => operator != "and",
+                _ => false,
+            };
+
+            if !valid {
+                return None;
+            }
+
+            operator = keyword;
+            self.consume_whitespace();
+            conditions.push(self.parse_supports_in_parens()?);
+        }
+
+        return match operator.as_ref() {
+            "and" => Some(cssom::SupportsCondition::And(conditions)),
+            "or" => Some(cssom::SupportsCondition::Or(conditions)),
+            _ => conditions.pop(),
+        };
+    }
+
+    /// Parses a condition or a declaration in parentheses, or a
+    /// `selector()` function. Anything else in parentheses, or any other
+    /// function, is kept but never supported.
+    fn parse_supports_in_parens(&mut self) -> Option<cssom::SupportsCondition> {
+        let value = self.consume_value()?;
+
+        let values = match value {
+            ComponentValue::Block(Token::OpenParen, ref values) => values.clone(),
+            ComponentValue::Function(ref name, ref arguments)
+                if name.eq_ignore_ascii_case("selector") =>
+            {
+                // Unlike in pseudo-class arguments, pseudo-elements are allowed
+                let mut parser = Self::from_values(arguments.clone());
+                parser.consume_whitespace();
+                let selector = parser.parse_selector().filter(|_| parser.eof());
+
+                return Some(cssom::SupportsCondition::Selector(selector));
+            }
+            ComponentValue::Function(_, _) => {
+                let source = tokenizer::serialize(&[value]);
+                return Some(cssom::SupportsCondition::Unknown(source));
+            }
+            _ => return None,
+        };
+
+        if let Some(condition) = Self::from_values(values.clone()).parse_supports_condition() {
+            return Some(condition);
+        }
+
+        // Anything starting with a property name and a colon is a
+        // declaration, even if its value is invalid
+        let mut parser = Self::from_values(values);
+        parser.consume_whitespace();
+
+        let is_declaration = matches!(
+            parser.next_value(),
+            Some(ComponentValue::Token(Token::Ident(_)))
+        ) && parser.values[parser.pos + 1..]
+            .iter()
+            .find(|value| !value.is_whitespace())
+            .map_or(false, |value| value.is_token(&Token::Colon));
+
+        if is_declaration {
+            let declarations = parser.parse_expanded_declaration().filter(|_| parser.eof());
+
+            return Some(cssom::SupportsCondition::Declaration(declarations));
+        }
+
+        let source = tokenizer::serialize(&[value]);
+
+        return Some(cssom::SupportsCondition::Unknown(source));
+    }
+
+    /// Returns the position of the block of a nested rule, if the values up
+    /// to `end` are one. Only custom properties can have blocks in their
+    /// values, so anything else with a block is a rule, even if it starts
+    /// like a declaration, like `a:hover { … }`.
+    fn nested_rule_block(&self, end: usize) -> Option<usize> {
+        let is_custom_property = self
+            .next_value()
+            .and_then(|value| value.ident())
+            .map_or(false, variables::is_custom_property);
+
+        if is_custom_property {
+            return None;
+        }
+
+        return self.values[self.pos..end]
+            .iter()
+            .position(|value| matches!(value, ComponentValue::Block(Token::OpenCurly, _)))
+            .map(|offset| self.pos + offset);
+    }
+
+    /// Returns the position of the semicolon ending the current declaration
+    /// value, or the end of the input.
+    fn declaration_value_end(&self) -> usize {
+        return self.values[self.pos..]
+            .iter()
+            .position(|value| value.is_token(&Token::Semicolon))
+            .map_or(self.values.len(), |offset| self.pos + offset);
+    }
+
+    fn next_is_declaration_block(&self) -> bool {
+        return matches!(
+            self.next_value(),
+            Some(ComponentValue::Block(Token::OpenCurly, _))
+        );
+    }
+
+    fn next_is(&self, token: &Token) -> bool {
+        return self
+            .next_value()
+            .map_or(false, |value| value.is_token(token));
+    }
+
+    fn next_value(&self) -> Option<&ComponentValue> {
+        return self.values.get(self.pos);
+    }
+
+    fn consume_value(&mut self) -> Option<ComponentValue> {
+        let value = self.values.get(self.pos).cloned();
+
+        if value.is_some() {
+            self.pos += 1;
+        }
+
+        return value;
+    }
+
+    fn consume_while(&mut self, cond: &dyn Fn(&ComponentValue) -> bool) -> ComponentValues {
+        let mut result = ComponentValues::new();
+
+        while !self.eof() && cond(&self.values[self.pos]) {
+            result.push(self.consume_value().unwrap());
+        }
+
+        return result;
+    }
+
+    fn consume_whitespace(&mut self) {
+        self.consume_while(&|value| value.is_whitespace());
+    }
+
+    /// Consumes an identifier if there is one, returns an empty string
+    /// otherwise.
+    fn consume_identifier(&mut self) -> String {
+        if let Some(ident) = self.next_value().and_then(|value| value.ident()) {
+            let ident = ident.to_owned();
+            self.pos += 1;
+
+            return ident;
+        }
+
+        return String::new();
+    }
+
+    fn eof(&self) -> bool {
+        return self.pos >= self.values.len();
+    }
+}
+
+/// Parses a layer name, like `base` or `components.buttons`. Its parts
+/// are joined by periods without whitespace.
+fn parse_layer_name(values: &[ComponentValue]) -> Option<String> {
+    let values = tokenizer::trim_whitespace(values);
+    let mut name = String::new();
+
+    for (index, value) in values.iter().enumerate() {
+        match (index % 2, value) {
+            (0, ComponentValue::Token(Token::Ident(ident))) => name.push_str(ident),
+            (1, value) if value.is_delim('.') => name.push('.'),
+            _ => return None,
+        }
+    }
+
+    return match name.is_empty() || name.ends_with('.') {
+        true => None,
+        false => Some(name),
+    };
+}
+
+/// Parses the comma-separated prelude of an `@media` rule. Invalid queries
+/// are replaced by `not all`, so they never match, but don’t invalidate the
+/// other queries in the list.
+fn parse_media_query_list(values: &[ComponentValue]) -> cssom::MediaQueryList {
+    if tokenizer::trim_whitespace(values).is_empty() {
+        return cssom::MediaQueryList::new();
+    }
+
+    return values
+        .split(|value| value.is_token(&Token::Comma))
+        .map(|values| {
+            let mut parser = Parser::from_values(values.to_vec());
+            let query = parser.parse_media_query();
+            parser.consume_whitespace();
+
+            return match parser.eof() {
+                true => query.unwrap_or_else(cssom::MediaQuery::not_all),
+                false => cssom::MediaQuery::not_all(),
+            };
+        })
+        .collect();
+}
+
+/// Parses the selector list argument of a logical pseudo-class. Forgiving
+/// lists, used by `:is()` and `:where()`, drop invalid selectors instead
+/// of failing as a whole.
+fn parse_selector_list(
+    values: &[ComponentValue],
+    forgiving: bool,
+    nesting: Option<&cssom::Selectors>,
+) -> Option<cssom::Selectors> {
+    let mut selectors = cssom::Selectors::new();
+
+    for part in values.split(|value| value.is_token(&Token::Comma)) {
+        let mut parser = Parser::from_values(part.to_vec());
+        parser.nesting = nesting.cloned();
+        parser.consume_whitespace();
+
+        match parser.parse_selector() {
+            Some(selector) if parser.eof() && selector.pseudo_element.is_none() => {
+                selectors.push(selector)
+            }
+            _ if forgiving => continue,
+            _ => return None,
+        }
+    }
+
+    return Some(selectors);
+}
+
+/// Parses the selector list of a nested style rule. Selectors without `&`
+/// are relative to the parent selectors, so `h2` is `& h2` and `> h2` is
+/// `& > h2`.
+fn parse_nested_selector_list(
+    values: &[ComponentValue],
+    parent: &cssom::Selectors,
+) -> Option<cssom::Selectors> {
+    let mut selectors = cssom::Selectors::new();
+
+    for part in values.split(|value| value.is_token(&Token::Comma)) {
+        let part = tokenizer::trim_whitespace(part);
+
+        if part.is_empty() {
+            return None;
+        }
+
+        let mut values = ComponentValues::new();
+
+        if !contains_nesting_selector(part) {
+            values.push(ComponentValue::Token(Token::Delim('&')));
+            values.push(ComponentValue::Token(Token::Whitespace));
+        }
+
+        values.extend(part.iter().cloned());
+
+        let mut parser = Parser::from_values(values);
+        parser.nesting = Some(parent.clone());
+
+        let selector = parser.parse_selector()?;
+
+        if !parser.eof() {
+            return None;
+        }
+
+        selectors.push(resolve_nesting_selector(selector, parent));
+    }
+
+    return Some(selectors);
+}
+
+fn contains_nesting_selector(values: &[ComponentValue]) -> bool {
+    return values.iter().any(|value| match value {
+        ComponentValue::Token(token) => *token == Token::Delim('&'),
+        ComponentValue::Function(_, values) | ComponentValue::Block(_, values) => {
+            contains_nesting_selector(values)
+        }
+    });
+}
+
+/// Replaces `&` at the start of a nested selector with the parent selector
+/// itself, if there is only one, so `&:hover > a` nested in `.nav li`
+/// becomes `.nav li:hover > a`. Everywhere else, `&` stays `:is()` of the
+/// parent selectors, which has the same specificity.
+fn resolve_nesting_selector(
+    mut selector: cssom::Selector,
+    parent: &cssom::Selectors,
+) -> cssom::Selector {
+    if let Some((combinator, left)) = selector.combinator.take() {
+        let left = resolve_nesting_selector(*left, parent);
+        selector.combinator = Some((combinator, Box::new(left)));
+
+        return selector;
+    }
+
+    let nesting = cssom::PseudoClass::Is(parent.clone());
+    let position = selector
+        .pseudo_classes
+        .iter()
+        .position(|pseudo_class| *pseudo_class == nesting);
+
+    let position = match position {
+        Some(position) if parent.len() == 1 => position,
+        _ => return selector,
+    };
+
+    let mut resolved = parent[0].clone();
+
+    let conflicts = (resolved.tag.is_some() && selector.tag.is_some())
+        || (resolved.id.is_some() && selector.id.is_some());
+
+    if conflicts {
+        return selector;
+    }
+
+    selector.pseudo_classes.remove(position);
+
+    if resolved.tag.is_none() {
+        resolved.tag = selector.tag;
+        resolved.universal |= selector.universal;
+    }
+
+    resolved.id = resolved.id.or(selector.id);
+    resolved.classes.extend(selector.classes);
+    resolved.attrs.extend(selector.attrs);
+    resolved.pseudo_classes.extend(selector.pseudo_classes);
+    resolved.pseudo_element = selector.pseudo_element;
+
+    return resolved;
+}
+
+/// Parses the relative selector list argument of `:has()`, where each
+/// selector may start with a combinator.
+fn parse_relative_selector_list(
+    values: &[ComponentValue],
+    nesting: Option<&cssom::Selectors>,
+) -> Option<cssom::RelativeSelectors> {
+    let mut selectors = cssom::RelativeSelectors::new();
+
+    for part in values.split(|value| value.is_token(&Token::Comma)) {
+        let mut parser = Parser::from_values(part.to_vec());
+        parser.nesting = nesting.cloned();
+        parser.consume_whitespace();
+
+        let combinator = match parser.next_value() {
+            Some(value) if value.is_delim('>') => cssom::Combinator::Child,
+            Some(value) if value.is_delim('+') => cssom::Combinator::NextSibling,
+            Some(value) if value.is_delim('~') => cssom::Combinator::SubsequentSibling,
+            _ => cssom::Combinator::Descendant,
+        };
+
+        if combinator != cssom::Combinator::Descendant {
+            parser.consume_value();
+            parser.consume_whitespace();
+        }
+
+        let selector = parser.parse_selector()?;
+
+        if !parser.eof() || selector.pseudo_element.is_some() {
+            return None;
+        }
+
+        selectors.push(cssom::RelativeSelector::new(combinator, selector));
+    }
+
+    return Some(selectors);
+}
+
+/// Parses the arguments of `:nth-child()` and `:nth-last-child()`, which
+/// may be followed by `of` and a selector list.
+fn parse_nth_arguments(
+    values: &[ComponentValue],
+    nesting: Option<&cssom::Selectors>,
+) -> Option<(cssom::Nth, Option<cssom::Selectors>)> {
+    let of = values.iter().position(|value| {
+        value
+            .ident()
+            .map_or(false, |ident| ident.eq_ignore_ascii_case("of"))
+    });
+
+    return match of {
+        Some(position) => {
+            let selectors = parse_selector_list(&values[position + 1..], false, nesting)?;
+
+            Some((parse_nth(&values[..position])?, Some(selectors)))
+        }
+        None => Some((parse_nth(values)?, None)),
+    };
+}
+
+/// Parses the `An+B` microsyntax. The tokenizer splits it in odd places
+/// (`2n-1` is a single dimension, `-n+3` an ident followed by a number),
+/// so this works on the serialized text instead.
+fn parse_nth(values: &[ComponentValue]) -> Option<cssom::Nth> {
+    let text = tokenizer::serialize(values).trim().to_ascii_lowercase();
+
+    let parse_integer = |text: &str| {
+        let digits = text.trim_start_matches(['+', '-']);
+
+        if digits.len() == 0 || text.len() - digits.len() > 1 {
+            return None;
+        }
+
+        if !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        return text.parse::<i32>().ok();
+    };
+
+    match &text[..] {
+        "odd" => return Some(cssom::Nth::new(2, 1)),
+        "even" => return Some(cssom::Nth::new(2, 0)),
+        _ => (),
+    }
+
+    let n = match text.find('n') {
+        Some(n) => n,
+        None => return Some(cssom::Nth::new(0, parse_integer(&text)?)),
+    };
+
+    let a = match &text[..n] {
+        "" | "+" => 1,
+        "-" => -1,
+        a => parse_integer(a)?,
+    };
+
+    // Whitespace is allowed around the sign of B, but not within A
+    let rest = text[n + 1..].trim_start();
+
+    if rest.len() == 0 {
+        return Some(cssom::Nth::new(a, 0));
+    }
+
+    let sign = match rest.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return None,
+    };
+
+    let b = rest[1..].trim_start();
+
+    if b.len() == 0 || !b.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    return Some(cssom::Nth::new(a, sign * b.parse::<i32>().ok()?));
+}
+
+/// Parses `#rgb`, `#rgba`, `#rrggbb` and `#rrggbbaa`.
+fn parse_hex_color(hex: &str) -> Option<cssom::Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    // Shorthand #abc == #aabbcc
+    let hex: String = match hex.len() {
+        3 | 4 => hex
+            .chars()
+            .flat_map(|c| std::iter::repeat(c).take(2))
+            .collect(),
+        6 | 8 => hex.to_owned(),
+        _ => return None,
+    };
+
+    let channel = |index: usize| u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).unwrap();
+    let color = cssom::Color::new(channel(0), channel(1), channel(2));
+
+    return match hex.len() {
+        8 => Some(color.alpha(channel(3))),
+        _ => Some(color),
+    };
+}
+
+fn is_color_function(name: &str) -> bool {
+    return ["rgb", "rgba", "hsl", "hsla", "hwb"]
+        .iter()
+        .any(|function| name.eq_ignore_ascii_case(function));
+}
+
+/// Parses `rgb()`, `hsl()`, `hwb()` and their aliases, either in the legacy
+/// syntax with commas, or with spaces and an optional `/ alpha`.
+fn parse_color_function(name: &str, arguments: &[ComponentValue]) -> Option<cssom::Color> {
+    let name = name.to_ascii_lowercase();
+    let arguments: Vec<&ComponentValue> = arguments
+        .iter()
+        .filter(|value| !value.is_whitespace())
+        .collect();
+
+    let is_legacy = arguments.iter().any(|value| value.is_token(&Token::Comma));
+
+    let (channels, alpha) = match is_legacy {
+        // There is no legacy syntax for `hwb()`
+        true if name == "hwb" => return None,
+
+        true => {
+            let parts: Vec<&[&ComponentValue]> = arguments
+                .split(|value| value.is_token(&Token::Comma))
+                .collect();
+
+            if !parts.iter().all(|part| part.len() == 1) {
+                return None;
+            }
+
+            let values: Vec<&ComponentValue> = parts.iter().map(|part| part[0]).collect();
+
+            match values.len() {
+                4 => (values[..3].to_vec(), Some(values[3])),
+                _ => (values, None),
+            }
+        }
+
+        false => match arguments.iter().position(|value| value.is_delim('/')) {
+            Some(slash) if slash + 2 == arguments.len() => {
+                (arguments[..slash].to_vec(), Some(arguments[slash + 1]))
+            }
+            Some(_) => return None,
+            None => (arguments, None),
+        },
+    };
+
+    if channels.len() != 3 {
+        return None;
+    }
+
+    let alpha = match alpha {
+        Some(alpha) => parse_alpha(alpha)?,
+        None => 255,
+    };
+
+    let color = match name.as_ref() {
+        "rgb" | "rgba" => {
+            let percentages = channels
+                .iter()
+                .filter(|channel| matches!(channel, ComponentValue::Token(Token::Percentage(_))))
+                .count();
+
+            // The legacy syntax doesn’t allow mixing numbers and percentages
+            if is_legacy && percentages != 0 && percentages != 3 {
+                return None;
+            }
+
+            cssom::Color::new(
+                parse_rgb_channel(channels[0])?,
+                parse_rgb_channel(channels[1])?,
+                parse_rgb_channel(channels[2])?,
+            )
+        }
+        "hsl" | "hsla" => cssom::Color::from_hsl(
+            parse_hue(channels[0])?,
+            parse_color_percentage(channels[1], !is_legacy)?,
+            parse_color_percentage(channels[2], !is_legacy)?,
+        ),
+        "hwb" => cssom::Color::from_hwb(
+            parse_hue(channels[0])?,
+            parse_color_percentage(channels[1], true)?,
+            parse_color_percentage(channels[2], true)?,
+        ),
+        _ => return None,
+    };
+
+    return Some(color.alpha(alpha));
+}
+
+/// A number from 0 to 255, or a percentage.
+fn parse_rgb_channel(value: &ComponentValue) -> Option<u8> {
+    let channel = match value {
+        ComponentValue::Token(Token::Number(number)) => number.value,
+        ComponentValue::Token(Token::Percentage(number)) => number.value * 255.0 / 100.0,
+        _ => return None,
+    };
+
+    return Some(channel.clamp(0.0, 255.0).round() as u8);
+}
+
+/// A number from 0 to 1, or a percentage.
+fn parse_alpha(value: &ComponentValue) -> Option<u8> {
+    let alpha = match value {
+        ComponentValue::Token(Token::Number(number)) => number.value,
+        ComponentValue::Token(Token::Percentage(number)) => number.value / 100.0,
+        _ => return None,
+    };
+
+    return Some((alpha.clamp(0.0, 1.0) * 255.0).round() as u8);
+}
+
+/// Returns a percentage as a fraction. Numbers are read as percentages
+/// where the modern syntax allows them.
+fn parse_color_percentage(value: &ComponentValue, allow_number: bool) -> Option<f64> {
+    return match value {
+        ComponentValue::Token(Token::Percentage(number)) => Some(number.value / 100.0),
+        ComponentValue::Token(Token::Number(number)) if allow_number => Some(number.value / 100.0),
+        _ => None,
+    };
+}
+
+/// Returns a hue in degrees. Unitless hues are degrees, too.
+fn parse_hue(value: &ComponentValue) -> Option<f64> {
+    return match value {
+        ComponentValue::Token(Token::Number(number)) => Some(number.value),
+        ComponentValue::Token(Token::Dimension(number, unit)) => {
+            match unit.to_ascii_lowercase().as_ref() {
+                "deg" => Some(number.value),
+                "grad" => Some(number.value * 0.9),
+                "rad" => Some(number.value.to_degrees()),
+                "turn" => Some(number.value * 360.0),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+}
+
+fn is_math_function(name: &str) -> bool {
+    return ["calc", "min", "max", "clamp"]
+        .iter()
+        .any(|function| name.eq_ignore_ascii_case(function));
+}
+
+/// Parses `calc()`, `min()`, `max()` and `clamp()`. Their types are
+/// checked by the caller, once the whole expression is parsed.
+fn parse_math_function(name: &str, arguments: &[ComponentValue]) -> Option<cssom::Calculation> {
+    let mut arguments = arguments
+        .split(|value| value.is_token(&Token::Comma))
+        .map(|argument| Parser::from_values(argument.to_vec()).parse_calculation())
+        .collect::<Option<Vec<cssom::Calculation>>>()?;
+
+    return match (name.to_ascii_lowercase().as_ref(), arguments.len()) {
+        ("calc", 1) => arguments.pop(),
+        ("min", _) => Some(cssom::Calculation::Min(arguments)),
+        ("max", _) => Some(cssom::Calculation::Max(arguments)),
+        ("clamp", 3) => {
+            let max = Box::new(arguments.pop()?);
+            let value = Box::new(arguments.pop()?);
+            let min = Box::new(arguments.pop()?);
+
+            Some(cssom::Calculation::Clamp(min, value, max))
+        }
+        _ => None,
+    };
+}
+
+/// Custom properties can contain anything but unmatched closing brackets
+/// and bad tokens.
+fn is_valid_in_custom_property(value: &ComponentValue) -> bool {
+    return match value {
+        ComponentValue::Token(token) => !matches!(
+            token,
+            Token::BadString
+                | Token::BadUrl
+                | Token::CloseParen
+                | Token::CloseSquare
+                | Token::CloseCurly
+        ),
+        ComponentValue::Function(_, values) | ComponentValue::Block(_, values) => {
+            values.iter().all(is_valid_in_custom_property)
+        }
+    };
+}
+
+/// Tokens that are never valid in a declaration value. A colon usually
+/// means a semicolon is missing, as in `color: red background: blue`.
+fn is_valid_in_value(value: &ComponentValue) -> bool {
+    return match value {
+        ComponentValue::Token(token) => !matches!(
+            token,
+            Token::BadString
+                | Token::BadUrl
+                | Token::Colon
+                | Token::CloseParen
+                | Token::CloseSquare
+                | Token::CloseCurly
+        ),
+        ComponentValue::Function(_, values) => values.iter().all(is_valid_in_value),
+        ComponentValue::Block(Token::OpenCurly, _) => false,
+        ComponentValue::Block(_, values) => values.iter().all(is_valid_in_value),
+    };
+}
+
+/// Properties whose numeric values can’t be negative.
+const NON_NEGATIVE_PROPERTIES: [&str; 19] = [
+    "width",
+    "height",
+    "min-width",
+    "min-height",
+    "max-width",
+    "max-height",
+    "font-size",
+    "line-height",
+    "padding-top",
+    "padding-right",
+    "padding-bottom",
+    "padding-left",
+    "border-top-width",
+    "border-right-width",
+    "border-bottom-width",
+    "border-left-width",
+    "flex-grow",
+    "flex-shrink",
+    "flex-basis",
+];
+
+/// Checks that a value is in the range of the property. Sizes can’t be
+/// negative, and declarations with negative ones are dropped, so earlier
+/// declarations of the property still apply. Math functions are only
+/// resolved during layout, so they aren’t checked here.
+fn is_in_range(property: &str, value: &cssom::Value) -> bool {
+    if !NON_NEGATIVE_PROPERTIES.contains(&property) {
+        return true;
+    }
+
+    return match value {
+        cssom::Value::Numeric(
+            cssom::NumericValue::Number(number)
+            | cssom::NumericValue::Px(number)
+            | cssom::NumericValue::Percentage(number)
+            | cssom::NumericValue::Length(number, _),
+        ) => *number >= 0.0,
+        _ => true,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parser_parse_rulesets() {
+        let mut parser = Parser::new("ul { padding-left: 10px; } p { font-family: serif; }");
+        let rulesets = parser.parse_rulesets();
+
+        assert!(rulesets.len() == 2);
+        assert!(rulesets[0].selectors[0] == cssom::Selector::new().tag("ul"));
+        assert!(rulesets[1].selectors[0] == cssom::Selector::new().tag("p"));
+        assert!(parser.eof());
+    }
+
+    #[test]
+    fn test_parser_parse_rulesets_whitespace() {
+        let mut parser = Parser::new("    div { color: red; }");
+        let rulesets = parser.parse_rulesets();
+
+        assert!(rulesets.len() == 1);
+    }
+
+    #[test]
+    fn test_parser_parse_ruleset() {
+        let mut parser = Parser::new("ul { padding-left: 10px; list-style: square; }");
+        let ruleset = parser.parse_ruleset().remove(0);
+
+        assert!(ruleset.selectors == cssom::Selectors::from([cssom::Selector::new().tag("ul")]));
+        assert!(ruleset.declarations.len() == 4);
+        assert!(parser.eof());
+    }
+
+    #[test]
+    fn test_parser_parse_declarations() {
+        let mut parser = Parser::new(" padding-left: 10px; list-style: square ");
+        let declarations = parser.parse_declarations();
+
+        assert!(declarations.len() == 4);
+        assert!(
+            declarations["padding-left"].value
+                == cssom::Value::Numeric(cssom::NumericValue::Px(10.0))
+        );
+        assert!(declarations["list-style-type"].value == cssom::Value::Ident("square".to_owned()));
+    }
+
+    #[test]
+    fn test_parser_parse_source_order() {
+        let rulesets = Parser::parse(
+            "a { width: 1px; width: 50%; width: ; } ! { color: red; } b { color: red; }",
+        );
+
+        assert!(rulesets.len() == 2);
+        assert!(rulesets[0].order == 0);
+        assert!(rulesets[1].order == 1);
+
+        // Duplicates are kept in order, invalid ones fall back to earlier ones
+        let widths: Vec<&cssom::Value> = rulesets[0]
+            .declarations
+            .iter()
+            .map(|declaration| &declaration.value)
+            .collect();
+
+        assert!(
+            widths
+                == [
+                    &cssom::Value::Numeric(cssom::NumericValue::Px(1.0)),
+                    &cssom::Value::Numeric(cssom::NumericValue::Percentage(50.0)),
+                ]
+        );
+        assert!(
+            rulesets[0].declarations["width"].value
+                == cssom::Value::Numeric(cssom::NumericValue::Percentage(50.0))
+        );
+    }
+
+    #[test]
+    fn test_parser_parse_declaration() {
+        let mut parser = Parser::new("padding-left: 10px;");
+        let declaration = parser.parse_declaration().unwrap();
+
+        assert!(declaration.property == "padding-left");
+        assert!(declaration.value == cssom::Value::Numeric(cssom::NumericValue::Px(10.0)));
+        assert!(declaration.important == false);
+    }
+
+    #[test]
+    fn test_parser_parse_declaration_ranges() {
+        let rulesets = Parser::parse(
+            "div { width: 100px; width: -10px; height: -1%; margin-left: -10px; font-size: -1em; }",
+        );
+        let declarations = &rulesets[0].declarations;
+
+        assert!(declarations.len() == 2);
+        assert!(
+            declarations["width"].value == cssom::Value::Numeric(cssom::NumericValue::Px(100.0))
+        );
+        assert!(
+            declarations["margin-left"].value
+                == cssom::Value::Numeric(cssom::NumericValue::Px(-10.0))
+        );
+
+        let substituted = |property: &str, css: &str| {
+            let values = tokenizer::parse_component_values(css);
+            return Parser::parse_substituted_value(property, property, values);
+        };
+
+        let rulesets = Parser::parse(
+            "p { padding: 1px; padding: 2px -1px; border-width: -1px; line-height: -2; }",
+        );
+        let declarations = &rulesets[0].declarations;
+
+        assert!(declarations.len() == 4);
+        assert!(
+            declarations["padding-right"].value
+                == cssom::Value::Numeric(cssom::NumericValue::Px(1.0))
+        );
+
+        let rulesets = Parser::parse("p { font-size: 10px; font: -12px serif; flex: 1 1 -1px; }");
+        let declarations = &rulesets[0].declarations;
+
+        assert!(declarations.len() == 1);
+        assert!(
+            declarations["font-size"].value == cssom::Value::Numeric(cssom::NumericValue::Px(10.0))
+        );
+
+        assert!(substituted("width", "-10px") == None);
+        assert!(substituted("max-height", "-1%") == None);
+        assert!(
+            substituted("width", "0") == Some(cssom::Value::Numeric(cssom::NumericValue::Zero))
+        );
+    }
+
+    #[test]
+    fn test_parser_parse_declaration_important() {
+        let parse = |css: &str| Parser::new(css).parse_declaration();
+
+        let declaration = parse("color: #333 !important;").unwrap();
+        assert!(declaration.property == "color");
+        assert!(declaration.value == cssom::Value::Color(cssom::Color::new(0x33, 0x33, 0x33)));
+        assert!(declaration.important == true);
+
+        let declaration = parse("font: bold 12px serif ! IMPORTANT").unwrap();
+        assert!(
+            declaration.value
+                == cssom::Value::SpaceList(Vec::from([
+                    cssom::Value::Ident("bold".to_owned()),
+                    cssom::Value::Numeric(cssom::NumericValue::Px(12.0)),
+                    cssom::Value::Ident("serif".to_owned()),
+                ]))
+        );
+        assert!(declaration.important == true);
+
+        let declaration = parse("content: \"!important\"").unwrap();
+        assert!(declaration.important == false);
+
+        let declaration = parse("color: red important").unwrap();
+        assert!(declaration.important == false);
+
+        assert!(parse("color: !important") == None);
+    }
+
+    #[test]
+    fn test_parser_parse_shorthands() {
+        let (rulesets, warnings) = Parser::parse_with_warnings(
+            "p { margin: 1px 2px; flex: 1; border: 1px solid #000 !important; padding: 1px 2px 3px 4px 5px; }",
+        );
+        let declarations = &rulesets[0].declarations;
+
+        assert!(declarations.len() == 4 + 3 + 12);
+        assert!(
+            declarations["margin-top"].value == cssom::Value::Numeric(cssom::NumericValue::Px(1.0))
+        );
+        assert!(
+            declarations["margin-left"].value
+                == cssom::Value::Numeric(cssom::NumericValue::Px(2.0))
+        );
+        assert!(
+            declarations["flex-grow"].value
+                == cssom::Value::Numeric(cssom::NumericValue::Number(1.0))
+        );
+        assert!(
+            declarations["flex-basis"].value == cssom::Value::Numeric(cssom::NumericValue::Zero)
+        );
+        assert!(declarations["border-left-style"].value == cssom::Value::Ident("solid".to_owned()));
+        assert!(declarations["border-left-style"].important == true);
+        assert!(
+            declarations["border-top-color"].value
+                == cssom::Value::Color(cssom::Color::new(0, 0, 0))
+        );
+        assert!(declarations.get("margin").is_none());
+        assert!(declarations.get("padding-top").is_none());
+        assert!(warnings.len() == 1);
+    }
+
+    #[test]
+    fn test_parser_parse_custom_properties() {
+        let (rulesets, warnings) = Parser::parse_with_warnings(
+            "a { --Brand:  #36c !important; --empty:; --json: {\"a\": [1]}; margin: var(--x) 0; --bad: ); }",
+        );
+        let declarations = &rulesets[0].declarations;
+        let tokens = |css: &str| tokenizer::parse_component_values(css);
+
+        assert!(declarations["--Brand"].value == cssom::Value::Tokens(tokens("#36c")));
+        assert!(declarations["--Brand"].important == true);
+        assert!(declarations["--empty"].value == cssom::Value::Tokens(Vec::new()));
+        assert!(declarations["--json"].value == cssom::Value::Tokens(tokens("{\"a\": [1]}")));
+        assert!(
+            declarations["margin-left"].value
+                == cssom::Value::Pending("margin".to_owned(), tokens("var(--x) 0"))
+        );
+        assert!(declarations.get("margin").is_none());
+        assert!(warnings == Warnings::from([Warning::InvalidDeclaration("--bad: )".to_owned())]));
+    }
+
+    #[test]
+    fn test_parser_parse_declaration_strings_and_urls() {
+        let mut parser = Parser::new("font-family: \"Helvetica Neue\", sans-serif;");
+        let declaration = parser.parse_declaration().unwrap();
+
+        assert!(declaration.property == "font-family");
+        assert!(
+            declaration.value
+                == cssom::Value::CommaList(Vec::from([
+                    cssom::Value::String("Helvetica Neue".to_owned()),
+                    cssom::Value::Ident("sans-serif".to_owned()),
+                ]))
+        );
+        assert!(parser.eof());
+
+        let mut parser = Parser::new("Background-Image: url(a;b); color: red;");
+        let declaration = parser.parse_declaration().unwrap();
+
+        assert!(declaration.property == "background-image");
+        assert!(declaration.value == cssom::Value::Url("a;b".to_owned()));
+    }
+
+    #[test]
+    fn test_parser_parse_rulesets_at_rules() {
+        let css = "<!-- @charset \"utf-8\"; @font-face { font-family: x; } p { color: red; } -->";
+        let rulesets = Parser::parse(css);
+
+        assert!(rulesets.len() == 1);
+        assert!(rulesets[0].selectors[0] == cssom::Selector::new().tag("p"));
+    }
+
+    #[test]
+    fn test_parser_parse_media_rules() {
+        let css = "
+            a { color: red; }
+            @media screen, (min-width: 600px) and (orientation: landscape) {
+                b { color: red; }
+                @media not print { c { color: red; } }
+            }
+            @media { d { color: red; } }
+            @media screen; e { color: red; }
+            f { color: red; }
+        ";
+        let (rulesets, warnings) = Parser::parse_with_warnings(css);
+        let tags: Vec<String> = rulesets
+            .iter()
+            .map(|ruleset| ruleset.selectors[0].tag.clone().unwrap())
+            .collect();
+
+        assert!(tags == ["a", "b", "c", "d", "e", "f"]);
+        assert!(rulesets
+            .iter()
+            .enumerate()
+            .all(|(order, ruleset)| ruleset.order == order));
+
+        let outer = vec![
+            cssom::MediaQuery::new(cssom::MediaType::Screen),
+            cssom::MediaQuery::new(cssom::MediaType::All).condition(cssom::MediaCondition::And(
+                vec![
+                    cssom::MediaCondition::Feature(cssom::MediaFeature::Range(
+                        "width".to_owned(),
+                        cssom::Comparison::GreaterOrEqual,
+                        cssom::MediaValue::Length(cssom::NumericValue::Px(600.0)),
+                    )),
+                    cssom::MediaCondition::Feature(cssom::MediaFeature::Range(
+                        "orientation".to_owned(),
+                        cssom::Comparison::Equal,
+                        cssom::MediaValue::Ident("landscape".to_owned()),
+                    )),
+                ],
+            )),
+        ];
+        let inner = vec![cssom::MediaQuery::new(cssom::MediaType::Print).negated(true)];
+
+        assert!(rulesets[0].media.is_empty());
+        assert!(rulesets[1].media == [outer.clone()]);
+        assert!(rulesets[2].media == [outer, inner]);
+        assert!(rulesets[3].media == [cssom::MediaQueryList::new()]);
+        assert!(rulesets[4].media.is_empty());
+        assert!(warnings == [Warning::InvalidRule("@media screen".to_owned())]);
+    }
+
+    #[test]
+    fn test_parser_parse_media_query_list() {
+        let parse = |css: &str| parse_media_query_list(&tokenizer::parse_component_values(css));
+        let feature = |name: &str, comparison: cssom::Comparison, value: cssom::MediaValue| {
+            let feature = cssom::MediaFeature::Range(name.to_owned(), comparison, value);
+            return cssom::MediaCondition::Feature(feature);
+        };
+        let query = |condition: cssom::MediaCondition| {
+            return vec![cssom::MediaQuery::new(cssom::MediaType::All).condition(condition)];
+        };
+        let px = |px: f64| cssom::MediaValue::Length(cssom::NumericValue::Px(px));
+
+        assert!(parse("ONLY Screen") == [cssom::MediaQuery::new(cssom::MediaType::Screen)]);
+        assert!(
+            parse("tv, all")
+                == [
+                    cssom::MediaQuery::new(cssom::MediaType::Other("tv".to_owned())),
+                    cssom::MediaQuery::new(cssom::MediaType::All),
+                ]
+        );
+        assert!(
+            parse("(width >= 600px)")
+                == query(feature(
+                    "width",
+                    cssom::Comparison::GreaterOrEqual,
+                    px(600.0)
+                ))
+        );
+        assert!(
+            parse("(400px < width <= 800px)")
+                == query(cssom::MediaCondition::And(vec![
+                    feature("width", cssom::Comparison::Greater, px(400.0)),
+                    feature("width", cssom::Comparison::LessOrEqual, px(800.0)),
+                ]))
+        );
+        assert!(parse("not (color) or (hover)") == [cssom::MediaQuery::not_all()]);
+        assert!(
+            parse("not ((color) or (hover: hover))")
+                == query(cssom::MediaCondition::Not(Box::new(
+                    cssom::MediaCondition::Or(vec![
+                        cssom::MediaCondition::Feature(cssom::MediaFeature::Boolean(
+                            "color".to_owned()
+                        )),
+                        feature(
+                            "hover",
+                            cssom::Comparison::Equal,
+                            cssom::MediaValue::Ident("hover".to_owned())
+                        ),
+                    ])
+                )))
+        );
+        assert!(
+            parse("(aspect-ratio: 16 / 9) and (max-resolution: 192dpi)")
+                == query(cssom::MediaCondition::And(vec![
+                    feature(
+                        "aspect-ratio",
+                        cssom::Comparison::Equal,
+                        cssom::MediaValue::Ratio(16.0, 9.0)
+                    ),
+                    feature(
+                        "resolution",
+                        cssom::Comparison::LessOrEqual,
+                        cssom::MediaValue::Resolution(2.0)
+                    ),
+                ]))
+        );
+        assert!(
+            parse("(width > 1px > 2px), custom(x)")
+                == [
+                    query(cssom::MediaCondition::Unknown(
+                        "(width > 1px > 2px)".to_owned()
+                    ))
+                    .remove(0),
+                    cssom::MediaQuery::not_all(),
+                ]
+        );
+
+        // Invalid queries don’t invalidate the rest of the list
+        assert!(
+            parse("screen and, print")
+                == [
+                    cssom::MediaQuery::not_all(),
+                    cssom::MediaQuery::new(cssom::MediaType::Print),
+                ]
+        );
+        assert!(parse("screen or (color)") == [cssom::MediaQuery::not_all()]);
+        assert!(parse("(color) and (hover) or (grid)") == [cssom::MediaQuery::not_all()]);
+    }
+
+    #[test]
+    fn test_parser_parse_import_rules() {
+        let stylesheets: HashMap<String, String> = HashMap::from([
+            (
+                "css/base.css".to_owned(),
+                "@import url(\"reset.css\"); a { color: red; }".to_owned(),
+            ),
+            (
+                "css/reset.css".to_owned(),
+                "@import \"main.css\"; b { color: red; }".to_owned(),
+            ),
+            ("print.css".to_owned(), "c { color: red; }".to_owned()),
+        ]);
+        let css = "
+            @charset \"utf-8\";
+            @import \"base.css\";
+            @import url(../print.css) layer(base.print) print;
+            @import \"missing.css\";
+            d { color: red; }
+            @import \"print.css\";
+        ";
+        let (rulesets, warnings) = Parser::parse_with_loader(css, "css/main.css", &stylesheets);
+        let tags: Vec<String> = rulesets
+            .iter()
+            .map(|ruleset| ruleset.selectors[0].tag.clone().unwrap())
+            .collect();
+
+        assert!(tags == ["b", "a", "c", "d"]);
+        assert!(rulesets
+            .iter()
+            .enumerate()
+            .all(|(order, ruleset)| ruleset.order == order));
+        assert!(rulesets[1].media.is_empty());
+        assert!(rulesets[2].media == [vec![cssom::MediaQuery::new(cssom::MediaType::Print)]]);
+        assert!(rulesets[3].media.is_empty());
+
+        assert!(
+            warnings
+                == [
+                    Warning::UnsupportedAtRule("@charset".to_owned()),
+                    Warning::CyclicImport("css/main.css".to_owned()),
+                    Warning::FailedImport("css/missing.css".to_owned()),
+                    Warning::InvalidRule("@import \"print.css\"".to_owned()),
+                ]
+        );
+    }
+
+    #[test]
+    fn test_parser_parse_import_prelude() {
+        let parse = |css: &str| Parser::new(css).parse_import_prelude();
+        let import = |url: &str, layer: Option<&str>, media: cssom::MediaQueryList| {
+            return Some(cssom::Import {
+                url: url.to_owned(),
+                layer: layer.map(|layer| layer.to_owned()),
+                media: media,
+            });
+        };
+
+        assert!(parse("'a.css'") == import("a.css", None, vec![]));
+        assert!(parse("url( 'a.css' )") == import("a.css", None, vec![]));
+        assert!(parse("url(a.css) LAYER") == import("a.css", Some(""), vec![]));
+        assert!(
+            parse("'a.css' layer(a.b) screen, print")
+                == import(
+                    "a.css",
+                    Some("a.b"),
+                    vec![
+                        cssom::MediaQuery::new(cssom::MediaType::Screen),
+                        cssom::MediaQuery::new(cssom::MediaType::Print),
+                    ]
+                )
+        );
+        assert!(parse("a.css").is_none());
+        assert!(parse("'a.css' layer(a. b)").is_none());
+
+        let (_, warnings) =
+            Parser::parse_with_warnings("@import 'a.css' { } @media print { @import 'b.css'; }");
+
+        assert!(
+            warnings
+                == [
+                    Warning::InvalidRule("@import \"a.css\"".to_owned()),
+                    Warning::InvalidRule("@import \"b.css\"".to_owned()),
+                ]
+        );
+    }
+
+    #[test]
+    fn test_parser_parse_layer_rules() {
+        let css = "
+            @layer reset, components;
+            a { color: red; }
+            @layer components {
+                b { color: red; }
+                @layer buttons { c { color: red; } }
+            }
+            @layer reset { d { color: red; } }
+            @layer { e { color: red; } }
+            @layer { @media print { f { color: red; } } }
+            @layer components.buttons { g { color: red; } }
+            @layer a, b { h { color: red; } }
+        ";
+        let (rulesets, warnings) = Parser::parse_with_warnings(css);
+        let layer = |index: usize| {
+            let layer = rulesets[index].layer.as_ref().unwrap();
+            return (layer.name(), layer.positions.clone());
+        };
+
+        assert!(rulesets.len() == 7);
+        assert!(rulesets[0].layer.is_none());
+        assert!(layer(1) == ("components".to_owned(), vec![1]));
+        assert!(layer(2) == ("components.buttons".to_owned(), vec![1, 0]));
+        assert!(layer(3) == ("reset".to_owned(), vec![0]));
+        assert!(layer(4) == ("".to_owned(), vec![2]));
+        assert!(layer(5) == ("".to_owned(), vec![3]));
+        assert!(rulesets[5].media.len() == 1);
+        assert!(layer(6) == ("components.buttons".to_owned(), vec![1, 0]));
+        assert!(warnings == [Warning::InvalidRule("@layer a, b".to_owned())]);
+    }
+
+    #[test]
+    fn test_parser_parse_import_layers() {
+        let stylesheets: HashMap<String, String> = HashMap::from([
+            (
+                "base.css".to_owned(),
+                "@layer reset { a { color: red; } } b { color: red; }".to_owned(),
+            ),
+            (
+                "theme.css".to_owned(),
+                "@layer colors; c { color: red; }".to_owned(),
+            ),
+        ]);
+        let css = "
+            @layer theme;
+            @import \"base.css\" layer(base);
+            @import \"theme.css\" layer(theme);
+            @import \"base.css\" layer;
+            @layer base.reset { d { color: red; } }
+        ";
+        let (rulesets, _) = Parser::parse_with_loader(css, "main.css", &stylesheets);
+        let layer = |index: usize| {
+            let layer = rulesets[index].layer.as_ref().unwrap();
+            return (layer.name(), layer.positions.clone());
+        };
+
+        assert!(rulesets.len() == 6);
+        assert!(layer(0) == ("base.reset".to_owned(), vec![1, 0]));
+        assert!(layer(1) == ("base".to_owned(), vec![1]));
+        assert!(layer(2) == ("theme".to_owned(), vec![0]));
+        assert!(layer(3) == (".reset".to_owned(), vec![2, 0]));
+        assert!(layer(4) == ("".to_owned(), vec![2]));
+        assert!(layer(5) == ("base.reset".to_owned(), vec![1, 0]));
+    }
+
+    #[test]
+    fn test_parser_parse_supports_rules() {
+        let css = "
+            @supports (display: grid) { a { color: red; } }
+            @supports not (display: grid) {
+                b { color: red; }
+                @media print { c { color: red; } }
+            }
+            @supports display: block { d { color: red; } }
+            @supports (display: block) and (color: red) or (width: 1px) { e { color: red; } }
+            @supports (display: block);
+            f { color: red; }
+        ";
+        let (rulesets, warnings) = Parser::parse_with_warnings(css);
+        let tags: Vec<String> = rulesets
+            .iter()
+            .map(|ruleset| ruleset.selectors[0].tag.clone().unwrap())
+            .collect();
+
+        assert!(tags == ["b", "c", "f"]);
+        assert!(rulesets
+            .iter()
+            .enumerate()
+            .all(|(order, ruleset)| ruleset.order == order));
+        assert!(rulesets[1].media.len() == 1);
+
+        assert!(
+            warnings
+                == [
+                    Warning::InvalidRule("@supports display: block".to_owned()),
+                    Warning::InvalidRule(
+                        "@supports (display: block) and (color: red) or (width: 1px)".to_owned()
+                    ),
+                    Warning::InvalidRule("@supports (display: block)".to_owned()),
+                ]
+        );
+    }
+
+    #[test]
+    fn test_parser_parse_supports_condition() {
+        let parse = |css: &str| Parser::new(css).parse_supports_condition();
+        let declaration = |property: &str, value: &str| {
+            let value = cssom::Value::Ident(value.to_owned());
+            let declarations = vec![cssom::Declaration::new(property, value)];
+            return cssom::SupportsCondition::Declaration(Some(declarations));
+        };
+
+        assert!(parse("(display: grid)") == Some(declaration("display", "grid")));
+        assert!(
+            parse("not ((display: grid) or (float: left))")
+                == Some(cssom::SupportsCondition::Not(Box::new(
+                    cssom::SupportsCondition::Or(vec![
+                        declaration("display", "grid"),
+                        declaration("float", "left"),
+                    ])
+                )))
+        );
+        assert!(parse("(width: 10zz)") == Some(cssom::SupportsCondition::Declaration(None)));
+        assert!(
+            parse("selector(a > b)")
+                == Some(cssom::SupportsCondition::Selector(Some(
+                    cssom::Selector::new()
+                        .tag("a")
+                        .combine(cssom::Combinator::Child, cssom::Selector::new().tag("b"))
+                )))
+        );
+        assert!(
+            parse("selector( p::before )")
+                == Some(cssom::SupportsCondition::Selector(Some(
+                    cssom::Selector::new()
+                        .tag("p")
+                        .pseudo_element(cssom::PseudoElement::Before)
+                )))
+        );
+        assert!(parse("selector(a, b)") == Some(cssom::SupportsCondition::Selector(None)));
+        assert!(parse("selector(p::before a)") == Some(cssom::SupportsCondition::Selector(None)));
+        assert!(parse("(1 + 1)") == Some(cssom::SupportsCondition::Unknown("(1 + 1)".to_owned())));
+        assert!(parse("not not (display: grid)").is_none());
+        assert!(parse("(display: grid) and").is_none());
+    }
+
+    #[test]
+    fn test_parser_parse_nested_rules() {
+        let css = "
+            .card {
+                color: red;
+                & > h2 { color: blue; }
+                &:hover { color: green; }
+                h3, > p { color: black; }
+                .dark & { color: white; }
+                width: 10px;
+            }
+        ";
+        let (rulesets, warnings) = Parser::parse_with_warnings(css);
+        let card = || cssom::Selector::new().class("card");
+        let selectors: Vec<cssom::Selectors> = rulesets
+            .iter()
+            .map(|ruleset| ruleset.selectors.clone())
+            .collect();
+
+        assert!(warnings.is_empty());
+        assert!(
+            selectors
+                == [
+                    cssom::Selectors::from([card()]),
+                    cssom::Selectors::from([
+                        card().combine(cssom::Combinator::Child, cssom::Selector::new().tag("h2"))
+                    ]),
+                    cssom::Selectors::from([card().pseudo_class(cssom::PseudoClass::Hover)]),
+                    cssom::Selectors::from([
+                        card().combine(
+                            cssom::Combinator::Descendant,
+                            cssom::Selector::new().tag("h3")
+                        ),
+                        card().combine(cssom::Combinator::Child, cssom::Selector::new().tag("p")),
+                    ]),
+                    cssom::Selectors::from([cssom::Selector::new().class("dark").combine(
+                        cssom::Combinator::Descendant,
+                        cssom::Selector::new()
+                            .pseudo_class(cssom::PseudoClass::Is(cssom::Selectors::from([card()])))
+                    )]),
+                ]
+        );
+        assert!(
+            rulesets[0]
+                .declarations
+                .iter()
+                .map(|declaration| declaration.property.as_str())
+                .collect::<Vec<&str>>()
+                == ["color", "width"]
+        );
+        assert!(rulesets
+            .iter()
+            .enumerate()
+            .all(|(order, ruleset)| ruleset.order == order));
+    }
+
+    #[test]
+    fn test_parser_parse_nested_selectors() {
+        let parse = |css: &str| Parser::parse_with_warnings(css);
+
+        // With several parent selectors, & is :is() of all of them and has
+        // the specificity of the most specific one
+        let (rulesets, _) = parse("#a, b { &.c { color: red; } }");
+        assert!(rulesets[1].selectors[0].specificity() == cssom::Specificity::new(1, 1, 0));
+
+        // Nested rules can be nested themselves
+        let (rulesets, _) = parse("nav { li { a:hover { color: red; } } }");
+        assert!(
+            rulesets[2].selectors
+                == cssom::Selectors::from([cssom::Selector::new()
+                    .tag("nav")
+                    .combine(
+                        cssom::Combinator::Descendant,
+                        cssom::Selector::new().tag("li")
+                    )
+                    .combine(
+                        cssom::Combinator::Descendant,
+                        cssom::Selector::new()
+                            .tag("a")
+                            .pseudo_class(cssom::PseudoClass::Hover)
+                    )])
+        );
+
+        // & can’t be joined with a parent of another tag
+        let (rulesets, _) = parse("a { div& { color: red; } }");
+        assert!(
+            rulesets[1].selectors
+                == cssom::Selectors::from([cssom::Selector::new().tag("div").pseudo_class(
+                    cssom::PseudoClass::Is(cssom::Selectors::from([
+                        cssom::Selector::new().tag("a")
+                    ]))
+                )])
+        );
+
+        // & can’t represent pseudo-elements, so such parents are left out
+        let (rulesets, warnings) = parse("a, p::before { & b { color: red; } } a::after { b {} }");
+        assert!(rulesets.len() == 3);
+        assert!(
+            rulesets[1].selectors
+                == cssom::Selectors::from([cssom::Selector::new().tag("a").combine(
+                    cssom::Combinator::Descendant,
+                    cssom::Selector::new().tag("b")
+                )])
+        );
+        assert!(warnings == [Warning::InvalidRule("b".to_owned())]);
+
+        // & in pseudo-class arguments, and at the top level
+        let (rulesets, _) = parse("a { :not(&) { color: red; } } & { color: red; }");
+        assert!(
+            rulesets[1].selectors[0].pseudo_classes
+                == [cssom::PseudoClass::Not(cssom::Selectors::from([
+                    cssom::Selector::new().pseudo_class(cssom::PseudoClass::Is(
+                        cssom::Selectors::from([cssom::Selector::new().tag("a")])
+                    ))
+                ]))]
+        );
+        assert!(rulesets[2].selectors[0].pseudo_classes == [cssom::PseudoClass::Root]);
+
+        // Custom properties can contain blocks, other declarations can’t
+        let (rulesets, warnings) = parse("a { --x: { b: c }; b, ! { color: red; } @page {} }");
+        assert!(rulesets.len() == 1);
+        assert!(rulesets[0].declarations.len() == 1);
+        assert!(
+            warnings
+                == [
+                    Warning::InvalidSelector("b, !".to_owned()),
+                    Warning::UnsupportedAtRule("@page".to_owned()),
+                ]
+        );
+    }
+
+    #[test]
+    fn test_parser_parse_nested_conditional_rules() {
+        let css = "
+            @media screen {
+                .a {
+                    @media (min-width: 1px) { color: red; & p { color: blue; } }
+                    @supports (display: block) { width: 1px; }
+                    @supports (display: nonsense) { height: 1px; }
+                    @supports nonsense { height: 1px; }
+                }
+            }
+        ";
+        let (rulesets, warnings) = Parser::parse_with_warnings(css);
+        let a = || cssom::Selector::new().class("a");
+        let media = |css: &str| parse_media_query_list(&tokenizer::parse_component_values(css));
+
+        assert!(rulesets.len() == 4);
+        assert!(warnings == [Warning::InvalidRule("@supports nonsense".to_owned())]);
+
+        assert!(rulesets[1].selectors == cssom::Selectors::from([a()]));
+        assert!(rulesets[1].media == [media("screen"), media("(min-width: 1px)")]);
+        assert!(rulesets[1].declarations.len() == 1);
+        assert!(rulesets[1].declarations.get("color").is_some());
+
+        assert!(
+            rulesets[2].selectors
+                == cssom::Selectors::from([a().combine(
+                    cssom::Combinator::Descendant,
+                    cssom::Selector::new().tag("p")
+                )])
+        );
+        assert!(rulesets[2].media == rulesets[1].media);
+
+        assert!(rulesets[3].selectors == cssom::Selectors::from([a()]));
+        assert!(rulesets[3].media == [media("screen")]);
+        assert!(rulesets[3].declarations.get("width").is_some());
+    }
+
+    #[test]
+    fn test_parser_parse_with_warnings() {
+        let css = "
+            h1 { color: red background: blue; width: 10px; }
+            p, { color: #333; }
+            ul ! li { color: #333; }
+            ol { height: 50%; width: 10zz; margin: 0 }
+            a { 10px; color: #xyz; }
+            @page { margin: 0; }
+            div
+        ";
+        let (rulesets, warnings) = Parser::parse_with_warnings(css);
+
+        assert!(rulesets.len() == 3);
+        assert!(rulesets[0].selectors[0] == cssom::Selector::new().tag("h1"));
+        assert!(rulesets[0].declarations.len() == 1);
+        assert!(
+            rulesets[0].declarations["width"].value
+                == cssom::Value::Numeric(cssom::NumericValue::Px(10.0))
+        );
+        assert!(rulesets[1].selectors[0] == cssom::Selector::new().tag("ol"));
+        assert!(rulesets[1].declarations.len() == 5);
+        assert!(rulesets[2].selectors[0] == cssom::Selector::new().tag("a"));
+        assert!(rulesets[2].declarations.len() == 0);
+
+        assert!(
+            warnings
+                == Warnings::from([
+                    Warning::InvalidDeclaration("color: red background: blue".to_owned()),
+                    Warning::InvalidSelector("p,".to_owned()),
+                    Warning::InvalidSelector("ul ! li".to_owned()),
+                    Warning::InvalidDeclaration("width: 10zz".to_owned()),
+                    Warning::InvalidDeclaration("10px".to_owned()),
+                    Warning::InvalidDeclaration("color: #xyz".to_owned()),
+                    Warning::UnsupportedAtRule("@page".to_owned()),
+                    Warning::InvalidRule("div".to_owned()),
+                ])
+        );
+    }
+
+    #[test]
+    fn test_parser_parse_value_invalid() {
+        assert!(Parser::new("").parse_value() == None);
+        assert!(Parser::new("10zz").parse_value() == None);
+        assert!(Parser::new("red blue: green").parse_value() == None);
+        assert!(Parser::new("url(a b)").parse_value() == None);
+        assert!(Parser::new("#abcde").parse_value() == None);
+    }
+
+    #[test]
+    fn test_parser_parse_value() {
+        assert!(
+            Parser::new("0").parse_value()
+                == Some(cssom::Value::Numeric(cssom::NumericValue::Zero))
+        );
+        assert!(Parser::new("auto").parse_value() == Some(cssom::Value::Ident("auto".to_owned())));
+    }
+
+    #[test]
+    fn test_parser_parse_math_functions() {
+        let value = |value: cssom::NumericValue| Box::new(cssom::Calculation::Value(value));
+        let calc = |css: &str| match Parser::new(css).parse_value() {
+            Some(cssom::Value::Numeric(cssom::NumericValue::Calc(calculation))) => {
+                Some(*calculation)
+            }
+            _ => None,
+        };
+
+        assert!(
+            calc("calc(100% - 2 * 16px)")
+                == Some(cssom::Calculation::Subtract(
+                    value(cssom::NumericValue::Percentage(100.0)),
+                    Box::new(cssom::Calculation::Multiply(
+                        value(cssom::NumericValue::Number(2.0)),
+                        value(cssom::NumericValue::Px(16.0)),
+                    )),
+                ))
+        );
+        assert!(
+            calc("CLAMP(1rem, 2vw, 2rem)")
+                == Some(cssom::Calculation::Clamp(
+                    value(cssom::NumericValue::Length(1.0, cssom::LengthUnit::Rem)),
+                    value(cssom::NumericValue::Length(2.0, cssom::LengthUnit::Vw)),
+                    value(cssom::NumericValue::Length(2.0, cssom::LengthUnit::Rem)),
+                ))
+        );
+        assert!(
+            calc("calc((1px + 0px) / 2)")
+                == Some(cssom::Calculation::Divide(
+                    Box::new(cssom::Calculation::Add(
+                        value(cssom::NumericValue::Px(1.0)),
+                        value(cssom::NumericValue::Px(0.0)),
+                    )),
+                    value(cssom::NumericValue::Number(2.0)),
+                ))
+        );
+        assert!(
+            calc("min(10px, max(1em, 5%))")
+                == Some(cssom::Calculation::Min(Vec::from([
+                    *value(cssom::NumericValue::Px(10.0)),
+                    cssom::Calculation::Max(Vec::from([
+                        *value(cssom::NumericValue::Length(1.0, cssom::LengthUnit::Em)),
+                        *value(cssom::NumericValue::Percentage(5.0)),
+                    ])),
+                ])))
+        );
+
+        // Incompatible types
+        assert!(calc("calc(1px + 2)") == None);
+        assert!(calc("calc(0 + 1px)") == None);
+        assert!(calc("calc(1px * 2px)") == None);
+        assert!(calc("calc(2 / 1px)") == None);
+        assert!(calc("min(1px, 2)") == None);
+
+        // Invalid syntax
+        assert!(calc("calc(1px -2px)") == None);
+        assert!(calc("calc(1px+2px)") == None);
+        assert!(calc("calc()") == None);
+        assert!(calc("min()") == None);
+        assert!(calc("clamp(1px, 2px)") == None);
+        assert!(calc("calc(1px, 2px)") == None);
+        assert!(calc("calc(auto)") == None);
+    }
+
+    #[test]
+    fn test_parser_parse_color_value() {
+        assert!(
+            Parser::new("#aabbcc").parse_color_value() == Some(cssom::Color::new(0xaa, 0xbb, 0xcc))
+        );
+        assert!(
+            Parser::new("#abc").parse_color_value() == Some(cssom::Color::new(0xaa, 0xbb, 0xcc))
+        );
+        assert!(
+            Parser::new("#abcd").parse_color_value()
+                == Some(cssom::Color::new(0xaa, 0xbb, 0xcc).alpha(0xdd))
+        );
+        assert!(
+            Parser::new("#11223344").parse_color_value()
+                == Some(cssom::Color::new(0x11, 0x22, 0x33).alpha(0x44))
+        );
+        assert!(Parser::new("#abcde").parse_color_value() == None);
+        assert!(Parser::new("#ggg").parse_color_value() == None);
+    }
+
+    #[test]
+    fn test_parser_parse_color_value_keywords() {
+        assert!(
+            Parser::new("Red").parse_value()
+                == Some(cssom::Value::Color(cssom::Color::new(255, 0, 0)))
+        );
+        assert!(
+            Parser::new("transparent").parse_value()
+                == Some(cssom::Value::Color(cssom::Color::new(0, 0, 0).alpha(0)))
+        );
+        assert!(Parser::new("currentColor").parse_value() == Some(cssom::Value::CurrentColor));
+        assert!(
+            Parser::new("reddish").parse_value() == Some(cssom::Value::Ident("reddish".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_parser_parse_color_functions() {
+        let color = |css: &str| Parser::new(css).parse_color_value();
+        let rgb = |r: u8, g: u8, b: u8| Some(cssom::Color::new(r, g, b));
+
+        assert!(color("rgb(255, 0, 51)") == rgb(255, 0, 51));
+        assert!(color("rgb(100%, 0%, 20%)") == rgb(255, 0, 51));
+        assert!(color("rgba(255, 0, 51, 0.5)") == Some(cssom::Color::new(255, 0, 51).alpha(128)));
+        assert!(color("rgb(255 0 51 / 50%)") == Some(cssom::Color::new(255, 0, 51).alpha(128)));
+        assert!(color("RGB(300 -10 51)") == rgb(255, 0, 51));
+        assert!(color("rgb(100% 0 20%)") == rgb(255, 0, 51));
+        assert!(color("hsl(120, 100%, 25%)") == rgb(0, 128, 0));
+        assert!(color("hsla(120deg, 100%, 25%, 1)") == rgb(0, 128, 0));
+        assert!(color("hsl(0.75turn 50 40)") == rgb(102, 51, 153));
+        assert!(
+            color("hsl(200grad 100% 50% / 0)") == Some(cssom::Color::new(0, 255, 255).alpha(0))
+        );
+        assert!(color("hwb(60 0% 0%)") == rgb(255, 255, 0));
+        assert!(color("hwb(0 20% 40% / 0.5)") == Some(cssom::Color::new(153, 51, 51).alpha(128)));
+
+        assert!(color("rgb(100%, 0, 20%)") == None);
+        assert!(color("rgb(255, 0)") == None);
+        assert!(color("rgb(255, 0, 51, 1, 1)") == None);
+        assert!(color("rgb(255 0 51 /)") == None);
+        assert!(color("rgb(255, 0 51)") == None);
+        assert!(color("hsl(120, 100, 25)") == None);
+        assert!(color("hwb(0, 20%, 40%)") == None);
+        assert!(color("hsl(1px 50% 50%)") == None);
+    }
+
+    #[test]
+    fn test_parser_parse_numeric_value() {
+        assert!(Parser::new("0").parse_numeric_value() == Some(cssom::NumericValue::Zero));
+        assert!(Parser::new("10px").parse_numeric_value() == Some(cssom::NumericValue::Px(10.0)));
+        assert!(
+            Parser::new("700").parse_numeric_value() == Some(cssom::NumericValue::Number(700.0))
+        );
+        assert!(
+            Parser::new("2EM").parse_numeric_value()
+                == Some(cssom::NumericValue::Length(2.0, cssom::LengthUnit::Em))
+        );
+        assert!(
+            Parser::new("50vmin").parse_numeric_value()
+                == Some(cssom::NumericValue::Length(50.0, cssom::LengthUnit::Vmin))
+        );
+        assert!(Parser::new("3furlongs").parse_numeric_value() == None);
+        assert!(Parser::new("-10px").parse_numeric_value() == Some(cssom::NumericValue::Px(-10.0)));
+        assert!(
+            Parser::new(".75").parse_numeric_value() == Some(cssom::NumericValue::Number(0.75))
+        );
+        assert!(
+            Parser::new("+2.5%").parse_numeric_value()
+                == Some(cssom::NumericValue::Percentage(2.5))
+        );
+        assert!(Parser::new("1e2px").parse_numeric_value() == Some(cssom::NumericValue::Px(100.0)));
+        assert!(
+            Parser::new("-1.5E-1em").parse_numeric_value()
+                == Some(cssom::NumericValue::Length(-0.15, cssom::LengthUnit::Em))
+        );
+        assert!(Parser::new("-0").parse_numeric_value() == Some(cssom::NumericValue::Zero));
+        assert!(
+            Parser::new("50%").parse_numeric_value() == Some(cssom::NumericValue::Percentage(50.0))
+        );
+    }
+
+    #[test]
+    fn test_parser_parse_value_lists() {
+        let ident = |ident: &str| cssom::Value::Ident(ident.to_owned());
+        let px = |px: f64| cssom::Value::Numeric(cssom::NumericValue::Px(px));
+
+        assert!(
+            Parser::new("Georgia, \"Times New Roman\", serif").parse_value()
+                == Some(cssom::Value::CommaList(Vec::from([
+                    ident("Georgia"),
+                    cssom::Value::String("Times New Roman".to_owned()),
+                    ident("serif"),
+                ])))
+        );
+        assert!(
+            Parser::new("0 1px 2px #000, inset 0 0 0 1px red").parse_value()
+                == Some(cssom::Value::CommaList(Vec::from([
+                    cssom::Value::SpaceList(Vec::from([
+                        cssom::Value::Numeric(cssom::NumericValue::Zero),
+                        px(1.0),
+                        px(2.0),
+                        cssom::Value::Color(cssom::Color::new(0, 0, 0)),
+                    ])),
+                    cssom::Value::SpaceList(Vec::from([
+                        ident("inset"),
+                        cssom::Value::Numeric(cssom::NumericValue::Zero),
+                        cssom::Value::Numeric(cssom::NumericValue::Zero),
+                        cssom::Value::Numeric(cssom::NumericValue::Zero),
+                        px(1.0),
+                        cssom::Value::Color(cssom::Color::new(255, 0, 0)),
+                    ])),
+                ])))
+        );
+        assert!(
+            Parser::new("12px/20px serif").parse_value()
+                == Some(cssom::Value::SpaceList(Vec::from([
+                    px(12.0),
+                    cssom::Value::Delim('/'),
+                    px(20.0),
+                    ident("serif"),
+                ])))
+        );
+        assert!(
+            Parser::new("attr(title) counter(item, decimal) foo()").parse_value()
+                == Some(cssom::Value::SpaceList(Vec::from([
+                    cssom::Value::Function("attr".to_owned(), Vec::from([ident("title")])),
+                    cssom::Value::Function(
+                        "counter".to_owned(),
+                        Vec::from([ident("item"), ident("decimal")])
+                    ),
+                    cssom::Value::Function("foo".to_owned(), Vec::new()),
+                ])))
+        );
+        assert!(
+            Parser::new("url(\"a.png\")").parse_value()
+                == Some(cssom::Value::Url("a.png".to_owned()))
+        );
+
+        assert!(Parser::new("serif /* fallback */;").parse_value() == Some(ident("serif")));
+        assert!(Parser::new("a, , b").parse_value() == None);
+        assert!(Parser::new("a,").parse_value() == None);
+        assert!(Parser::new("rgb(1,)").parse_value() == None);
+        assert!(Parser::new("a [b]").parse_value() == None);
+    }
+
+    #[test]
+    fn test_parser_parse_comments() {
+        let css = "
+            /* Lists */
+            ul /* unordered */, /* ordered */ ol/**/{
+                /* before */ padding-left /* a */ : /* b */ 10px /* c */;
+                color: #333 /* d */;
+                /* after */
+            }
+            /* Paragraphs */ p.lead/* lead */.intro { font: bold 12px/* size *//20px serif; }
+            /* unterminated";
+        let rulesets = Parser::parse(css);
+
+        assert!(rulesets.len() == 2);
+        assert!(
+            rulesets[0].selectors
+                == cssom::Selectors::from([
+                    cssom::Selector::new().tag("ul"),
+                    cssom::Selector::new().tag("ol"),
+                ])
+        );
+        assert!(rulesets[0].declarations.len() == 2);
+        assert!(
+            rulesets[0].declarations["padding-left"].value
+                == cssom::Value::Numeric(cssom::NumericValue::Px(10.0))
+        );
+        assert!(
+            rulesets[0].declarations["color"].value
+                == cssom::Value::Color(cssom::Color::new(0x33, 0x33, 0x33))
+        );
+        assert!(
+            rulesets[1].selectors[0]
+                == cssom::Selector::new().tag("p").class("lead").class("intro")
+        );
+        assert!(
+            rulesets[1].declarations["font-size"].value
+                == cssom::Value::Numeric(cssom::NumericValue::Px(12.0))
+        );
+        assert!(
+            rulesets[1].declarations["line-height"].value
+                == cssom::Value::Numeric(cssom::NumericValue::Px(20.0))
+        );
+        assert!(
+            rulesets[1].declarations["font-family"].value
+                == cssom::Value::Ident("serif".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_parser_parse_selectors() {
+        let mut parser = Parser::new("ul, ol { padding-left: 10px; }");
+        let selectors = parser.parse_selectors().unwrap();
+
+        assert!(selectors.len() == 2);
+        assert!(selectors[0] == cssom::Selector::new().tag("ul"));
+        assert!(selectors[1] == cssom::Selector::new().tag("ol"));
+
+        assert!(
+            selectors
+                == cssom::Selectors::from([
+                    cssom::Selector::new().tag("ul"),
+                    cssom::Selector::new().tag("ol"),
+                ])
+        );
+    }
+
+    #[test]
+    fn test_parser_parse_selector() {
+        let mut parser = Parser::new("ul, ol { padding-left: 10px; }");
+        let selector = parser.parse_selector().unwrap();
+
+        assert!(selector == cssom::Selector::new().tag("ul"));
+
+        // Trims whitespace
+        let mut parser = Parser::new("ul { padding-left: 10px; }");
+        let selector = parser.parse_selector().unwrap();
+
+        assert!(selector == cssom::Selector::new().tag("ul"));
+    }
+
+    #[test]
+    fn test_parser_parse_selector_class() {
+        let mut parser = Parser::new("p.class1.class2 { color: #333; }");
+        let selector = parser.parse_selector().unwrap();
+        let expected = cssom::Selector::new()
+            .tag("p")
+            .class("class1")
+            .class("class2");
+
+        assert!(selector == expected);
+
+        let mut parser = Parser::new(".class-1.class_2 { color: #333; }");
+        let selector = parser.parse_selector().unwrap();
+        let expected = cssom::Selector::new().class("class-1").class("class_2");
+
+        assert!(selector == expected);
+    }
+
+    #[test]
+    fn test_parser_parse_selector_id() {
+        let mut parser = Parser::new("p#intro { font-weight: italic; }");
+        let selector = parser.parse_selector().unwrap();
+        let expected = cssom::Selector::new().tag("p").id("intro");
+
+        assert!(selector == expected);
+
+        let mut parser = Parser::new("#intro { font-weight: italic; }");
+        let selector = parser.parse_selector().unwrap();
+        let expected = cssom::Selector::new().id("intro");
+
+        assert!(selector == expected);
+    }
+
+    #[test]
+    fn test_parser_parse_selector_combinators() {
+        let mut parser = Parser::new("nav a, ul>li , h1 + p.lead ~ p { color: red; }");
+        let selectors = parser.parse_selectors().unwrap();
+
+        assert!(
+            selectors
+                == cssom::Selectors::from([
+                    cssom::Selector::new().tag("nav").combine(
+                        cssom::Combinator::Descendant,
+                        cssom::Selector::new().tag("a")
+                    ),
+                    cssom::Selector::new()
+                        .tag("ul")
+                        .combine(cssom::Combinator::Child, cssom::Selector::new().tag("li")),
+                    cssom::Selector::new()
+                        .tag("h1")
+                        .combine(
+                            cssom::Combinator::NextSibling,
+                            cssom::Selector::new().tag("p").class("lead")
+                        )
+                        .combine(
+                            cssom::Combinator::SubsequentSibling,
+                            cssom::Selector::new().tag("p")
+                        ),
+                ])
+        );
+
+        assert!(Parser::new("ul > { }").parse_selectors().is_none());
+        assert!(Parser::new("> li { }").parse_selectors().is_none());
+        assert!(Parser::new("ul >> li { }").parse_selectors().is_none());
+    }
+
+    #[test]
+    fn test_parser_parse_selector_attrs() {
+        let mut parser = Parser::new("button[aria-expanded=\"true\"]");
+        let selector = parser.parse_selector().unwrap();
+        let expected = cssom::Selector::new()
+            .tag("button")
+            .attr("aria-expanded", "true");
+
+        assert!(selector == expected);
+
+        let mut parser = Parser::new("button[ aria-expanded  =  \"true\" ]");
+        let selector = parser.parse_selector().unwrap();
+
+        assert!(selector == expected);
+
+        // Unquoted values
+        let mut parser = Parser::new("button[aria-expanded=true]");
+        let selector = parser.parse_selector().unwrap();
+
+        assert!(selector == expected);
+    }
+
+    #[test]
+    fn test_parser_parse_selector_attr_operators() {
+        let parse = |css: &str| Parser::new(css).parse_selector();
+        let attr = |operator, value| {
+            return cssom::Selector::new()
+                .attr_selector(cssom::AttributeSelector::new("a", operator, value));
+        };
+
+        assert!(
+            parse("[a]")
+                == Some(
+                    cssom::Selector::new().attr_selector(cssom::AttributeSelector::exists("a"))
+                )
+        );
+        assert!(parse("[a~=b]") == Some(attr(cssom::AttributeOperator::Includes, "b")));
+        assert!(parse("[a|=b]") == Some(attr(cssom::AttributeOperator::DashMatch, "b")));
+        assert!(parse("[a^='b']") == Some(attr(cssom::AttributeOperator::Prefix, "b")));
+        assert!(parse("[a$=\"b\"]") == Some(attr(cssom::AttributeOperator::Suffix, "b")));
+        assert!(parse("[a *= b]") == Some(attr(cssom::AttributeOperator::Substring, "b")));
+
+        let insensitive = cssom::Selector::new().attr_selector(
+            cssom::AttributeSelector::new("a", cssom::AttributeOperator::Equals, "b")
+                .case_sensitivity(cssom::CaseSensitivity::Insensitive),
+        );
+        assert!(parse("[a=b i]") == Some(insensitive));
+
+        let sensitive = cssom::Selector::new().attr_selector(
+            cssom::AttributeSelector::new("a", cssom::AttributeOperator::Equals, "b")
+                .case_sensitivity(cssom::CaseSensitivity::Sensitive),
+        );
+        assert!(parse("[a='b' S]") == Some(sensitive));
+
+        assert!(parse("[a~b]") == None);
+        assert!(parse("[a ~ = b]") == None);
+        assert!(parse("[a=b x]") == None);
+        assert!(parse("[a=]") == None);
+        assert!(parse("[=b]") == None);
+    }
+
+    #[test]
+    fn test_parser_parse_selector_pseudo_classes() {
+        let parse = |css: &str| Parser::new(css).parse_selector();
+
+        assert!(
+            parse("li:first-child:LAST-CHILD")
+                == Some(
+                    cssom::Selector::new()
+                        .tag("li")
+                        .pseudo_class(cssom::PseudoClass::FirstChild)
+                        .pseudo_class(cssom::PseudoClass::LastChild)
+                )
+        );
+        assert!(
+            parse(":root") == Some(cssom::Selector::new().pseudo_class(cssom::PseudoClass::Root))
+        );
+        assert!(
+            parse("tr:nth-child(odd)")
+                == Some(
+                    cssom::Selector::new()
+                        .tag("tr")
+                        .pseudo_class(cssom::PseudoClass::NthChild(cssom::Nth::new(2, 1), None))
+                )
+        );
+        assert!(
+            parse(":nth-last-child(-n + 3 of li.item, p)")
+                == Some(
+                    cssom::Selector::new().pseudo_class(cssom::PseudoClass::NthLastChild(
+                        cssom::Nth::new(-1, 3),
+                        Some(cssom::Selectors::from([
+                            cssom::Selector::new().tag("li").class("item"),
+                            cssom::Selector::new().tag("p"),
+                        ]))
+                    ))
+                )
+        );
+        assert!(
+            parse(":nth-of-type(3)")
+                == Some(
+                    cssom::Selector::new()
+                        .pseudo_class(cssom::PseudoClass::NthOfType(cssom::Nth::new(0, 3)))
+                )
+        );
+
+        assert!(
+            parse("a:hover:active")
+                == Some(
+                    cssom::Selector::new()
+                        .tag("a")
+                        .pseudo_class(cssom::PseudoClass::Hover)
+                        .pseudo_class(cssom::PseudoClass::Active)
+                )
+        );
+        assert!(
+            parse("button:focus-visible")
+                == Some(
+                    cssom::Selector::new()
+                        .tag("button")
+                        .pseudo_class(cssom::PseudoClass::FocusVisible)
+                )
+        );
+
+        assert!(parse(":nth-of-type(2n of p)") == None);
+        assert!(parse(":nth-child(1 of ::before)") == None);
+        assert!(parse(":nth-child(1 of p, a::after)") == None);
+        assert!(parse(":nth-child(1 of)") == None);
+        assert!(parse(":nth-child()") == None);
+        assert!(parse(":hovered") == None);
+        assert!(parse("li: first-child") == None);
+    }
+
+    #[test]
+    fn test_parser_parse_selector_pseudo_elements() {
+        let parse = |css: &str| Parser::new(css).parse_selector();
+
+        assert!(
+            parse("q::before")
+                == Some(
+                    cssom::Selector::new()
+                        .tag("q")
+                        .pseudo_element(cssom::PseudoElement::Before)
+                )
+        );
+        assert!(
+            parse("ul > li.item:hover::AFTER")
+                == Some(
+                    cssom::Selector::new().tag("ul").combine(
+                        cssom::Combinator::Child,
+                        cssom::Selector::new()
+                            .tag("li")
+                            .class("item")
+                            .pseudo_class(cssom::PseudoClass::Hover)
+                            .pseudo_element(cssom::PseudoElement::After)
+                    )
+                )
+        );
+        assert!(
+            parse("p:before")
+                == Some(
+                    cssom::Selector::new()
+                        .tag("p")
+                        .pseudo_element(cssom::PseudoElement::Before)
+                )
+        );
+
+        assert!(parse("::before p") == None);
+        assert!(parse("p::before.a") == None);
+        assert!(parse("p::first-child") == None);
+        assert!(parse(":not(p::before)") == None);
+    }
+
+    #[test]
+    fn test_parser_parse_selector_logical_pseudo_classes() {
+        let parse = |css: &str| Parser::new(css).parse_selector();
+        let list = || {
+            return cssom::Selectors::from([
+                cssom::Selector::new().tag("h1"),
+                cssom::Selector::new()
+                    .tag("ul")
+                    .combine(cssom::Combinator::Child, cssom::Selector::new().tag("li")),
+            ]);
+        };
+
+        assert!(
+            parse("a:not(.disabled)")
+                == Some(
+                    cssom::Selector::new()
+                        .tag("a")
+                        .pseudo_class(cssom::PseudoClass::Not(cssom::Selectors::from([
+                            cssom::Selector::new().class("disabled")
+                        ])))
+                )
+        );
+        assert!(
+            parse(":is(h1, ul > li)")
+                == Some(cssom::Selector::new().pseudo_class(cssom::PseudoClass::Is(list())))
+        );
+        assert!(
+            parse(":where( h1 ,ul>li )")
+                == Some(cssom::Selector::new().pseudo_class(cssom::PseudoClass::Where(list())))
+        );
+
+        // :is() and :where() are forgiving, :not() isn’t
+        assert!(
+            parse(":is(h1, !, ul > li)")
+                == Some(cssom::Selector::new().pseudo_class(cssom::PseudoClass::Is(list())))
+        );
+        assert!(
+            parse(":where()")
+                == Some(
+                    cssom::Selector::new()
+                        .pseudo_class(cssom::PseudoClass::Where(cssom::Selectors::new()))
+                )
+        );
+        assert!(parse(":not(h1, !)") == None);
+        assert!(parse(":not()") == None);
+
+        assert!(
+            parse("a:has(> img, + p b)")
+                == Some(
+                    cssom::Selector::new()
+                        .tag("a")
+                        .pseudo_class(cssom::PseudoClass::Has(cssom::RelativeSelectors::from([
+                            cssom::RelativeSelector::new(
+                                cssom::Combinator::Child,
+                                cssom::Selector::new().tag("img")
+                            ),
+                            cssom::RelativeSelector::new(
+                                cssom::Combinator::NextSibling,
+                                cssom::Selector::new().tag("p").combine(
+                                    cssom::Combinator::Descendant,
+                                    cssom::Selector::new().tag("b")
+                                )
+                            ),
+                        ])))
+                )
+        );
+        assert!(parse(":has()") == None);
+        assert!(parse(":has(> > img)") == None);
+    }
+
+    #[test]
+    fn test_parse_nth() {
+        let nth = |css: &str| parse_nth(&tokenizer::parse_component_values(css));
+
+        assert!(nth("odd") == Some(cssom::Nth::new(2, 1)));
+        assert!(nth(" EVEN ") == Some(cssom::Nth::new(2, 0)));
+        assert!(nth("5") == Some(cssom::Nth::new(0, 5)));
+        assert!(nth("-5") == Some(cssom::Nth::new(0, -5)));
+        assert!(nth("+5") == Some(cssom::Nth::new(0, 5)));
+        assert!(nth("n") == Some(cssom::Nth::new(1, 0)));
+        assert!(nth("+n") == Some(cssom::Nth::new(1, 0)));
+        assert!(nth("-n+3") == Some(cssom::Nth::new(-1, 3)));
+        assert!(nth("2n+1") == Some(cssom::Nth::new(2, 1)));
+        assert!(nth("2n-1") == Some(cssom::Nth::new(2, -1)));
+        assert!(nth("2n- 1") == Some(cssom::Nth::new(2, -1)));
+        assert!(nth("2n + 1") == Some(cssom::Nth::new(2, 1)));
+        assert!(nth("-2N") == Some(cssom::Nth::new(-2, 0)));
+
+        assert!(nth("") == None);
+        assert!(nth("+ n") == None);
+        assert!(nth("2 n") == None);
+        assert!(nth("2n 1") == None);
+        assert!(nth("2n + -1") == None);
+        assert!(nth("1.5n") == None);
+        assert!(nth("foo") == None);
+    }
+
+    #[test]
+    fn test_parser_parse_selector_universal() {
+        let parse = |css: &str| Parser::new(css).parse_selector();
+
+        assert!(parse("*") == Some(cssom::Selector::new().universal()));
+        assert!(parse("*.a") == Some(cssom::Selector::new().universal().class("a")));
+        assert!(
+            parse("ul > *")
+                == Some(
+                    cssom::Selector::new()
+                        .tag("ul")
+                        .combine(cssom::Combinator::Child, cssom::Selector::new().universal())
+                )
+        );
+        assert!(parse("*p") == None);
     }
 }
@@ -1,10 +1,22 @@
+use crate::tokenizer::{self, ComponentValues, Token};
 use core::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Add, Index};
 use std::vec::Vec;
 
 pub struct Ruleset {
     pub selectors: Selectors,
     pub declarations: Declarations,
+    pub origin: Origin,
+    /// Position of the ruleset in its stylesheet, used to break ties in
+    /// the cascade.
+    pub order: usize,
+    /// The media query lists of the `@media` rules the ruleset is nested
+    /// in, outermost first. The ruleset only applies if all of them match.
+    pub media: Vec<MediaQueryList>,
+    /// The cascade layer the ruleset is in, `None` if it isn’t in one.
+    pub layer: Option<Layer>,
 }
 
 impl Ruleset {
@@ -12,28 +24,332 @@ impl Ruleset {
         return Self {
             selectors: selectors,
             declarations: declarations,
+            origin: Origin::Author,
+            order: 0,
+            media: Vec::new(),
+            layer: None,
+        };
+    }
+
+    pub fn order(mut self, order: usize) -> Self {
+        self.order = order;
+
+        return self;
+    }
+
+    pub fn origin(mut self, origin: Origin) -> Self {
+        self.origin = origin;
+
+        return self;
+    }
+
+    /// Nests the ruleset in an `@media` rule around the ones it is
+    /// already nested in.
+    pub fn media(mut self, media: MediaQueryList) -> Self {
+        self.media.insert(0, media);
+
+        return self;
+    }
+
+    pub fn layer(mut self, layer: Layer) -> Self {
+        self.layer = Some(layer);
+
+        return self;
+    }
+
+    /// Ranks the ruleset’s layer among the layers of its stylesheet, for
+    /// declarations of the same origin and importance. Later layers rank
+    /// higher, and rulesets outside of a layer higher than all layers.
+    /// Important declarations reverse that order.
+    pub fn layer_rank(&self, important: bool) -> Vec<usize> {
+        let mut rank = self
+            .layer
+            .as_ref()
+            .map_or(Vec::new(), |layer| layer.positions.clone());
+
+        // Rulesets directly in a layer rank above those in its sublayers
+        rank.push(usize::MAX);
+
+        if important {
+            rank = rank
+                .into_iter()
+                .map(|position| usize::MAX - position)
+                .collect();
+        }
+
+        return rank;
+    }
+}
+
+/// A cascade layer, declared by `@layer` or by `@import` with `layer()`.
+#[derive(PartialEq, Eq, Clone)]
+pub struct Layer {
+    /// The names of the layer and of the layers it is nested in, outermost
+    /// first. Anonymous layers have an empty name.
+    pub names: Vec<String>,
+    /// The position of each of these layers among the layers nested in the
+    /// same parent, in the order they are first declared.
+    pub positions: Vec<usize>,
+}
+
+impl Layer {
+    /// The implicit layer of all rulesets outside of layers.
+    pub fn root() -> Self {
+        return Self {
+            names: Vec::new(),
+            positions: Vec::new(),
+        };
+    }
+
+    pub fn child(mut self, name: &str, position: usize) -> Self {
+        self.names.push(name.to_owned());
+        self.positions.push(position);
+
+        return self;
+    }
+
+    /// Returns the dot-separated name, like `components.buttons`.
+    pub fn name(&self) -> String {
+        return self.names.join(".");
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        return self.names.last().map_or(false, |name| name.is_empty());
+    }
+
+    /// Checks whether the layer is nested directly in `parent`.
+    pub fn is_child_of(&self, parent: &Layer) -> bool {
+        return self.positions.len() == parent.positions.len() + 1
+            && self.positions.starts_with(&parent.positions);
+    }
+}
+
+/// An `@import` rule. Once the stylesheet it refers to is loaded, its
+/// rulesets take the place of the rule.
+#[derive(PartialEq, Clone)]
+pub struct Import {
+    pub url: String,
+    /// The cascade layer the imported rulesets are put in, an empty name
+    /// for an anonymous layer.
+    pub layer: Option<String>,
+    pub media: MediaQueryList,
+}
+
+/// A comma-separated list of media queries. It matches if any of its
+/// queries does, or if it is empty.
+pub type MediaQueryList = Vec<MediaQuery>;
+
+#[derive(PartialEq, Clone)]
+pub struct MediaQuery {
+    /// Whether the query starts with `not`.
+    pub negated: bool,
+    pub media_type: MediaType,
+    pub condition: Option<MediaCondition>,
+}
+
+impl MediaQuery {
+    pub fn new(media_type: MediaType) -> Self {
+        return Self {
+            negated: false,
+            media_type: media_type,
+            condition: None,
+        };
+    }
+
+    /// A query that never matches, which replaces invalid queries.
+    pub fn not_all() -> Self {
+        return Self::new(MediaType::All).negated(true);
+    }
+
+    pub fn negated(mut self, negated: bool) -> Self {
+        self.negated = negated;
+
+        return self;
+    }
+
+    pub fn condition(mut self, condition: MediaCondition) -> Self {
+        self.condition = Some(condition);
+
+        return self;
+    }
+}
+
+#[derive(PartialEq, Eq, Clone)]
+pub enum MediaType {
+    All,
+    Screen,
+    Print,
+    /// Any other media type, which is valid but never matches.
+    Other(String),
+}
+
+#[derive(PartialEq, Clone)]
+pub enum MediaCondition {
+    Feature(MediaFeature),
+    Not(Box<MediaCondition>),
+    And(Vec<MediaCondition>),
+    Or(Vec<MediaCondition>),
+    /// Anything else in parentheses, kept as source text. Its result is
+    /// always unknown.
+    Unknown(String),
+}
+
+#[derive(PartialEq, Clone)]
+pub enum MediaFeature {
+    /// A feature without a value, like `(color)`, which matches if the
+    /// feature isn’t zero or `none`.
+    Boolean(String),
+    /// A feature compared to a value. `min-` and `max-` prefixes are
+    /// turned into comparisons, `(width: 600px)` compares for equality.
+    Range(String, Comparison, MediaValue),
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    /// The comparison with its operands swapped, so `600px < width`
+    /// becomes `width > 600px`.
+    pub fn reversed(&self) -> Self {
+        return match self {
+            Self::Equal => Self::Equal,
+            Self::Less => Self::Greater,
+            Self::LessOrEqual => Self::GreaterOrEqual,
+            Self::Greater => Self::Less,
+            Self::GreaterOrEqual => Self::LessOrEqual,
+        };
+    }
+}
+
+#[derive(PartialEq, Clone)]
+pub enum MediaValue {
+    Length(NumericValue),
+    Number(f64),
+    /// Dots per pixel.
+    Resolution(f64),
+    Ratio(f64, f64),
+    Ident(String),
+}
+
+/// The condition of an `@supports` rule.
+#[derive(PartialEq, Clone)]
+pub enum SupportsCondition {
+    /// A declaration in parentheses, expanded into its longhands, or
+    /// `None` if it doesn’t parse.
+    Declaration(Option<Vec<Declaration>>),
+    /// A `selector()` function, `None` if the selector doesn’t parse.
+    Selector(Option<Selector>),
+    Not(Box<SupportsCondition>),
+    And(Vec<SupportsCondition>),
+    Or(Vec<SupportsCondition>),
+    /// Anything else in parentheses, or any other function, kept as
+    /// source text. It is never supported.
+    Unknown(String),
+}
+
+/// Where a stylesheet comes from: the browser’s defaults, the user’s
+/// preferences or the page itself.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Origin {
+    UserAgent,
+    User,
+    Author,
+}
+
+#[derive(PartialEq, Clone)]
+pub struct Declaration {
+    pub property: String,
+    pub value: Value,
+    pub important: bool,
+}
+
+impl Declaration {
+    pub fn new(property: &str, value: Value) -> Self {
+        return Self {
+            property: String::from(property),
+            value: value,
+            important: false,
+        };
+    }
+
+    pub fn important(mut self) -> Self {
+        self.important = true;
+
+        return self;
+    }
+
+    /// Returns the declaration’s precedence in the cascade, lowest first.
+    /// Important declarations reverse the order of origins, so user-agent
+    /// and user styles can’t be overridden by `!important` author styles.
+    pub fn cascade_level(&self, origin: Origin) -> u8 {
+        return match (self.important, origin) {
+            (false, Origin::UserAgent) => 0,
+            (false, Origin::User) => 1,
+            (false, Origin::Author) => 2,
+            (true, Origin::Author) => 3,
+            (true, Origin::User) => 4,
+            (true, Origin::UserAgent) => 5,
         };
     }
 }
 
+/// A compound selector, like `ul.nav`, optionally joined to the compound
+/// selector to its left by a combinator. `nav > ul.nav` is stored as
+/// `ul.nav` with a child combinator pointing to `nav`, which is the order
+/// selectors are matched in.
 #[derive(PartialEq, Eq, Clone)]
 pub struct Selector {
+    pub universal: bool,
     pub tag: Option<String>,
     pub id: Option<String>,
     pub classes: Vec<String>,
-    pub attrs: HashMap<String, String>,
+    pub attrs: Vec<AttributeSelector>,
+    pub pseudo_classes: Vec<PseudoClass>,
+    /// Only the rightmost compound selector can have a pseudo-element.
+    pub pseudo_element: Option<PseudoElement>,
+    pub combinator: Option<(Combinator, Box<Selector>)>,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Combinator {
+    /// `a b`
+    Descendant,
+    /// `a > b`
+    Child,
+    /// `a + b`
+    NextSibling,
+    /// `a ~ b`
+    SubsequentSibling,
 }
 
 impl Selector {
     pub fn new() -> Self {
         return Selector {
+            universal: false,
             tag: None,
             id: None,
             classes: Vec::new(),
-            attrs: HashMap::new(),
+            attrs: Vec::new(),
+            pseudo_classes: Vec::new(),
+            pseudo_element: None,
+            combinator: None,
         };
     }
 
+    /// Marks the selector as universal (`*`). This doesn’t change what it
+    /// matches, but is kept for serialization.
+    pub fn universal(mut self) -> Self {
+        self.universal = true;
+
+        return self;
+    }
+
     pub fn tag(mut self, tag: &str) -> Self {
         self.tag = Some(String::from(tag));
 
@@ -52,21 +368,237 @@ impl Selector {
         return self;
     }
 
-    pub fn attr(mut self, name: &str, value: &str) -> Self {
-        self.attrs.insert(String::from(name), String::from(value));
+    /// Adds an `[name="value"]` attribute selector.
+    pub fn attr(self, name: &str, value: &str) -> Self {
+        return self.attr_selector(AttributeSelector::new(
+            name,
+            AttributeOperator::Equals,
+            value,
+        ));
+    }
+
+    pub fn attr_selector(mut self, attr: AttributeSelector) -> Self {
+        self.attrs.push(attr);
+
+        return self;
+    }
+
+    pub fn pseudo_class(mut self, pseudo_class: PseudoClass) -> Self {
+        self.pseudo_classes.push(pseudo_class);
+
+        return self;
+    }
+
+    pub fn pseudo_element(mut self, pseudo_element: PseudoElement) -> Self {
+        self.pseudo_element = Some(pseudo_element);
 
         return self;
     }
 
+    /// Joins two selectors, `a.combine(Combinator::Child, b)` is `a > b`.
+    pub fn combine(self, combinator: Combinator, mut other: Selector) -> Self {
+        other.combinator = match other.combinator.take() {
+            None => Some((combinator, Box::new(self))),
+            Some((inner, left)) => Some((inner, Box::new(self.combine(combinator, *left)))),
+        };
+
+        return other;
+    }
+
+    /// Returns the specificity of the whole selector, summed across all
+    /// compound selectors.
     pub fn specificity(&self) -> Specificity {
-        return Specificity {
+        let mut specificity = Specificity {
             ids: if self.id.is_some() { 1 } else { 0 },
             classes: self.classes.len() + self.attrs.len(),
             elements: if self.tag.is_some() { 1 } else { 0 },
         };
+
+        if self.pseudo_element.is_some() {
+            specificity.elements += 1;
+        }
+
+        for pseudo_class in &self.pseudo_classes {
+            specificity = specificity + pseudo_class.specificity();
+        }
+
+        return match &self.combinator {
+            Some((_, left)) => specificity + left.specificity(),
+            None => specificity,
+        };
+    }
+}
+
+#[derive(PartialEq, Eq, Clone)]
+pub struct AttributeSelector {
+    pub name: String,
+    pub operator: AttributeOperator,
+    pub value: String,
+    pub case_sensitivity: CaseSensitivity,
+}
+
+impl AttributeSelector {
+    pub fn new(name: &str, operator: AttributeOperator, value: &str) -> Self {
+        return Self {
+            name: String::from(name),
+            operator: operator,
+            value: String::from(value),
+            case_sensitivity: CaseSensitivity::Default,
+        };
     }
+
+    /// Creates an `[name]` selector that only checks whether the attribute
+    /// is present.
+    pub fn exists(name: &str) -> Self {
+        return Self::new(name, AttributeOperator::Exists, "");
+    }
+
+    pub fn case_sensitivity(mut self, case_sensitivity: CaseSensitivity) -> Self {
+        self.case_sensitivity = case_sensitivity;
+
+        return self;
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum AttributeOperator {
+    /// `[attr]`
+    Exists,
+    /// `[attr=value]`
+    Equals,
+    /// `[attr~=value]`, one of the whitespace-separated words equals value
+    Includes,
+    /// `[attr|=value]`, equals value or starts with value followed by `-`
+    DashMatch,
+    /// `[attr^=value]`
+    Prefix,
+    /// `[attr$=value]`
+    Suffix,
+    /// `[attr*=value]`
+    Substring,
+}
+
+/// The `i` and `s` flags of attribute selectors. Without a flag, values
+/// are compared case-sensitively.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum CaseSensitivity {
+    Default,
+    Insensitive,
+    Sensitive,
+}
+
+#[derive(PartialEq, Eq, Clone)]
+pub enum PseudoClass {
+    Root,
+    Empty,
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    FirstOfType,
+    LastOfType,
+    OnlyOfType,
+    /// `:nth-child(An+B [of S])`
+    NthChild(Nth, Option<Selectors>),
+    /// `:nth-last-child(An+B [of S])`
+    NthLastChild(Nth, Option<Selectors>),
+    NthOfType(Nth),
+    NthLastOfType(Nth),
+    Not(Selectors),
+    Is(Selectors),
+    Where(Selectors),
+    Has(RelativeSelectors),
+    Hover,
+    Active,
+    Focus,
+    FocusVisible,
+}
+
+impl PseudoClass {
+    /// Pseudo-classes count like classes. `:nth-child(… of S)` adds the
+    /// specificity of the most specific selector in S. The logical
+    /// pseudo-classes are replaced by the specificity of their most
+    /// specific argument, except for `:where()`, which is always zero.
+    pub fn specificity(&self) -> Specificity {
+        let specificity = Specificity::new(0, 1, 0);
+
+        return match self {
+            PseudoClass::NthChild(_, Some(selectors))
+            | PseudoClass::NthLastChild(_, Some(selectors)) => {
+                specificity + max_specificity(selectors)
+            }
+            PseudoClass::Not(selectors) | PseudoClass::Is(selectors) => max_specificity(selectors),
+            PseudoClass::Where(_) => Specificity::new(0, 0, 0),
+            PseudoClass::Has(relative_selectors) => relative_selectors
+                .iter()
+                .map(|relative| relative.selector.specificity())
+                .max()
+                .unwrap_or(Specificity::new(0, 0, 0)),
+            _ => specificity,
+        };
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum PseudoElement {
+    Before,
+    After,
+}
+
+/// A selector relative to an anchor element, as used by `:has()`.
+/// `:has(> img)` is the child combinator and `img`.
+#[derive(PartialEq, Eq, Clone)]
+pub struct RelativeSelector {
+    pub combinator: Combinator,
+    pub selector: Selector,
+}
+
+impl RelativeSelector {
+    pub fn new(combinator: Combinator, selector: Selector) -> Self {
+        return Self {
+            combinator: combinator,
+            selector: selector,
+        };
+    }
+}
+
+pub type RelativeSelectors = Vec<RelativeSelector>;
+
+/// The `An+B` notation, matching every element whose 1-based position
+/// equals `A * n + B` for some n >= 0.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct Nth {
+    pub a: i32,
+    pub b: i32,
 }
 
+impl Nth {
+    pub fn new(a: i32, b: i32) -> Self {
+        return Self { a: a, b: b };
+    }
+
+    pub fn matches(&self, position: usize) -> bool {
+        // A and B can be anywhere in the range of i32, so their difference
+        // and quotient only fit into i64
+        let (a, b) = (self.a as i64, self.b as i64);
+        let offset = position as i64 - b;
+
+        if a == 0 {
+            return offset == 0;
+        }
+
+        return offset % a == 0 && offset / a >= 0;
+    }
+}
+
+fn max_specificity(selectors: &Selectors) -> Specificity {
+    return selectors
+        .iter()
+        .map(|selector| selector.specificity())
+        .max()
+        .unwrap_or(Specificity::new(0, 0, 0));
+}
+
+#[derive(Clone, Copy)]
 pub struct Specificity {
     ids: usize,
     classes: usize,
@@ -83,6 +615,18 @@ impl Specificity {
     }
 }
 
+impl Add for Specificity {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        return Specificity {
+            ids: self.ids + other.ids,
+            classes: self.classes + other.classes,
+            elements: self.elements + other.elements,
+        };
+    }
+}
+
 impl PartialEq for Specificity {
     fn eq(&self, other: &Self) -> bool {
         return self.ids == other.ids
@@ -113,18 +657,165 @@ impl Ord for Specificity {
     }
 }
 
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Clone)]
 pub enum Value {
+    /// A keyword, like `auto` or `sans-serif`.
+    Ident(String),
+    /// A quoted string, without its quotes.
     String(String),
     Numeric(NumericValue),
     Color(Color),
+    Url(String),
+    /// The `currentColor` keyword, which refers to the `color` property.
+    CurrentColor,
+    /// A delimiter separating parts of a value, like the `/` in `16 / 9`.
+    Delim(char),
+    /// A function and its comma-separated arguments, like `attr(title)`.
+    Function(String, Values),
+    /// Values separated by whitespace, like `1px solid red`.
+    SpaceList(Values),
+    /// Values separated by commas, like `Georgia, serif`.
+    CommaList(Values),
+    /// The value of a custom property, which is only parsed where `var()`
+    /// substitutes it.
+    Tokens(ComponentValues),
+    /// A value using `var()`, which is parsed once it is substituted, and
+    /// the property it was declared for. Longhands set by a shorthand refer
+    /// to the shorthand.
+    Pending(String, ComponentValues),
 }
 
-#[derive(PartialEq, Eq, Clone)]
+impl Value {
+    pub fn ident(&self) -> Option<&str> {
+        return match self {
+            Value::Ident(ident) => Some(ident),
+            _ => None,
+        };
+    }
+
+    /// Returns the items of a space-separated list, or the value itself
+    /// if it isn’t one.
+    pub fn space_separated(&self) -> &[Value] {
+        return match self {
+            Value::SpaceList(values) => values,
+            _ => std::slice::from_ref(self),
+        };
+    }
+}
+
+pub type Values = Vec<Value>;
+
+#[derive(PartialEq, Clone)]
 pub enum NumericValue {
     Zero,
-    Px(usize),
-    Percentage(usize),
+    /// A unitless number, like a `flex-grow` or `font-weight`.
+    Number(f64),
+    Px(f64),
+    Percentage(f64),
+    /// A length in a unit other than pixels, resolved during layout.
+    Length(f64, LengthUnit),
+    /// A math function like `calc()`, resolved during layout.
+    Calc(Box<Calculation>),
+}
+
+/// The expression tree of a math function. `calc()` itself doesn’t need a
+/// node, it only wraps its argument.
+#[derive(PartialEq, Clone)]
+pub enum Calculation {
+    Value(NumericValue),
+    Add(Box<Calculation>, Box<Calculation>),
+    Subtract(Box<Calculation>, Box<Calculation>),
+    Multiply(Box<Calculation>, Box<Calculation>),
+    Divide(Box<Calculation>, Box<Calculation>),
+    Min(Vec<Calculation>),
+    Max(Vec<Calculation>),
+    /// The minimum, preferred and maximum value.
+    Clamp(Box<Calculation>, Box<Calculation>, Box<Calculation>),
+}
+
+impl Calculation {
+    /// Returns the type the calculation resolves to, or `None` if it
+    /// combines incompatible types, like `1px + 2`.
+    pub fn resolved_type(&self) -> Option<CalculationType> {
+        return match self {
+            Calculation::Value(NumericValue::Number(_)) => Some(CalculationType::Number),
+            Calculation::Value(NumericValue::Percentage(_)) => Some(CalculationType::Percentage),
+            Calculation::Value(NumericValue::Calc(calculation)) => calculation.resolved_type(),
+            Calculation::Value(_) => Some(CalculationType::Length),
+
+            Calculation::Add(left, right) | Calculation::Subtract(left, right) => {
+                CalculationType::sum(left.resolved_type()?, right.resolved_type()?)
+            }
+
+            // One of the factors has to be a number...
+            Calculation::Multiply(left, right) => {
+                match (left.resolved_type()?, right.resolved_type()?) {
+                    (CalculationType::Number, other) | (other, CalculationType::Number) => {
+                        Some(other)
+                    }
+                    _ => None,
+                }
+            }
+
+            // ...and so does the divisor
+            Calculation::Divide(left, right) => match right.resolved_type()? {
+                CalculationType::Number => left.resolved_type(),
+                _ => None,
+            },
+
+            Calculation::Min(arguments) | Calculation::Max(arguments) => {
+                let mut types = arguments.iter().map(|argument| argument.resolved_type());
+                let first = types.next()??;
+
+                types.try_fold(first, |acc, other| CalculationType::sum(acc, other?))
+            }
+
+            Calculation::Clamp(min, value, max) => CalculationType::sum(
+                CalculationType::sum(min.resolved_type()?, value.resolved_type()?)?,
+                max.resolved_type()?,
+            ),
+        };
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum CalculationType {
+    Number,
+    Length,
+    Percentage,
+    /// A mix of lengths and percentages, like `100% - 16px`.
+    LengthPercentage,
+}
+
+impl CalculationType {
+    /// The type of a sum, or `None` if the types can’t be added.
+    fn sum(left: Self, right: Self) -> Option<Self> {
+        if left == right {
+            return Some(left);
+        }
+
+        if left == CalculationType::Number || right == CalculationType::Number {
+            return None;
+        }
+
+        return Some(CalculationType::LengthPercentage);
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum LengthUnit {
+    Em,
+    Rem,
+    Ex,
+    Ch,
+    Vw,
+    Vh,
+    Vmin,
+    Vmax,
+    Pt,
+    Cm,
+    Mm,
+    In,
 }
 
 #[derive(PartialEq, Eq, Clone)]
@@ -132,52 +823,1435 @@ pub struct Color {
     r: u8,
     g: u8,
     b: u8,
+    a: u8,
 }
 
 impl Color {
     pub fn new(r: u8, g: u8, b: u8) -> Self {
-        return Self { r: r, g: g, b: b };
+        return Self {
+            r: r,
+            g: g,
+            b: b,
+            a: 255,
+        };
     }
 
-    pub fn as_u32(&self) -> u32 {
-        return u32::from_be_bytes([0, self.r, self.g, self.b]);
+    pub fn alpha(mut self, a: u8) -> Self {
+        self.a = a;
+        return self;
     }
-}
 
-pub type Rulesets = Vec<Ruleset>;
-pub type Selectors = Vec<Selector>;
-pub type Declarations = HashMap<String, Value>;
+    /// Looks up one of the named colors, or `transparent`, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        if name == "transparent" {
+            return Some(Self::new(0, 0, 0).alpha(0));
+        }
 
-    #[test]
-    fn test_specificity_eq() {
-        let one = &Specificity::new(1, 1, 1);
-        let two = &Specificity::new(1, 1, 1);
-        let three = &Specificity::new(2, 1, 1);
+        let index = NAMED_COLORS
+            .binary_search_by(|(other, _)| other.cmp(&name.as_ref()))
+            .ok()?;
+        let [_, r, g, b] = NAMED_COLORS[index].1.to_be_bytes();
 
-        assert!(one.eq(two) == true);
-        assert!(one.eq(three) == false);
-        assert!(two.eq(three) == false);
+        return Some(Self::new(r, g, b));
     }
 
-    #[test]
-    fn test_specificity_cmp() {
-        let greater = &Specificity::new(1, 0, 0);
-        let less = &Specificity::new(0, 1000, 1000);
+    /// The hue is given in degrees, saturation and lightness range from 0
+    /// to 1.
+    pub fn from_hsl(hue: f64, saturation: f64, lightness: f64) -> Self {
+        return Self::from_channels(hsl_channels(hue, saturation, lightness));
+    }
 
-        assert!(greater.cmp(less) == Ordering::Greater);
+    /// The hue is given in degrees, whiteness and blackness range from 0
+    /// to 1.
+    pub fn from_hwb(hue: f64, whiteness: f64, blackness: f64) -> Self {
+        // Too much white and black mix to a gray
+        if whiteness + blackness >= 1.0 {
+            let gray = whiteness / (whiteness + blackness);
+            return Self::from_channels([gray, gray, gray]);
+        }
 
-        let greater = &Specificity::new(0, 1, 0);
-        let less = &Specificity::new(0, 0, 1000);
+        let channels = hsl_channels(hue, 1.0, 0.5)
+            .map(|channel| channel * (1.0 - whiteness - blackness) + whiteness);
 
-        assert!(greater.cmp(less) == Ordering::Greater);
+        return Self::from_channels(channels);
+    }
 
-        let greater = &Specificity::new(0, 0, 1);
-        let less = &Specificity::new(0, 0, 0);
+    fn from_channels(channels: [f64; 3]) -> Self {
+        let [r, g, b] = channels.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+        return Self::new(r, g, b);
+    }
 
-        assert!(greater.cmp(less) == Ordering::Greater);
+    /// Returns the color as `0xRRGGBB`, ignoring alpha.
+    pub fn as_u32(&self) -> u32 {
+        return u32::from_be_bytes([0, self.r, self.g, self.b]);
+    }
+
+    /// Composites the color over an opaque `0xRRGGBB` color.
+    pub fn blend(&self, background: u32) -> u32 {
+        let [_, r, g, b] = background.to_be_bytes();
+        let alpha = self.a as u32;
+        let mix = |channel: u8, background: u8| {
+            return ((channel as u32 * alpha + background as u32 * (255 - alpha) + 127) / 255)
+                as u8;
+        };
+
+        return u32::from_be_bytes([0, mix(self.r, r), mix(self.g, g), mix(self.b, b)]);
+    }
+}
+
+/// Converts HSL to red, green and blue channels ranging from 0 to 1, as
+/// described in CSS Color Level 4.
+fn hsl_channels(hue: f64, saturation: f64, lightness: f64) -> [f64; 3] {
+    let hue = hue.rem_euclid(360.0);
+    let saturation = saturation.clamp(0.0, 1.0);
+    let lightness = lightness.clamp(0.0, 1.0);
+
+    let channel = |n: f64| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+
+        return lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0);
+    };
+
+    return [channel(0.0), channel(8.0), channel(4.0)];
+}
+
+/// The named colors of CSS Color Level 4, sorted by name.
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+pub type Rulesets = Vec<Ruleset>;
+
+/// The rulesets of a stylesheet, with the layers declared in it. Layers
+/// keep their position even without rulesets, like those of `@layer reset,
+/// base;`.
+pub struct Stylesheet {
+    pub rulesets: Rulesets,
+    /// The declared layers, including the ones they are nested in.
+    pub layers: Vec<Layer>,
+}
+
+impl Stylesheet {
+    pub fn new(rulesets: Rulesets, layers: Vec<Layer>) -> Self {
+        return Self {
+            rulesets: rulesets,
+            layers: layers,
+        };
+    }
+
+    pub fn origin(mut self, origin: Origin) -> Self {
+        self.rulesets = self
+            .rulesets
+            .into_iter()
+            .map(|ruleset| ruleset.origin(origin))
+            .collect();
+
+        return self;
+    }
+
+    /// Appends the rulesets of another stylesheet, which come after all
+    /// existing rulesets in source order.
+    ///
+    /// Layers of the other stylesheet come after the existing layers nested
+    /// in the same parent, unless one of them has the same name.
+    pub fn append(&mut self, other: Stylesheet) -> () {
+        let offset = self
+            .rulesets
+            .iter()
+            .map(|ruleset| ruleset.order + 1)
+            .max()
+            .unwrap_or(0);
+
+        let layers = self.layers.clone();
+
+        for layer in other.layers {
+            let merged = merge_layer(&layers, &layer);
+
+            if !self.layers.contains(&merged) {
+                self.layers.push(merged);
+            }
+        }
+
+        for mut ruleset in other.rulesets {
+            ruleset.layer = ruleset.layer.map(|layer| merge_layer(&layers, &layer));
+
+            let order = ruleset.order + offset;
+            self.rulesets.push(ruleset.order(order));
+        }
+    }
+}
+
+/// Finds the place of a layer from another stylesheet among the existing
+/// layers, one nesting level at a time.
+fn merge_layer(layers: &[Layer], layer: &Layer) -> Layer {
+    let mut merged = Layer::root();
+
+    for (name, position) in layer.names.iter().zip(&layer.positions) {
+        let siblings: Vec<&Layer> = layers
+            .iter()
+            .filter(|other| other.is_child_of(&merged))
+            .collect();
+        let existing = siblings
+            .iter()
+            .find(|other| !name.is_empty() && other.names.last() == Some(name));
+
+        merged = match existing {
+            Some(existing) => (*existing).clone(),
+            None => {
+                let offset = siblings
+                    .iter()
+                    .map(|sibling| sibling.positions.last().unwrap() + 1)
+                    .max()
+                    .unwrap_or(0);
+
+                merged.child(name, position + offset)
+            }
+        };
+    }
+
+    return merged;
+}
+
+pub type Selectors = Vec<Selector>;
+
+/// The declarations of a block in source order. Duplicate properties are
+/// kept, as later declarations only win the cascade if they are valid.
+#[derive(PartialEq, Clone)]
+pub struct Declarations {
+    entries: Vec<Declaration>,
+}
+
+impl Declarations {
+    pub fn new() -> Self {
+        return Self {
+            entries: Vec::new(),
+        };
+    }
+
+    pub fn push(&mut self, declaration: Declaration) -> () {
+        self.entries.push(declaration);
+    }
+
+    /// Returns the declaration that wins within this block: the last
+    /// important one, or else the last one.
+    pub fn get(&self, property: &str) -> Option<&Declaration> {
+        let mut declarations = self
+            .entries
+            .iter()
+            .rev()
+            .filter(|declaration| declaration.property == property);
+
+        return declarations
+            .clone()
+            .find(|declaration| declaration.important)
+            .or(declarations.next());
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Declaration> {
+        return self.entries.iter();
+    }
+}
+
+impl<'a> IntoIterator for &'a Declarations {
+    type Item = &'a Declaration;
+    type IntoIter = std::slice::Iter<'a, Declaration>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        return self.entries.iter();
+    }
+}
+
+impl Index<&str> for Declarations {
+    type Output = Declaration;
+
+    fn index(&self, property: &str) -> &Declaration {
+        return self.get(property).expect("no declaration for property");
+    }
+}
+
+/// The value of each property for an element, after the cascade.
+pub type ComputedStyle = HashMap<String, Value>;
+
+/// Serializes rulesets into CSS text that parses back into the same
+/// rulesets. Rulesets are grouped by cascade layer, in layer order, so the
+/// layers are declared in the same order. That doesn’t change how they
+/// cascade, source order only matters within the same layer.
+pub fn serialize(rulesets: &Rulesets) -> String {
+    let mut rulesets: Vec<&Ruleset> = rulesets.iter().collect();
+    rulesets.sort_by_key(|ruleset| {
+        let positions = ruleset
+            .layer
+            .as_ref()
+            .map_or(Vec::new(), |layer| layer.positions.clone());
+
+        return (positions, ruleset.order);
+    });
+
+    let mut output = String::new();
+    let mut open: Vec<Block> = Vec::new();
+
+    for ruleset in rulesets {
+        let blocks = Block::for_ruleset(ruleset);
+        let common = open
+            .iter()
+            .zip(&blocks)
+            .take_while(|(open, block)| open == block)
+            .count();
+
+        while open.len() > common {
+            open.pop();
+            output += &format!("{}}}\n", "  ".repeat(open.len()));
+        }
+
+        for block in &blocks[common..] {
+            output += &format!("{}{} {{\n", "  ".repeat(open.len()), block);
+            open.push(block.clone());
+        }
+
+        output += &format!("{}{}\n", "  ".repeat(open.len()), ruleset);
+    }
+
+    while open.pop().is_some() {
+        output += &format!("{}}}\n", "  ".repeat(open.len()));
+    }
+
+    return output;
+}
+
+/// A layer or media block rulesets are serialized in.
+#[derive(PartialEq, Clone)]
+enum Block<'a> {
+    Layer(Layer),
+    Media(&'a MediaQueryList),
+}
+
+impl<'a> Block<'a> {
+    /// The blocks around a ruleset, outermost first. Layers go around media
+    /// rules, which applies the same conditions.
+    fn for_ruleset(ruleset: &'a Ruleset) -> Vec<Self> {
+        let mut blocks = Vec::new();
+
+        if let Some(layer) = &ruleset.layer {
+            let mut parent = Layer::root();
+
+            for (name, position) in layer.names.iter().zip(&layer.positions) {
+                parent = parent.child(name, *position);
+                blocks.push(Block::Layer(parent.clone()));
+            }
+        }
+
+        for media in &ruleset.media {
+            blocks.push(Block::Media(media));
+        }
+
+        return blocks;
+    }
+}
+
+impl<'a> fmt::Display for Block<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Block::Layer(layer) if layer.is_anonymous() => write!(f, "@layer"),
+            Block::Layer(layer) => {
+                let name = layer.names.last().unwrap();
+                write!(f, "@layer {}", tokenizer::escape_identifier(name))
+            }
+            Block::Media(media) if media.is_empty() => write!(f, "@media"),
+            Block::Media(media) => write!(f, "@media {}", join(media, ", ")),
+        };
+    }
+}
+
+fn join<T: fmt::Display>(items: &[T], separator: &str) -> String {
+    return items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<String>>()
+        .join(separator);
+}
+
+fn escape_string(value: &str) -> String {
+    return Token::String(value.to_owned()).to_string();
+}
+
+impl fmt::Display for Ruleset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.declarations.is_empty() {
+            return write!(f, "{} {{ }}", join(&self.selectors, ", "));
+        }
+
+        return write!(
+            f,
+            "{} {{ {} }}",
+            join(&self.selectors, ", "),
+            self.declarations
+        );
+    }
+}
+
+/// Longhands set by a shorthand using `var()` all keep the shorthand’s
+/// value, so they are written as the shorthand again.
+impl fmt::Display for Declarations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut declarations: Vec<String> = Vec::new();
+        let mut shorthand: Option<&Declaration> = None;
+
+        for declaration in &self.entries {
+            let declared = match &declaration.value {
+                Value::Pending(declared, _) if *declared != declaration.property => declared,
+                _ => {
+                    shorthand = None;
+                    declarations.push(declaration.to_string());
+                    continue;
+                }
+            };
+
+            let is_same_shorthand = shorthand.map_or(false, |shorthand| {
+                return shorthand.value == declaration.value
+                    && shorthand.important == declaration.important;
+            });
+
+            if is_same_shorthand {
+                continue;
+            }
+
+            shorthand = Some(declaration);
+
+            let mut declaration = declaration.clone();
+            declaration.property = declared.clone();
+            declarations.push(declaration.to_string());
+        }
+
+        return write!(f, "{}", declarations.join(" "));
+    }
+}
+
+impl fmt::Display for Declaration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let important = if self.important { " !important" } else { "" };
+
+        return write!(f, "{}: {}{};", self.property, self.value, important);
+    }
+}
+
+impl fmt::Display for MediaQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let media_type = match &self.media_type {
+            MediaType::All => "all".to_owned(),
+            MediaType::Screen => "screen".to_owned(),
+            MediaType::Print => "print".to_owned(),
+            MediaType::Other(name) => tokenizer::escape_identifier(name),
+        };
+
+        return match (&self.condition, self.negated) {
+            // `all and` can be left out before a condition
+            (Some(condition), false) if self.media_type == MediaType::All => {
+                write!(f, "{}", condition)
+            }
+            // After a media type, `or` needs another pair of parentheses
+            (Some(condition @ MediaCondition::Or(_)), negated) => {
+                let not = if negated { "not " } else { "" };
+                write!(f, "{}{} and ({})", not, media_type, condition)
+            }
+            (Some(condition), negated) => {
+                let not = if negated { "not " } else { "" };
+                write!(f, "{}{} and {}", not, media_type, condition)
+            }
+            (None, true) => write!(f, "not {}", media_type),
+            (None, false) => write!(f, "{}", media_type),
+        };
+    }
+}
+
+impl MediaCondition {
+    /// Serializes the condition so it can be nested in another one.
+    fn to_string_in_parens(&self) -> String {
+        return match self {
+            MediaCondition::Not(_) | MediaCondition::And(_) | MediaCondition::Or(_) => {
+                format!("({})", self)
+            }
+            _ => self.to_string(),
+        };
+    }
+}
+
+impl fmt::Display for MediaCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let conditions = |conditions: &[MediaCondition], operator: &str| {
+            return conditions
+                .iter()
+                .map(|condition| condition.to_string_in_parens())
+                .collect::<Vec<String>>()
+                .join(operator);
+        };
+
+        return match self {
+            MediaCondition::Feature(feature) => write!(f, "({})", feature),
+            MediaCondition::Not(condition) => write!(f, "not {}", condition.to_string_in_parens()),
+            MediaCondition::And(list) => write!(f, "{}", conditions(list, " and ")),
+            MediaCondition::Or(list) => write!(f, "{}", conditions(list, " or ")),
+            MediaCondition::Unknown(source) => write!(f, "{}", source),
+        };
+    }
+}
+
+impl fmt::Display for MediaFeature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            MediaFeature::Boolean(name) => write!(f, "{}", name),
+            MediaFeature::Range(name, Comparison::Equal, value) => write!(f, "{}: {}", name, value),
+            MediaFeature::Range(name, comparison, value) => {
+                write!(f, "{} {} {}", name, comparison, value)
+            }
+        };
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Comparison::Equal => write!(f, "="),
+            Comparison::Less => write!(f, "<"),
+            Comparison::LessOrEqual => write!(f, "<="),
+            Comparison::Greater => write!(f, ">"),
+            Comparison::GreaterOrEqual => write!(f, ">="),
+        };
+    }
+}
+
+impl fmt::Display for MediaValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            MediaValue::Length(value) => write!(f, "{}", value),
+            MediaValue::Number(number) => write!(f, "{}", number),
+            MediaValue::Resolution(dppx) => write!(f, "{}dppx", dppx),
+            MediaValue::Ratio(width, height) => write!(f, "{} / {}", width, height),
+            MediaValue::Ident(ident) => write!(f, "{}", tokenizer::escape_identifier(ident)),
+        };
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((combinator, left)) = &self.combinator {
+            write!(f, "{}{}", left, combinator)?;
+        }
+
+        let mut compound = String::new();
+
+        if self.universal {
+            compound.push('*');
+        }
+
+        if let Some(tag) = &self.tag {
+            compound += &tokenizer::escape_identifier(tag);
+        }
+
+        if let Some(id) = &self.id {
+            compound += &format!("#{}", tokenizer::escape_identifier(id));
+        }
+
+        for class in &self.classes {
+            compound += &format!(".{}", tokenizer::escape_identifier(class));
+        }
+
+        for attr in &self.attrs {
+            compound += &attr.to_string();
+        }
+
+        for pseudo_class in &self.pseudo_classes {
+            compound += &format!(":{}", pseudo_class);
+        }
+
+        match self.pseudo_element {
+            Some(PseudoElement::Before) => compound += "::before",
+            Some(PseudoElement::After) => compound += "::after",
+            None => (),
+        }
+
+        // A compound selector can’t be empty
+        if compound.is_empty() {
+            compound.push('*');
+        }
+
+        return write!(f, "{}", compound);
+    }
+}
+
+impl fmt::Display for Combinator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Combinator::Descendant => write!(f, " "),
+            Combinator::Child => write!(f, " > "),
+            Combinator::NextSibling => write!(f, " + "),
+            Combinator::SubsequentSibling => write!(f, " ~ "),
+        };
+    }
+}
+
+impl fmt::Display for AttributeSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operator = match self.operator {
+            AttributeOperator::Exists => {
+                return write!(f, "[{}]", tokenizer::escape_identifier(&self.name));
+            }
+            AttributeOperator::Equals => "=",
+            AttributeOperator::Includes => "~=",
+            AttributeOperator::DashMatch => "|=",
+            AttributeOperator::Prefix => "^=",
+            AttributeOperator::Suffix => "$=",
+            AttributeOperator::Substring => "*=",
+        };
+
+        let flag = match self.case_sensitivity {
+            CaseSensitivity::Default => "",
+            CaseSensitivity::Insensitive => " i",
+            CaseSensitivity::Sensitive => " s",
+        };
+
+        return write!(
+            f,
+            "[{}{}{}{}]",
+            tokenizer::escape_identifier(&self.name),
+            operator,
+            escape_string(&self.value),
+            flag
+        );
+    }
+}
+
+impl fmt::Display for PseudoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let nth_of = |nth: &Nth, of: &Option<Selectors>| match of {
+            Some(selectors) => format!("{} of {}", nth, join(selectors, ", ")),
+            None => nth.to_string(),
+        };
+
+        return match self {
+            PseudoClass::Root => write!(f, "root"),
+            PseudoClass::Empty => write!(f, "empty"),
+            PseudoClass::FirstChild => write!(f, "first-child"),
+            PseudoClass::LastChild => write!(f, "last-child"),
+            PseudoClass::OnlyChild => write!(f, "only-child"),
+            PseudoClass::FirstOfType => write!(f, "first-of-type"),
+            PseudoClass::LastOfType => write!(f, "last-of-type"),
+            PseudoClass::OnlyOfType => write!(f, "only-of-type"),
+            PseudoClass::NthChild(nth, of) => write!(f, "nth-child({})", nth_of(nth, of)),
+            PseudoClass::NthLastChild(nth, of) => {
+                write!(f, "nth-last-child({})", nth_of(nth, of))
+            }
+            PseudoClass::NthOfType(nth) => write!(f, "nth-of-type({})", nth),
+            PseudoClass::NthLastOfType(nth) => write!(f, "nth-last-of-type({})", nth),
+            PseudoClass::Not(selectors) => write!(f, "not({})", join(selectors, ", ")),
+            PseudoClass::Is(selectors) => write!(f, "is({})", join(selectors, ", ")),
+            PseudoClass::Where(selectors) => write!(f, "where({})", join(selectors, ", ")),
+            PseudoClass::Has(selectors) => write!(f, "has({})", join(selectors, ", ")),
+            PseudoClass::Hover => write!(f, "hover"),
+            PseudoClass::Active => write!(f, "active"),
+            PseudoClass::Focus => write!(f, "focus"),
+            PseudoClass::FocusVisible => write!(f, "focus-visible"),
+        };
+    }
+}
+
+impl fmt::Display for RelativeSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let combinator = self.combinator.to_string();
+
+        return write!(f, "{}{}", combinator.trim_start(), self.selector);
+    }
+}
+
+/// Serializes `An+B` the way CSSOM does, like `2n+1`, `-n` or `3`.
+impl fmt::Display for Nth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let a = match self.a {
+            0 => return write!(f, "{}", self.b),
+            1 => "n".to_owned(),
+            -1 => "-n".to_owned(),
+            a => format!("{}n", a),
+        };
+
+        return match self.b {
+            0 => write!(f, "{}", a),
+            b if b > 0 => write!(f, "{}+{}", a, b),
+            b => write!(f, "{}{}", a, b),
+        };
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Value::Ident(ident) => write!(f, "{}", tokenizer::escape_identifier(ident)),
+            Value::String(string) => write!(f, "{}", escape_string(string)),
+            Value::Numeric(value) => write!(f, "{}", value),
+            Value::Color(color) => write!(f, "{}", color),
+            Value::Url(url) => write!(f, "url({})", escape_string(url)),
+            Value::CurrentColor => write!(f, "currentcolor"),
+            Value::Delim(delim) => write!(f, "{}", delim),
+            Value::Function(name, arguments) => write!(
+                f,
+                "{}({})",
+                tokenizer::escape_identifier(name),
+                join(arguments, ", ")
+            ),
+            Value::SpaceList(values) => write!(f, "{}", join(values, " ")),
+            Value::CommaList(values) => write!(f, "{}", join(values, ", ")),
+            Value::Tokens(values) | Value::Pending(_, values) => {
+                write!(f, "{}", tokenizer::serialize(values))
+            }
+        };
+    }
+}
+
+impl fmt::Display for NumericValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            NumericValue::Zero => write!(f, "0"),
+            NumericValue::Number(number) => write!(f, "{}", number),
+            NumericValue::Px(px) => write!(f, "{}px", px),
+            NumericValue::Percentage(percentage) => write!(f, "{}%", percentage),
+            NumericValue::Length(length, unit) => write!(f, "{}{}", length, unit),
+            // Math functions other than `calc()` are kept as they are
+            NumericValue::Calc(calculation) => match **calculation {
+                Calculation::Min(_) | Calculation::Max(_) | Calculation::Clamp(..) => {
+                    write!(f, "{}", calculation)
+                }
+                _ => write!(f, "calc({})", calculation),
+            },
+        };
+    }
+}
+
+impl Calculation {
+    fn is_sum(&self) -> bool {
+        return matches!(self, Calculation::Add(..) | Calculation::Subtract(..));
+    }
+
+    fn is_product(&self) -> bool {
+        return matches!(self, Calculation::Multiply(..) | Calculation::Divide(..));
+    }
+}
+
+impl fmt::Display for Calculation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Operators are left-associative, so only operands to their right
+        // with the same precedence need parentheses
+        let operand = |calculation: &Calculation, parenthesize: bool| match parenthesize {
+            true => format!("({})", calculation),
+            false => calculation.to_string(),
+        };
+
+        return match self {
+            Calculation::Value(value) => write!(f, "{}", value),
+            Calculation::Add(left, right) => {
+                write!(f, "{} + {}", left, operand(right, right.is_sum()))
+            }
+            Calculation::Subtract(left, right) => {
+                write!(f, "{} - {}", left, operand(right, right.is_sum()))
+            }
+            Calculation::Multiply(left, right) => write!(
+                f,
+                "{} * {}",
+                operand(left, left.is_sum()),
+                operand(right, right.is_sum() || right.is_product())
+            ),
+            Calculation::Divide(left, right) => write!(
+                f,
+                "{} / {}",
+                operand(left, left.is_sum()),
+                operand(right, right.is_sum() || right.is_product())
+            ),
+            Calculation::Min(arguments) => write!(f, "min({})", join(arguments, ", ")),
+            Calculation::Max(arguments) => write!(f, "max({})", join(arguments, ", ")),
+            Calculation::Clamp(min, value, max) => {
+                write!(f, "clamp({}, {}, {})", min, value, max)
+            }
+        };
+    }
+}
+
+impl fmt::Display for LengthUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let unit = match self {
+            LengthUnit::Em => "em",
+            LengthUnit::Rem => "rem",
+            LengthUnit::Ex => "ex",
+            LengthUnit::Ch => "ch",
+            LengthUnit::Vw => "vw",
+            LengthUnit::Vh => "vh",
+            LengthUnit::Vmin => "vmin",
+            LengthUnit::Vmax => "vmax",
+            LengthUnit::Pt => "pt",
+            LengthUnit::Cm => "cm",
+            LengthUnit::Mm => "mm",
+            LengthUnit::In => "in",
+        };
+
+        return write!(f, "{}", unit);
+    }
+}
+
+/// Serializes colors as `rgb()`, or `rgba()` if they aren’t opaque, the
+/// way CSSOM does. Alpha gets as few decimals as it needs to parse back
+/// into the same value.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.a == 255 {
+            return write!(f, "rgb({}, {}, {})", self.r, self.g, self.b);
+        }
+
+        let mut alpha = (self.a as f64 / 255.0 * 100.0).round() / 100.0;
+
+        if (alpha * 255.0).round() as u8 != self.a {
+            alpha = (self.a as f64 / 255.0 * 1000.0).round() / 1000.0;
+        }
+
+        return write!(f, "rgba({}, {}, {}, {})", self.r, self.g, self.b, alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_declaration_cascade_level() {
+        let normal = Declaration::new("width", Value::Ident("auto".to_owned()));
+        let important = normal.clone().important();
+
+        assert!(normal.cascade_level(Origin::UserAgent) < normal.cascade_level(Origin::User));
+        assert!(normal.cascade_level(Origin::User) < normal.cascade_level(Origin::Author));
+        assert!(normal.cascade_level(Origin::Author) < important.cascade_level(Origin::Author));
+        assert!(important.cascade_level(Origin::Author) < important.cascade_level(Origin::User));
+        assert!(important.cascade_level(Origin::User) < important.cascade_level(Origin::UserAgent));
+    }
+
+    #[test]
+    fn test_declarations_get() {
+        let declaration = |value: &str| Declaration::new("color", Value::Ident(value.to_owned()));
+
+        let mut declarations = Declarations::new();
+        declarations.push(declaration("red"));
+        declarations.push(Declaration::new(
+            "width",
+            Value::Numeric(NumericValue::Zero),
+        ));
+        declarations.push(declaration("blue"));
+
+        assert!(declarations.len() == 3);
+        assert!(declarations["color"] == declaration("blue"));
+        assert!(declarations.get("height") == None);
+
+        declarations.push(declaration("green").important());
+        declarations.push(declaration("pink"));
+
+        assert!(declarations["color"] == declaration("green").important());
+    }
+
+    #[test]
+    fn test_color_from_name() {
+        assert!(Color::from_name("RebeccaPurple") == Some(Color::new(0x66, 0x33, 0x99)));
+        assert!(Color::from_name("aliceblue") == Some(Color::new(0xf0, 0xf8, 0xff)));
+        assert!(Color::from_name("yellowgreen") == Some(Color::new(0x9a, 0xcd, 0x32)));
+        assert!(Color::from_name("transparent") == Some(Color::new(0, 0, 0).alpha(0)));
+        assert!(Color::from_name("currentcolor") == None);
+        assert!(Color::from_name("bluish") == None);
+    }
+
+    #[test]
+    fn test_color_from_hsl_and_hwb() {
+        assert!(Color::from_hsl(0.0, 1.0, 0.5) == Color::new(255, 0, 0));
+        assert!(Color::from_hsl(120.0, 1.0, 0.25) == Color::new(0, 128, 0));
+        assert!(Color::from_hsl(-120.0, 1.0, 0.5) == Color::new(0, 0, 255));
+        assert!(Color::from_hsl(270.0, 0.5, 0.4) == Color::new(102, 51, 153));
+        assert!(Color::from_hwb(60.0, 0.0, 0.0) == Color::new(255, 255, 0));
+        assert!(Color::from_hwb(0.0, 0.2, 0.4) == Color::new(153, 51, 51));
+        assert!(Color::from_hwb(0.0, 0.6, 0.6) == Color::new(128, 128, 128));
+    }
+
+    #[test]
+    fn test_color_blend() {
+        assert!(Color::new(255, 0, 0).blend(0x0000ff) == 0xff0000);
+        assert!(Color::new(255, 0, 0).alpha(0).blend(0x0000ff) == 0x0000ff);
+        assert!(Color::new(255, 255, 255).alpha(128).blend(0x000000) == 0x808080);
+    }
+
+    #[test]
+    fn test_calculation_resolved_type() {
+        let value = |value: NumericValue| Box::new(Calculation::Value(value));
+        let px = || value(NumericValue::Px(1.0));
+        let percentage = || value(NumericValue::Percentage(50.0));
+        let number = || value(NumericValue::Number(2.0));
+
+        assert!(
+            Calculation::Subtract(percentage(), px()).resolved_type()
+                == Some(CalculationType::LengthPercentage)
+        );
+        assert!(
+            Calculation::Multiply(number(), px()).resolved_type() == Some(CalculationType::Length)
+        );
+        assert!(
+            Calculation::Divide(percentage(), number()).resolved_type()
+                == Some(CalculationType::Percentage)
+        );
+        assert!(
+            Calculation::Min(Vec::from([*number(), *number()])).resolved_type()
+                == Some(CalculationType::Number)
+        );
+        assert!(
+            Calculation::Clamp(px(), percentage(), px()).resolved_type()
+                == Some(CalculationType::LengthPercentage)
+        );
+
+        assert!(Calculation::Add(px(), number()).resolved_type() == None);
+        assert!(Calculation::Multiply(px(), px()).resolved_type() == None);
+        assert!(Calculation::Divide(number(), px()).resolved_type() == None);
+        assert!(Calculation::Max(Vec::from([*px(), *number()])).resolved_type() == None);
+        assert!(Calculation::Max(Vec::new()).resolved_type() == None);
+    }
+
+    #[test]
+    fn test_stylesheet_append() {
+        let stylesheet = || {
+            let rulesets = Rulesets::from([
+                Ruleset::new(Selectors::new(), Declarations::new()).order(0),
+                Ruleset::new(Selectors::new(), Declarations::new()).order(1),
+            ]);
+
+            return Stylesheet::new(rulesets, Vec::new());
+        };
+
+        let mut merged = stylesheet();
+        merged.append(stylesheet());
+
+        let order: Vec<usize> = merged
+            .rulesets
+            .iter()
+            .map(|ruleset| ruleset.order)
+            .collect();
+        assert!(order == [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_stylesheet_append_layers() {
+        let layers = |stylesheet: &Stylesheet| {
+            return stylesheet
+                .rulesets
+                .iter()
+                .map(|ruleset| ruleset.layer.as_ref().unwrap())
+                .map(|layer| (layer.name(), layer.positions.clone()))
+                .collect::<Vec<(String, Vec<usize>)>>();
+        };
+
+        let mut stylesheet = crate::css::Parser::parse_with_layers(
+            "@layer a.x { b { } } @layer c { d { } } @layer { e { } }",
+        );
+        stylesheet.append(crate::css::Parser::parse_with_layers(
+            "@layer a.y { f { } } @layer g.x { h { } } @layer c { i { } } @layer { j { } }",
+        ));
+
+        assert!(
+            layers(&stylesheet)
+                == [
+                    ("a.x".to_owned(), vec![0, 0]),
+                    ("c".to_owned(), vec![1]),
+                    ("".to_owned(), vec![2]),
+                    ("a.y".to_owned(), vec![0, 1]),
+                    ("g.x".to_owned(), vec![4, 0]),
+                    ("c".to_owned(), vec![1]),
+                    ("".to_owned(), vec![6]),
+                ]
+        );
+
+        // Declared layers keep their position, even without rulesets
+        let mut stylesheet =
+            crate::css::Parser::parse_with_layers("@layer a, b, c; @layer c { x { } }");
+        stylesheet.append(crate::css::Parser::parse_with_layers(
+            "@layer d, b; @layer b { y { } }",
+        ));
+        stylesheet.append(crate::css::Parser::parse_with_layers("@layer d { z { } }"));
+
+        assert!(
+            layers(&stylesheet)
+                == [
+                    ("c".to_owned(), vec![2]),
+                    ("b".to_owned(), vec![1]),
+                    ("d".to_owned(), vec![3]),
+                ]
+        );
+    }
+
+    #[test]
+    fn test_selector_combine() {
+        // ul > li a
+        let selector = Selector::new()
+            .tag("ul")
+            .combine(Combinator::Child, Selector::new().tag("li"))
+            .combine(Combinator::Descendant, Selector::new().tag("a"));
+
+        assert!(selector.tag == Some("a".to_owned()));
+
+        let (combinator, left) = selector.combinator.unwrap();
+        assert!(combinator == Combinator::Descendant);
+        assert!(left.tag == Some("li".to_owned()));
+
+        let (combinator, left) = left.combinator.unwrap();
+        assert!(combinator == Combinator::Child);
+        assert!(left.tag == Some("ul".to_owned()));
+        assert!(left.combinator.is_none());
+
+        // Combining with a complex selector attaches to its leftmost part
+        let complex = Selector::new()
+            .tag("li")
+            .combine(Combinator::Descendant, Selector::new().tag("a"));
+        let other = Selector::new()
+            .tag("ul")
+            .combine(Combinator::Child, complex);
+
+        assert!(
+            other
+                == Selector::new()
+                    .tag("ul")
+                    .combine(Combinator::Child, Selector::new().tag("li"))
+                    .combine(Combinator::Descendant, Selector::new().tag("a"))
+        );
+    }
+
+    #[test]
+    fn test_selector_specificity() {
+        let selector = Selector::new().tag("p").class("intro");
+        assert!(selector.specificity() == Specificity::new(0, 1, 1));
+
+        // #main ul.nav > li
+        let selector = Selector::new()
+            .id("main")
+            .combine(
+                Combinator::Descendant,
+                Selector::new().tag("ul").class("nav"),
+            )
+            .combine(Combinator::Child, Selector::new().tag("li"));
+
+        assert!(selector.specificity() == Specificity::new(1, 1, 2));
+
+        // *[lang|="en"] [href]
+        let selector = Selector::new()
+            .universal()
+            .attr_selector(AttributeSelector::new(
+                "lang",
+                AttributeOperator::DashMatch,
+                "en",
+            ))
+            .combine(
+                Combinator::Descendant,
+                Selector::new().attr_selector(AttributeSelector::exists("href")),
+            );
+
+        assert!(selector.specificity() == Specificity::new(0, 2, 0));
+    }
+
+    #[test]
+    fn test_selector_specificity_pseudo_classes() {
+        // li:first-child
+        let selector = Selector::new()
+            .tag("li")
+            .pseudo_class(PseudoClass::FirstChild);
+        assert!(selector.specificity() == Specificity::new(0, 1, 1));
+
+        // :nth-child(2n of #a, .b)
+        let of = Selectors::from([Selector::new().id("a"), Selector::new().class("b")]);
+        let selector =
+            Selector::new().pseudo_class(PseudoClass::NthChild(Nth::new(2, 0), Some(of)));
+        assert!(selector.specificity() == Specificity::new(1, 1, 0));
+    }
+
+    #[test]
+    fn test_selector_specificity_logical_pseudo_classes() {
+        let list = || Selectors::from([Selector::new().id("a"), Selector::new().tag("p")]);
+
+        // :is(#a, p) and :not(#a, p)
+        let selector = Selector::new().pseudo_class(PseudoClass::Is(list()));
+        assert!(selector.specificity() == Specificity::new(1, 0, 0));
+
+        let selector = Selector::new().pseudo_class(PseudoClass::Not(list()));
+        assert!(selector.specificity() == Specificity::new(1, 0, 0));
+
+        // p:where(#a, p)
+        let selector = Selector::new()
+            .tag("p")
+            .pseudo_class(PseudoClass::Where(list()));
+        assert!(selector.specificity() == Specificity::new(0, 0, 1));
+
+        // :has(> .a, + p)
+        let selector = Selector::new().pseudo_class(PseudoClass::Has(RelativeSelectors::from([
+            RelativeSelector::new(Combinator::Child, Selector::new().class("a")),
+            RelativeSelector::new(Combinator::NextSibling, Selector::new().tag("p")),
+        ])));
+        assert!(selector.specificity() == Specificity::new(0, 1, 0));
+    }
+
+    #[test]
+    fn test_selector_specificity_pseudo_element() {
+        let selector = Selector::new()
+            .tag("q")
+            .class("a")
+            .pseudo_element(PseudoElement::Before);
+
+        assert!(selector.specificity() == Specificity::new(0, 1, 2));
+    }
+
+    #[test]
+    fn test_nth_matches() {
+        let matches = |nth: Nth| (1..=10).filter(|&i| nth.matches(i)).collect::<Vec<_>>();
+
+        assert!(matches(Nth::new(2, 1)) == [1, 3, 5, 7, 9]);
+        assert!(matches(Nth::new(2, 0)) == [2, 4, 6, 8, 10]);
+        assert!(matches(Nth::new(0, 3)) == [3]);
+        assert!(matches(Nth::new(3, -1)) == [2, 5, 8]);
+        assert!(matches(Nth::new(-1, 3)) == [1, 2, 3]);
+        assert!(matches(Nth::new(1, 0)) == [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert!(matches(Nth::new(-2, 0)) == []);
+
+        // Extreme values don’t overflow
+        assert!(matches(Nth::new(0, i32::MIN)) == []);
+        assert!(matches(Nth::new(-1, -i32::MAX)) == []);
+        assert!(matches(Nth::new(i32::MIN, i32::MAX)) == []);
+        assert!(matches(Nth::new(-1, i32::MAX)) == [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+    }
+
+    #[test]
+    fn test_specificity_eq() {
+        let one = &Specificity::new(1, 1, 1);
+        let two = &Specificity::new(1, 1, 1);
+        let three = &Specificity::new(2, 1, 1);
+
+        assert!(one.eq(two) == true);
+        assert!(one.eq(three) == false);
+        assert!(two.eq(three) == false);
+    }
+
+    #[test]
+    fn test_specificity_cmp() {
+        let greater = &Specificity::new(1, 0, 0);
+        let less = &Specificity::new(0, 1000, 1000);
+
+        assert!(greater.cmp(less) == Ordering::Greater);
+
+        let greater = &Specificity::new(0, 1, 0);
+        let less = &Specificity::new(0, 0, 1000);
+
+        assert!(greater.cmp(less) == Ordering::Greater);
+
+        let greater = &Specificity::new(0, 0, 1);
+        let less = &Specificity::new(0, 0, 0);
+
+        assert!(greater.cmp(less) == Ordering::Greater);
+    }
+
+    #[test]
+    fn test_serialize_values() {
+        let serialize = |css: &str| {
+            let rulesets = crate::css::Parser::parse(&format!("a {{ x: {} }}", css));
+            let value = rulesets[0].declarations["x"].value.to_string();
+
+            // The serialized value parses back into the same value
+            let reparsed = crate::css::Parser::parse(&format!("a {{ x: {} }}", value));
+            assert!(reparsed[0].declarations["x"].value == rulesets[0].declarations["x"].value);
+
+            return value;
+        };
+
+        assert!(serialize("red") == "rgb(255, 0, 0)");
+        assert!(serialize("#ff000080") == "rgba(255, 0, 0, 0.5)");
+        assert!(serialize("hsl(0 0% 100% / 25%)") == "rgba(255, 255, 255, 0.25)");
+        assert!(serialize("rgb(0 0 0 / 0.001)") == "rgba(0, 0, 0, 0)");
+        assert!(serialize("rgb(0 0 0 / 0.499)") == "rgba(0, 0, 0, 0.498)");
+        assert!(serialize("currentColor") == "currentcolor");
+
+        assert!(serialize("0") == "0");
+        assert!(serialize("1.5EM -10px 50%") == "1.5em -10px 50%");
+        assert!(serialize("1e400px -1e400%") == format!("{}px -{}%", f64::MAX, f64::MAX));
+        assert!(serialize("calc(100% - 2 * (1em + 4px))") == "calc(100% - 2 * (1em + 4px))");
+        assert!(serialize("calc((1px - 2px) - (3px - 4px))") == "calc(1px - 2px - (3px - 4px))");
+        assert!(serialize("calc(1 / (2 / 3))") == "calc(1 / (2 / 3))");
+        assert!(serialize("min(10px, max(5vw, 1rem) / 2)") == "min(10px, max(5vw, 1rem) / 2)");
+        assert!(serialize("clamp(1px,2px,3px)") == "clamp(1px, 2px, 3px)");
+
+        assert!(
+            serialize("'Helvetica \"Neue\"', sans-serif")
+                == "\"Helvetica \\\"Neue\\\"\", sans-serif"
+        );
+        assert!(serialize("url(a.png) attr(title)") == "url(\"a.png\") attr(title)");
+        assert!(serialize("16/9") == "16 / 9");
+        assert!(serialize("var(--x,  1px)") == "var(--x, 1px)");
+        assert!(serialize("f\\(x(1)") == "f\\(x(1)");
+
+        let rulesets = crate::css::Parser::parse("a { --x: #a\\ b #\\31 x }");
+        assert!(rulesets[0].declarations["--x"].value.to_string() == "#a\\ b #\\31 x");
+    }
+
+    #[test]
+    fn test_serialize_selectors() {
+        let serialize = |css: &str| {
+            let rulesets = crate::css::Parser::parse(&format!("{} {{ }}", css));
+            let selectors = join(&rulesets[0].selectors, ", ");
+
+            let reparsed = crate::css::Parser::parse(&format!("{} {{ }}", selectors));
+            assert!(reparsed[0].selectors == rulesets[0].selectors);
+
+            return selectors;
+        };
+
+        assert!(serialize("ul.nav>li") == "ul.nav > li");
+        assert!(serialize("a ~ b + c d") == "a ~ b + c d");
+        assert!(serialize("* , *.a, #b.c.d") == "*, *.a, #b.c.d");
+        assert!(serialize(".a\\:b, .\\31 0") == ".a\\:b, .\\31 0");
+        assert!(
+            serialize("[href] , [href^=http i] , [lang|='en' s]")
+                == "[href], [href^=\"http\" i], [lang|=\"en\" s]"
+        );
+        assert!(serialize("p:first-child:before") == "p:first-child::before");
+        assert!(serialize("li:nth-child(odd of .a)") == "li:nth-child(2n+1 of .a)");
+        assert!(serialize("li:nth-last-child(-n + 3)") == "li:nth-last-child(-n+3)");
+        assert!(
+            serialize("li:nth-of-type(n-1), li:nth-last-of-type(4)")
+                == "li:nth-of-type(n-1), li:nth-last-of-type(4)"
+        );
+        assert!(
+            serialize(":is(h1, ul > li):not(.a):where():has(> img, + p b)")
+                == ":is(h1, ul > li):not(.a):where():has(> img, + p b)"
+        );
+
+        // Nesting selectors stay `:is()` where they can’t be replaced
+        let rulesets = crate::css::Parser::parse(".a, .b { & > h2 { } }");
+        assert!(rulesets[1].selectors[0].to_string() == ":is(.a, .b) > h2");
+    }
+
+    #[test]
+    fn test_serialize_rulesets() {
+        let css = "
+            @layer base, components;
+            a { color: red !important; --x: { b: c } }
+            @layer components { .button { width: 10px; } }
+            @media screen and (min-width: 600px), print {
+                @layer base { b { height: 0; } }
+            }
+            @layer { c { } }
+            @media not all and (color) { d { height: 1px; } }
+            @media (400px < width <= 800px) or (not (hover)) { e { } }
+        ";
+        let serialized = serialize(&crate::css::Parser::parse(css));
+
+        assert!(
+            serialized
+                == "\
+a { color: rgb(255, 0, 0) !important; --x: { b: c }; }
+@media not all and (color) {
+  d { height: 1px; }
+}
+@media ((width > 400px) and (width <= 800px)) or (not (hover)) {
+  e { }
+}
+@layer base {
+  @media screen and (width >= 600px), print {
+    b { height: 0; }
+  }
+}
+@layer components {
+  .button { width: 10px; }
+}
+@layer {
+  c { }
+}
+"
+        );
+        assert!(serialize(&crate::css::Parser::parse(&serialized)) == serialized);
+
+        // Shorthands using `var()` are kept
+        let css = "a { margin: var(--m) !important; margin-top: 1px; padding: 0 var(--p); }";
+        let serialized = serialize(&crate::css::Parser::parse(css));
+
+        assert!(
+            serialized
+                == "a { margin: var(--m) !important; margin-top: 1px; padding: 0 var(--p); }\n"
+        );
+        assert!(serialize(&crate::css::Parser::parse(&serialized)) == serialized);
+
+        // Rulesets directly in a layer come before its sublayers
+        let css = "@layer a { @layer b { x { } } y { } } @layer { z { } }";
+
+        assert!(
+            serialize(&crate::css::Parser::parse(css))
+                == "\
+@layer a {
+  y { }
+  @layer b {
+    x { }
+  }
+}
+@layer {
+  z { }
+}
+"
+        );
     }
 }
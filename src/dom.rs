@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::vec::Vec;
 
@@ -76,7 +77,322 @@ impl Element {
             .map(&|class| String::from(class))
             .collect();
     }
+
+    /// Whether the element can receive focus by clicking or tabbing.
+    pub fn is_focusable(&self) -> bool {
+        return match self.tag.as_ref() {
+            "button" | "input" | "select" | "textarea" => true,
+            "a" => self.attrs.contains_key("href"),
+            _ => self.attrs.contains_key("tabindex"),
+        };
+    }
+}
+
+/// Element attributes in insertion order. Lookups by name go through an
+/// index, so they don’t need to scan the list.
+#[derive(PartialEq, Eq, Clone)]
+pub struct AttrMap {
+    entries: Vec<(String, String)>,
+    index: HashMap<String, usize>,
+}
+
+impl AttrMap {
+    pub fn new() -> Self {
+        return Self {
+            entries: Vec::new(),
+            index: HashMap::new(),
+        };
+    }
+
+    /// Sets an attribute. Overwriting an existing attribute keeps its
+    /// original position.
+    pub fn insert(&mut self, name: String, value: String) -> Option<String> {
+        if let Some(&position) = self.index.get(&name) {
+            return Some(std::mem::replace(&mut self.entries[position].1, value));
+        }
+
+        self.index.insert(name.clone(), self.entries.len());
+        self.entries.push((name, value));
+
+        return None;
+    }
+
+    pub fn get(&self, name: &str) -> Option<&String> {
+        return self
+            .index
+            .get(name)
+            .map(|&position| &self.entries[position].1);
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        return self.index.contains_key(name);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let position = self.index.remove(name)?;
+        let (_, value) = self.entries.remove(position);
+
+        // Entries after the removed one moved up by one
+        for other in self.index.values_mut() {
+            if *other > position {
+                *other -= 1;
+            }
+        }
+
+        return Some(value);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        return self.entries.iter().map(|(name, value)| (name, value));
+    }
+}
+
+impl<'a> IntoIterator for &'a AttrMap {
+    type Item = (&'a String, &'a String);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (String, String)>,
+        fn(&'a (String, String)) -> (&'a String, &'a String),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        return self.entries.iter().map(|(name, value)| (name, value));
+    }
 }
 
-pub type AttrMap = HashMap<String, String>;
 pub type Nodes = Vec<Node>;
+
+/// A parsed document together with the dynamic state of its elements.
+pub struct Document {
+    pub nodes: Nodes,
+    pub states: ElementStates,
+}
+
+impl Document {
+    pub fn new(nodes: Nodes) -> Self {
+        return Self {
+            nodes: nodes,
+            states: ElementStates::new(),
+        };
+    }
+
+    /// Returns the first element of the document.
+    pub fn root(&self) -> Option<&Node> {
+        return self.nodes.iter().find(|node| node.element().is_some());
+    }
+
+    /// Returns all elements in tree order.
+    pub fn elements(&self) -> Vec<&Element> {
+        fn collect<'a>(nodes: &'a Nodes, elements: &mut Vec<&'a Element>) {
+            for node in nodes {
+                if let Node::Element(element) = node {
+                    elements.push(element);
+                    collect(&element.children, elements);
+                }
+            }
+        }
+
+        let mut elements = Vec::new();
+        collect(&self.nodes, &mut elements);
+
+        return elements;
+    }
+
+    /// Returns the first element in the given state, in tree order.
+    pub fn element_in_state(&self, state: ElementState) -> Option<&Element> {
+        return self
+            .elements()
+            .into_iter()
+            .find(|element| self.states.has(element, state));
+    }
+
+    /// Returns the focusable element following `current` in tree order,
+    /// wrapping around at the end of the document.
+    pub fn next_focusable(&self, current: Option<&Element>) -> Option<&Element> {
+        let focusable: Vec<&Element> = self
+            .elements()
+            .into_iter()
+            .filter(|element| element.is_focusable())
+            .collect();
+
+        let next = match current {
+            Some(current) => focusable
+                .iter()
+                .position(|element| std::ptr::eq(*element, current))
+                .map_or(0, |position| position + 1),
+            None => 0,
+        };
+
+        return focusable.get(next).or(focusable.first()).copied();
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ElementState {
+    Hover,
+    Active,
+    Focus,
+    FocusVisible,
+}
+
+/// Tracks which elements are in which dynamic state. Elements are
+/// identified by their address, so the nodes of a document must not move
+/// while states are tracked. States can change while the document is
+/// borrowed for rendering, hence the `RefCell`.
+pub struct ElementStates {
+    inner: RefCell<HashMap<ElementState, Vec<*const Element>>>,
+}
+
+impl ElementStates {
+    pub fn new() -> Self {
+        return Self {
+            inner: RefCell::new(HashMap::new()),
+        };
+    }
+
+    pub fn has(&self, element: &Element, state: ElementState) -> bool {
+        return self.inner.borrow().get(&state).map_or(false, |elements| {
+            elements.contains(&(element as *const Element))
+        });
+    }
+
+    /// Puts exactly the given elements into a state and returns whether
+    /// that changed anything.
+    pub fn set(&self, state: ElementState, elements: &[&Element]) -> bool {
+        let elements: Vec<*const Element> = elements
+            .iter()
+            .map(|&element| element as *const Element)
+            .collect();
+
+        let mut inner = self.inner.borrow_mut();
+        let previous = inner.insert(state, elements.clone()).unwrap_or(Vec::new());
+
+        return previous != elements;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attr_map_insertion_order() {
+        let mut attrs = AttrMap::new();
+        attrs.insert("id".to_owned(), "foo".to_owned());
+        attrs.insert("class".to_owned(), "bar".to_owned());
+        attrs.insert("alt".to_owned(), "baz".to_owned());
+
+        let names: Vec<&String> = attrs.iter().map(|(name, _)| name).collect();
+        assert!(names == ["id", "class", "alt"]);
+    }
+
+    #[test]
+    fn test_attr_map_insert_existing() {
+        let mut attrs = AttrMap::new();
+        attrs.insert("id".to_owned(), "foo".to_owned());
+        attrs.insert("class".to_owned(), "bar".to_owned());
+
+        let previous = attrs.insert("id".to_owned(), "baz".to_owned());
+
+        assert!(previous == Some("foo".to_owned()));
+        assert!(attrs.len() == 2);
+        assert!(attrs.iter().next() == Some((&"id".to_owned(), &"baz".to_owned())));
+    }
+
+    #[test]
+    fn test_attr_map_get() {
+        let element = Element::new("img")
+            .attr("src", "cat.jpg")
+            .attr("alt", "Cat");
+
+        assert!(element.attrs.get("alt") == Some(&"Cat".to_owned()));
+        assert!(element.attrs.get("title") == None);
+        assert!(element.attrs.contains_key("src") == true);
+        assert!(element.attrs.contains_key("title") == false);
+    }
+
+    #[test]
+    fn test_attr_map_remove() {
+        let mut attrs = AttrMap::new();
+        attrs.insert("id".to_owned(), "foo".to_owned());
+        attrs.insert("class".to_owned(), "bar".to_owned());
+        attrs.insert("alt".to_owned(), "baz".to_owned());
+
+        assert!(attrs.remove("class") == Some("bar".to_owned()));
+        assert!(attrs.remove("class") == None);
+        assert!(attrs.get("alt") == Some(&"baz".to_owned()));
+
+        let names: Vec<&String> = (&attrs).into_iter().map(|(name, _)| name).collect();
+        assert!(names == ["id", "alt"]);
+    }
+
+    #[test]
+    fn test_element_is_focusable() {
+        assert!(Element::new("button").is_focusable() == true);
+        assert!(Element::new("a").is_focusable() == false);
+        assert!(Element::new("a").attr("href", "/").is_focusable() == true);
+        assert!(Element::new("div").attr("tabindex", "0").is_focusable() == true);
+        assert!(Element::new("div").is_focusable() == false);
+    }
+
+    #[test]
+    fn test_document_next_focusable() {
+        let document = Document::new(Nodes::from([Node::Element(
+            Element::new("div")
+                .child(Node::Element(Element::new("button")))
+                .child(Node::Element(Element::new("p")))
+                .child(Node::Element(Element::new("input"))),
+        )]));
+        let elements = document.elements();
+        let (button, input) = (elements[1], elements[3]);
+
+        assert!(elements.len() == 4);
+        assert!(std::ptr::eq(document.next_focusable(None).unwrap(), button));
+        assert!(std::ptr::eq(
+            document.next_focusable(Some(button)).unwrap(),
+            input
+        ));
+        assert!(std::ptr::eq(
+            document.next_focusable(Some(input)).unwrap(),
+            button
+        ));
+
+        document.states.set(ElementState::Focus, &[input]);
+        assert!(std::ptr::eq(
+            document.element_in_state(ElementState::Focus).unwrap(),
+            input
+        ));
+    }
+
+    #[test]
+    fn test_element_states() {
+        let (a, b) = (Element::new("a"), Element::new("b"));
+        let states = ElementStates::new();
+
+        assert!(states.has(&a, ElementState::Hover) == false);
+
+        assert!(states.set(ElementState::Hover, &[&a, &b]) == true);
+        assert!(states.set(ElementState::Hover, &[&a, &b]) == false);
+        assert!(states.has(&a, ElementState::Hover) == true);
+        assert!(states.has(&a, ElementState::Active) == false);
+
+        assert!(states.set(ElementState::Hover, &[&b]) == true);
+        assert!(states.has(&a, ElementState::Hover) == false);
+        assert!(states.has(&b, ElementState::Hover) == true);
+    }
+
+    #[test]
+    fn test_element_class_list() {
+        let element = Element::new("div").attr("class", "foo bar");
+
+        assert!(element.class_list() == ["foo", "bar"]);
+        assert!(Element::new("div").class_list().is_empty());
+    }
+}
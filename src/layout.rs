@@ -1,12 +1,26 @@
 use crate::cssom;
+use crate::dom;
 use crate::render;
 
-#[derive(PartialEq, Eq, Clone)]
+/// The font size of the root element if it doesn’t set one.
+pub const INITIAL_FONT_SIZE: f64 = 16.0;
+
+/// Lengths are clamped to this many pixels in either direction, so adding
+/// up margins, offsets and positions can’t overflow.
+const MAX_LENGTH: f64 = 1e9;
+
+#[derive(PartialEq, Clone)]
 pub struct LayoutNode<'a> {
     pub node: &'a render::RenderNode<'a>,
     pub children: Vec<Self>,
     pub dimensions: Dimensions,
     pub position: Point,
+    /// Margins may be negative, which pulls the box and the boxes after
+    /// it towards the edge.
+    pub margin: Edges,
+    /// The computed font size in pixels.
+    pub font_size: f64,
+    context: LengthContext,
 }
 
 impl<'a> LayoutNode<'a> {
@@ -14,38 +28,106 @@ impl<'a> LayoutNode<'a> {
         render_node: &'a render::RenderNode,
         viewport: &Dimensions,
         anchor: &Point,
+    ) -> Self {
+        let context = LengthContext::new(viewport);
+
+        return Self::from_context(render_node, viewport, anchor, context, true);
+    }
+
+    fn from_context(
+        render_node: &'a render::RenderNode,
+        containing_block_dimensions: &Dimensions,
+        anchor: &Point,
+        context: LengthContext,
+        is_root: bool,
     ) -> Self {
         let mut node = LayoutNode {
             node: render_node,
             children: Vec::new(),
-            dimensions: *viewport,
+            dimensions: *containing_block_dimensions,
             position: *anchor,
+            margin: Edges::new(0, 0, 0, 0),
+            font_size: context.parent_font_size,
+            context: context,
         };
 
-        node.set_position(&anchor);
+        node.font_size = node.calculate_font_size();
+
+        let mut child_context = LengthContext {
+            parent_font_size: node.font_size,
+            ..context
+        };
+
+        if is_root {
+            child_context.root_font_size = node.font_size;
+        }
+
+        node.set_margin(&containing_block_dimensions);
+        node.set_position(&anchor, &containing_block_dimensions);
 
         // Calculate initial dimensions without child nodes
-        node.set_dimensions(&viewport);
+        node.set_dimensions(&containing_block_dimensions);
 
         let mut next_position = node.position;
 
         for render_child in &render_node.children {
-            let layout_child = Self::from(&render_child, &node.dimensions, &next_position);
+            let layout_child = Self::from_context(
+                &render_child,
+                &node.dimensions,
+                &next_position,
+                child_context,
+                false,
+            );
 
             next_position = Point::new(
-                layout_child.position.x,
-                layout_child.position.y + layout_child.dimensions.height,
+                next_position.x,
+                next_position.y + layout_child.outer_height(),
             );
 
             node.children.push(layout_child);
         }
 
         // Calculate final dimensions including child nodes
-        node.set_dimensions(&viewport);
+        node.set_dimensions(&containing_block_dimensions);
 
         return node;
     }
 
+    /// Returns the elements under a point, outermost first. Later siblings
+    /// are painted on top of earlier ones, so they are hit first. Hitting
+    /// generated content counts as hitting its originating element.
+    pub fn hit_test(&self, point: &Point) -> Vec<&'a dom::Element> {
+        let element = match self.node.pseudo_element {
+            Some(_) => None,
+            None => self.node.node.element(),
+        };
+
+        for child in self.children.iter().rev() {
+            let mut hits = child.hit_test(point);
+
+            if !hits.is_empty() {
+                if let Some(element) = element {
+                    hits.insert(0, element);
+                }
+
+                return hits;
+            }
+        }
+
+        if !self.contains(point) {
+            return Vec::new();
+        }
+
+        return element.into_iter().collect();
+    }
+
+    fn contains(&self, point: &Point) -> bool {
+        return point.x >= self.position.x
+            && point.x < self.position.x + self.dimensions.width as isize
+            && point.y >= self.position.y
+            && point.y < self.position.y + self.dimensions.height as isize;
+    }
+
     fn set_dimensions(&mut self, containing_block_dimensions: &Dimensions) -> &Self {
         self.dimensions.width = self.calculate_width(containing_block_dimensions);
         self.dimensions.height = self.calculate_height(containing_block_dimensions);
@@ -54,54 +136,228 @@ impl<'a> LayoutNode<'a> {
     }
 
     fn calculate_width(&self, containing_block_dimensions: &Dimensions) -> usize {
-        let implicit_width = containing_block_dimensions.width;
-
-        if self.node.declarations.get("width").is_none() {
-            return implicit_width;
-        }
-
-        if let cssom::Value::Numeric(width) = &self.node.declarations["width"] {
-            return match width {
-                cssom::NumericValue::Zero => 0,
-                cssom::NumericValue::Px(number) => *number,
-                cssom::NumericValue::Percentage(number) => {
-                    number * containing_block_dimensions.width / 100
-                }
-            };
-        }
-
-        return implicit_width;
+        let implicit_width =
+            (containing_block_dimensions.width as isize - self.margin.left - self.margin.right)
+                .max(0) as usize;
+
+        return self
+            .resolve_property("width", containing_block_dimensions.width)
+            .filter(|width| *width >= 0.0)
+            .map_or(implicit_width, |width| width.round() as usize);
     }
 
     fn calculate_height(&self, containing_block_dimensions: &Dimensions) -> usize {
         let implicit_height = self
             .children
             .iter()
-            .fold(0usize, |acc, child| acc + child.dimensions.height);
+            .fold(0isize, |acc, child| acc + child.outer_height())
+            .max(0) as usize;
 
-        if self.node.declarations.get("height").is_none() {
-            return implicit_height;
-        }
+        return self
+            .resolve_property("height", containing_block_dimensions.height)
+            .filter(|height| *height >= 0.0)
+            .map_or(implicit_height, |height| height.round() as usize);
+    }
 
-        if let cssom::Value::Numeric(height) = &self.node.declarations["height"] {
-            return match height {
-                cssom::NumericValue::Zero => 0,
-                cssom::NumericValue::Px(number) => *number,
-                cssom::NumericValue::Percentage(number) => {
-                    number * containing_block_dimensions.height / 100
-                }
-            };
+    /// The height including margins, which is the space the box takes up
+    /// in the flow of its parent.
+    fn outer_height(&self) -> isize {
+        return self.margin.top + self.dimensions.height as isize + self.margin.bottom;
+    }
+
+    /// Font sizes are inherited. Relative font sizes refer to the font
+    /// size of the parent.
+    fn calculate_font_size(&self) -> f64 {
+        let parent_font_size = self.context.parent_font_size;
+
+        if let Some(cssom::Value::Numeric(font_size)) = self.node.declarations.get("font-size") {
+            return self
+                .context
+                .resolve_length(font_size, parent_font_size, parent_font_size)
+                .filter(|font_size| *font_size >= 0.0)
+                .unwrap_or(parent_font_size);
         }
 
-        return implicit_height;
+        return parent_font_size;
+    }
+
+    /// Percentage margins refer to the width of the containing block, even
+    /// for the top and bottom margins.
+    fn set_margin(&mut self, containing_block_dimensions: &Dimensions) -> &Self {
+        let margin = |side: &str| {
+            return self
+                .resolve_property(
+                    &format!("margin-{}", side),
+                    containing_block_dimensions.width,
+                )
+                .map_or(0, |margin| margin.round() as isize);
+        };
+
+        self.margin = Edges::new(
+            margin("top"),
+            margin("right"),
+            margin("bottom"),
+            margin("left"),
+        );
+
+        return self;
     }
 
-    fn set_position(&mut self, anchor: &Point) -> &Self {
-        // TODO: Support margin, position etc.
-        self.position = Point::new(anchor.x, anchor.y);
+    fn set_position(&mut self, anchor: &Point, containing_block_dimensions: &Dimensions) -> &Self {
+        let offset = self.relative_offset(containing_block_dimensions);
+
+        self.position = Point::new(
+            anchor.x + self.margin.left + offset.x,
+            anchor.y + self.margin.top + offset.y,
+        );
 
         return self;
     }
+
+    /// Relatively positioned boxes are moved by `top` and `left`, or else
+    /// by `bottom` and `right`, without affecting the boxes after them.
+    fn relative_offset(&self, containing_block_dimensions: &Dimensions) -> Point {
+        let is_relative = match self.node.declarations.get("position") {
+            Some(cssom::Value::Ident(position)) => position == "relative",
+            _ => false,
+        };
+
+        if !is_relative {
+            return Point::new(0, 0);
+        }
+
+        let offset = |start: &str, end: &str, percentage_base: usize| {
+            let start = self.resolve_property(start, percentage_base);
+            let end = self.resolve_property(end, percentage_base);
+
+            return match (start, end) {
+                (Some(start), _) => start.round() as isize,
+                (None, Some(end)) => -end.round() as isize,
+                (None, None) => 0,
+            };
+        };
+
+        return Point::new(
+            offset("left", "right", containing_block_dimensions.width),
+            offset("top", "bottom", containing_block_dimensions.height),
+        );
+    }
+
+    /// Resolves a length property to pixels, if it is set to a length.
+    fn resolve_property(&self, property: &str, percentage_base: usize) -> Option<f64> {
+        let length = match self.node.declarations.get(property) {
+            Some(cssom::Value::Numeric(value)) => {
+                self.context
+                    .resolve_length(value, percentage_base as f64, self.font_size)?
+            }
+            _ => return None,
+        };
+
+        return Some(length.clamp(-MAX_LENGTH, MAX_LENGTH));
+    }
+}
+
+/// What relative lengths are resolved against.
+#[derive(Clone, Copy, PartialEq)]
+pub struct LengthContext {
+    viewport: Dimensions,
+    root_font_size: f64,
+    parent_font_size: f64,
+}
+
+impl LengthContext {
+    /// The context of the root element, before it sets a font size.
+    pub fn new(viewport: &Dimensions) -> Self {
+        return Self {
+            viewport: *viewport,
+            root_font_size: INITIAL_FONT_SIZE,
+            parent_font_size: INITIAL_FONT_SIZE,
+        };
+    }
+
+    /// Resolves a length to pixels. Returns `None` for unitless numbers,
+    /// which aren’t lengths.
+    pub fn resolve_length(
+        &self,
+        value: &cssom::NumericValue,
+        percentage_base: f64,
+        font_size: f64,
+    ) -> Option<f64> {
+        let (number, unit) = match value {
+            cssom::NumericValue::Zero => return Some(0.0),
+            cssom::NumericValue::Px(number) => return Some(*number),
+            cssom::NumericValue::Percentage(number) => {
+                return Some(number * percentage_base / 100.0)
+            }
+            cssom::NumericValue::Number(_) => return None,
+            cssom::NumericValue::Calc(calculation) => {
+                if calculation.resolved_type()? == cssom::CalculationType::Number {
+                    return None;
+                }
+
+                return self.resolve_calculation(calculation, percentage_base, font_size);
+            }
+            cssom::NumericValue::Length(number, unit) => (*number, unit),
+        };
+
+        let viewport = self.viewport;
+        let vmin = viewport.width.min(viewport.height);
+        let vmax = viewport.width.max(viewport.height);
+
+        let px = match unit {
+            cssom::LengthUnit::Em => font_size,
+            cssom::LengthUnit::Rem => self.root_font_size,
+            // Without font metrics, both are assumed to be half an em
+            cssom::LengthUnit::Ex | cssom::LengthUnit::Ch => font_size / 2.0,
+            cssom::LengthUnit::Vw => viewport.width as f64 / 100.0,
+            cssom::LengthUnit::Vh => viewport.height as f64 / 100.0,
+            cssom::LengthUnit::Vmin => vmin as f64 / 100.0,
+            cssom::LengthUnit::Vmax => vmax as f64 / 100.0,
+            // Absolute units are defined relative to 96px per inch
+            cssom::LengthUnit::Pt => 96.0 / 72.0,
+            cssom::LengthUnit::Cm => 96.0 / 2.54,
+            cssom::LengthUnit::Mm => 96.0 / 25.4,
+            cssom::LengthUnit::In => 96.0,
+        };
+
+        return Some(number * px);
+    }
+
+    /// Resolves a math function. Numbers are kept as they are, so they
+    /// can be used as factors and divisors.
+    fn resolve_calculation(
+        &self,
+        calculation: &cssom::Calculation,
+        percentage_base: f64,
+        font_size: f64,
+    ) -> Option<f64> {
+        let resolve = |calculation: &cssom::Calculation| {
+            return self.resolve_calculation(calculation, percentage_base, font_size);
+        };
+
+        return match calculation {
+            cssom::Calculation::Value(cssom::NumericValue::Number(number)) => Some(*number),
+            cssom::Calculation::Value(value) => {
+                self.resolve_length(value, percentage_base, font_size)
+            }
+            cssom::Calculation::Add(left, right) => Some(resolve(left)? + resolve(right)?),
+            cssom::Calculation::Subtract(left, right) => Some(resolve(left)? - resolve(right)?),
+            cssom::Calculation::Multiply(left, right) => Some(resolve(left)? * resolve(right)?),
+            cssom::Calculation::Divide(left, right) => Some(resolve(left)? / resolve(right)?),
+            cssom::Calculation::Min(arguments) => arguments
+                .iter()
+                .map(resolve)
+                .try_fold(f64::INFINITY, |acc, value| Some(acc.min(value?))),
+            cssom::Calculation::Max(arguments) => arguments
+                .iter()
+                .map(resolve)
+                .try_fold(f64::NEG_INFINITY, |acc, value| Some(acc.max(value?))),
+            // The minimum wins over the maximum if they overlap
+            cssom::Calculation::Clamp(min, value, max) => {
+                Some(resolve(value)?.min(resolve(max)?).max(resolve(min)?))
+            }
+        };
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -119,18 +375,39 @@ impl Dimensions {
     }
 }
 
+/// A point relative to the top left corner of the viewport. Boxes can be
+/// moved past that corner, so coordinates may be negative.
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Point {
-    pub x: usize,
-    pub y: usize,
+    pub x: isize,
+    pub y: isize,
 }
 
 impl Point {
-    pub fn new(x: usize, y: usize) -> Self {
+    pub fn new(x: isize, y: isize) -> Self {
         return Self { x: x, y: y };
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Edges {
+    pub top: isize,
+    pub right: isize,
+    pub bottom: isize,
+    pub left: isize,
+}
+
+impl Edges {
+    pub fn new(top: isize, right: isize, bottom: isize, left: isize) -> Self {
+        return Self {
+            top: top,
+            right: right,
+            bottom: bottom,
+            left: left,
+        };
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -219,6 +496,82 @@ mod tests {
         assert!(percentage_layout_node.dimensions.height == 240);
     }
 
+    #[test]
+    fn test_layout_node_set_dimensions_units() {
+        let viewport = Dimensions::new(640, 480);
+        let anchor = Point::new(0, 0);
+        let dom = html::Parser::parse("<html><div><p></p></div></html>");
+
+        let css = "
+            html { font-size: 20px; }
+            div { font-size: 2em; width: 10rem; height: 50vh; }
+            p { font-size: 50%; width: 2em; height: 1in; }
+        ";
+        let rulesets = css::Parser::parse(css);
+        let render_node = render::RenderNode::from(&dom[0], &rulesets);
+        let html = LayoutNode::from(&render_node, &viewport, &anchor);
+        let div = &html.children[0];
+        let p = &div.children[0];
+
+        assert!(html.font_size == 20.0);
+        assert!(div.font_size == 40.0);
+        assert!(div.dimensions == Dimensions::new(200, 240));
+        assert!(p.font_size == 20.0);
+        assert!(p.dimensions == Dimensions::new(40, 96));
+
+        let width = |css: &str| {
+            let rulesets = css::Parser::parse(css);
+            let render_node = render::RenderNode::from(&dom[0], &rulesets);
+            return LayoutNode::from(&render_node, &viewport, &anchor)
+                .dimensions
+                .width;
+        };
+
+        assert!(width("html { width: 2em; }") == 32);
+        assert!(width("html { width: 2ex; }") == 16);
+        assert!(width("html { font-size: 20px; width: 3ex; }") == 30);
+        assert!(width("html { width: 4ch; }") == 32);
+        assert!(width("html { width: 10vw; }") == 64);
+        assert!(width("html { width: 10vh; }") == 48);
+        assert!(width("html { width: 150vh; }") == 720);
+        assert!(width("html { width: 10vmin; }") == 48);
+        assert!(width("html { width: 10vmax; }") == 64);
+        assert!(width("html { width: 12pt; }") == 16);
+        assert!(width("html { width: 1cm; }") == 38);
+        assert!(width("html { width: 10mm; }") == 38);
+        assert!(width("html { width: 1; }") == 640);
+    }
+
+    #[test]
+    fn test_layout_node_set_dimensions_math_functions() {
+        let viewport = Dimensions::new(640, 480);
+        let anchor = Point::new(0, 0);
+        let dom = html::Parser::parse("<html><div></div></html>");
+
+        let dimensions = |css: &str| {
+            let rulesets = css::Parser::parse(css);
+            let render_node = render::RenderNode::from(&dom[0], &rulesets);
+            let html = LayoutNode::from(&render_node, &viewport, &anchor);
+
+            return html.children[0].dimensions;
+        };
+
+        assert!(
+            dimensions("div { width: calc(100% - 2 * 16px); height: calc((50% + 10px) / 2); }")
+                == Dimensions::new(608, 5)
+        );
+        assert!(
+            dimensions("div { width: calc(50% - 2em); height: calc(50vh - 40px); }")
+                == Dimensions::new(288, 200)
+        );
+        assert!(dimensions("div { width: clamp(1rem, 2vw, 2rem); }").width == 16);
+        assert!(dimensions("div { width: clamp(1rem, 4vw, 2rem); }").width == 26);
+        assert!(dimensions("div { width: clamp(1rem, 10vw, 2rem); }").width == 32);
+        assert!(dimensions("div { width: min(50%, 100px, 20em); }").width == 100);
+        assert!(dimensions("div { width: max(50%, 100px, 20em); }").width == 320);
+        assert!(dimensions("div { width: calc(2 * 3); }").width == 640);
+    }
+
     #[test]
     fn test_layout_node_calculate_position() {
         let viewport = Dimensions::new(640, 480);
@@ -232,4 +585,150 @@ mod tests {
         assert!(layout_node.children[0].position == Point::new(0, 0));
         assert!(layout_node.children[1].position == Point::new(0, 100));
     }
+
+    #[test]
+    fn test_layout_node_calculate_position_margins_and_offsets() {
+        let viewport = Dimensions::new(640, 480);
+        let anchor = Point::new(0, 0);
+
+        let css = "
+            div { height: 100px; }
+            .first { margin: 10px 20px -30px 10%; }
+            .second { margin-top: -0.5em; }
+            .third { position: relative; bottom: 5px; left: -10px; }
+        ";
+        let rulesets = css::Parser::parse(css);
+        let dom = html::Parser::parse(
+            "<main><div class=\"first\"></div><div class=\"second\"></div><div class=\"third\"></div></main>",
+        );
+        let render_node = render::RenderNode::from(&dom[0], &rulesets);
+        let main = LayoutNode::from(&render_node, &viewport, &anchor);
+
+        let first = &main.children[0];
+        let second = &main.children[1];
+        let third = &main.children[2];
+
+        assert!(first.margin == Edges::new(10, 20, -30, 64));
+        assert!(first.position == Point::new(64, 10));
+        assert!(first.dimensions.width == 556);
+        assert!(second.position == Point::new(0, 72));
+        assert!(third.position == Point::new(-10, 167));
+        assert!(main.dimensions.height == 272);
+    }
+
+    #[test]
+    fn test_layout_node_percentages_of_containing_block() {
+        let viewport = Dimensions::new(640, 480);
+        let anchor = Point::new(0, 0);
+        let dom = html::Parser::parse("<main><div><p></p></div></main>");
+
+        let css = "
+            main { width: 400px; height: 300px; }
+            div { width: 50%; height: 50%; margin-left: 10%; }
+            p { width: 50%; height: 10%; margin-top: 10%; position: relative; left: 10%; top: 10%; }
+        ";
+        let rulesets = css::Parser::parse(css);
+        let render_node = render::RenderNode::from(&dom[0], &rulesets);
+        let main = LayoutNode::from(&render_node, &viewport, &anchor);
+        let div = &main.children[0];
+        let p = &div.children[0];
+
+        // Percentages refer to the parent, not the viewport
+        assert!(div.dimensions == Dimensions::new(200, 150));
+        assert!(div.position == Point::new(40, 0));
+        assert!(p.dimensions == Dimensions::new(100, 15));
+
+        // Vertical margins refer to the width, offsets to the matching side
+        assert!(p.margin.top == 20);
+        assert!(p.position == Point::new(60, 35));
+    }
+
+    #[test]
+    fn test_layout_node_negative_margins() {
+        let viewport = Dimensions::new(640, 480);
+        let anchor = Point::new(0, 0);
+        let dom = html::Parser::parse(
+            "<main><div class=\"a\"></div><div class=\"b\"></div><div class=\"c\"></div></main>",
+        );
+
+        let css = "
+            div { height: 100px; }
+            .a { margin: -20px -30px -40px -10px; }
+            .b { margin-top: -50px; width: 100px; width: -10px; }
+        ";
+        let rulesets = css::Parser::parse(css);
+        let render_node = render::RenderNode::from(&dom[0], &rulesets);
+        let main = LayoutNode::from(&render_node, &viewport, &anchor);
+        let (a, b, c) = (&main.children[0], &main.children[1], &main.children[2]);
+
+        // Negative side margins widen the implicit width
+        assert!(a.position == Point::new(-10, -20));
+        assert!(a.dimensions.width == 680);
+
+        // Negative vertical margins pull the following siblings up
+        assert!(b.position == Point::new(0, -10));
+        assert!(b.dimensions.width == 100);
+        assert!(c.position == Point::new(0, 90));
+        assert!(main.dimensions.height == 190);
+    }
+
+    #[test]
+    fn test_layout_node_huge_lengths() {
+        let viewport = Dimensions::new(640, 480);
+        let anchor = Point::new(0, 0);
+        let dom = html::Parser::parse("<main><div></div><div></div></main>");
+
+        let layout = |css: &str| {
+            let rulesets = css::Parser::parse(css);
+            let render_node = render::RenderNode::from(&dom[0], &rulesets);
+            let main = LayoutNode::from(&render_node, &viewport, &anchor);
+
+            let div = &main.children[0];
+
+            return (
+                div.margin,
+                div.dimensions,
+                div.position,
+                main.children[1].position,
+            );
+        };
+
+        let (margin, dimensions, _, _) = layout("div { margin-left: -1e19px; }");
+        assert!(margin.left == -1_000_000_000);
+        assert!(dimensions.width == 1_000_000_640);
+
+        let (_, dimensions, position, next) =
+            layout("div { margin: -1e20px; position: relative; top: 1e20px; }");
+        assert!(dimensions.width == 2_000_000_640);
+        assert!(position == Point::new(-1_000_000_000, 0));
+        assert!(next.y == -2_000_000_000);
+
+        let (_, _, position, next) = layout("div { margin-top: 1e19px; height: 1e19px; }");
+        assert!(position.y == 1_000_000_000);
+        assert!(next.y == 3_000_000_000);
+    }
+
+    #[test]
+    fn test_layout_node_hit_test() {
+        let viewport = Dimensions::new(640, 480);
+        let anchor = Point::new(0, 0);
+
+        let rulesets = css::Parser::parse("div { height: 100px; } p { width: 50%; height: 50px; }");
+        let dom = html::Parser::parse("<main><div><p></p></div><div></div></main>");
+        let render_node = render::RenderNode::from(&dom[0], &rulesets);
+        let layout_node = LayoutNode::from(&render_node, &viewport, &anchor);
+
+        let tags = |x: isize, y: isize| {
+            return layout_node
+                .hit_test(&Point::new(x, y))
+                .iter()
+                .map(|element| element.tag.as_ref())
+                .collect::<Vec<&str>>();
+        };
+
+        assert!(tags(10, 10) == ["main", "div", "p"]);
+        assert!(tags(400, 10) == ["main", "div"]);
+        assert!(tags(10, 150) == ["main", "div"]);
+        assert!(tags(10, 250) == Vec::<&str>::new());
+    }
 }
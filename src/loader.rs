@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Loads resources, like imported stylesheets, by their URL.
+pub trait Loader {
+    /// Returns the contents of a resource, or `None` if it can’t be loaded.
+    fn load(&self, url: &str) -> Option<String>;
+}
+
+/// Loads resources from files below a root directory. URLs are paths
+/// relative to that directory.
+pub struct FileLoader {
+    root: PathBuf,
+}
+
+impl FileLoader {
+    pub fn new(root: PathBuf) -> Self {
+        return Self { root: root };
+    }
+}
+
+impl Loader for FileLoader {
+    fn load(&self, url: &str) -> Option<String> {
+        return fs::read_to_string(self.root.join(url.trim_start_matches('/'))).ok();
+    }
+}
+
+/// Resources kept in memory, keyed by their URL.
+impl Loader for HashMap<String, String> {
+    fn load(&self, url: &str) -> Option<String> {
+        return self.get(url).cloned();
+    }
+}
+
+/// Resolves a URL relative to the URL of the resource referring to it.
+/// Absolute URLs are kept as they are, paths are normalized, so the same
+/// resource always has the same URL.
+pub fn resolve_url(base: &str, url: &str) -> String {
+    if url.contains("://") {
+        return url.to_owned();
+    }
+
+    let path = match url.starts_with('/') {
+        true => url.to_owned(),
+        false => match base.rfind('/') {
+            Some(index) => format!("{}{}", &base[..index + 1], url),
+            None => url.to_owned(),
+        },
+    };
+
+    let mut segments: Vec<&str> = Vec::new();
+
+    for segment in path.split('/') {
+        match segment {
+            "." => (),
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    // Keep the leading slash of absolute paths
+    if path.starts_with('/') && segments.first() != Some(&"") {
+        segments.insert(0, "");
+    }
+
+    return segments.join("/");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_url() {
+        assert!(resolve_url("main.css", "base.css") == "base.css");
+        assert!(resolve_url("css/main.css", "base.css") == "css/base.css");
+        assert!(resolve_url("css/main.css", "./lib/../base.css") == "css/base.css");
+        assert!(resolve_url("css/main.css", "../base.css") == "base.css");
+        assert!(resolve_url("/css/main.css", "../base.css") == "/base.css");
+        assert!(resolve_url("css/main.css", "/base.css") == "/base.css");
+        assert!(resolve_url("css/main.css", "https://a.b/c.css") == "https://a.b/c.css");
+    }
+
+    #[test]
+    fn test_file_loader() {
+        let root = std::env::temp_dir().join("browser-test-file-loader");
+        fs::create_dir_all(root.join("css")).unwrap();
+        fs::write(root.join("css/base.css"), "p { color: red; }").unwrap();
+
+        let loader = FileLoader::new(root);
+
+        assert!(loader.load("css/base.css") == Some("p { color: red; }".to_owned()));
+        assert!(loader.load("/css/base.css") == Some("p { color: red; }".to_owned()));
+        assert!(loader.load("css/missing.css") == None);
+    }
+}
@@ -3,15 +3,21 @@ pub mod cssom;
 pub mod dom;
 pub mod html;
 pub mod layout;
+pub mod loader;
+pub mod media;
 pub mod painting;
 pub mod parser;
 pub mod render;
+pub mod shorthand;
+pub mod supports;
+pub mod tokenizer;
+pub mod variables;
 pub mod window;
 
 fn main() {
     let mut window = window::Window::new();
 
-    let dom = html::Parser::parse(
+    let document = dom::Document::new(html::Parser::parse(
         "
         <html>
             <head>
@@ -27,7 +33,7 @@ fn main() {
             </body>
         </html>
     ",
-    );
+    ));
 
     let styles = css::Parser::parse(
         "
@@ -49,19 +55,95 @@ fn main() {
             height: 50%;
             background-color: #0f0;
         }
+
+        .inner:hover {
+            background-color: #ff0;
+        }
+
+        .inner:active {
+            background-color: #f0f;
+        }
+
+        @media (max-width: 480px) {
+            .first, .second {
+                width: 100%;
+            }
+        }
     ",
     );
 
-    let viewport = layout::Dimensions::new(640, 480);
     let anchor = layout::Point::new(0, 0);
 
-    let render_node = render::RenderNode::from(&dom[0], &styles);
-    let layout_node = layout::LayoutNode::from(&render_node, &viewport, &anchor);
+    while window.is_open() {
+        let viewport = window.viewport();
+        let device = media::Device::new(&viewport);
 
-    window.paint_node(&layout_node.children[1].children[0].children[0]);
-    window.paint_node(&layout_node.children[1].children[0].children[1]);
+        let render_node = render::RenderNode::from_document(&document, &styles, &device).unwrap();
+        let layout_node = layout::LayoutNode::from(&render_node, &viewport, &anchor);
 
-    while window.is_open() {
-        window.update();
+        window.clear();
+        window.paint_node(&layout_node);
+
+        // Restyle and repaint once an element changed its state or the
+        // window was resized, which may change the matching media queries
+        while window.is_open() {
+            window.update();
+
+            if window.viewport() != viewport {
+                break;
+            }
+
+            if update_element_states(&document, &layout_node, &window) {
+                break;
+            }
+        }
     }
 }
+
+/// Updates the hover, active and focus states from the mouse and keyboard
+/// input and returns whether any state changed.
+fn update_element_states(
+    document: &dom::Document,
+    layout_node: &layout::LayoutNode,
+    window: &window::Window,
+) -> bool {
+    let hovered = match window.mouse_position() {
+        Some(position) => layout_node.hit_test(&position),
+        None => Vec::new(),
+    };
+
+    let active = match window.is_mouse_down() {
+        true => hovered.clone(),
+        false => Vec::new(),
+    };
+
+    let mut changed = document.states.set(dom::ElementState::Hover, &hovered);
+    changed |= document.states.set(dom::ElementState::Active, &active);
+
+    // Clicking focuses the innermost focusable element, tabbing moves the
+    // focus to the next one and makes it visible
+    let focused = document.element_in_state(dom::ElementState::Focus);
+
+    let (focus, focus_visible) = if window.is_tab_pressed() {
+        (document.next_focusable(focused), true)
+    } else if window.is_mouse_down() {
+        let focus = hovered.iter().rev().find(|element| element.is_focusable());
+        (focus.copied(), false)
+    } else {
+        let focus_visible = document.element_in_state(dom::ElementState::FocusVisible);
+        (focused, focus_visible.is_some())
+    };
+
+    let focus: Vec<&dom::Element> = focus.into_iter().collect();
+    let focus_visible = match focus_visible {
+        true => focus.clone(),
+        false => Vec::new(),
+    };
+
+    changed |= document.states.set(dom::ElementState::Focus, &focus);
+    changed |= document
+        .states
+        .set(dom::ElementState::FocusVisible, &focus_visible);
+
+    return changed;
+}
@@ -0,0 +1,283 @@
+use crate::cssom;
+use crate::layout;
+
+/// The device media queries are evaluated against.
+#[derive(PartialEq, Clone)]
+pub struct Device {
+    pub media_type: cssom::MediaType,
+    pub viewport: layout::Dimensions,
+    /// The number of device pixels per CSS pixel.
+    pub resolution: f64,
+    pub color_scheme: ColorScheme,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+impl Device {
+    pub fn new(viewport: &layout::Dimensions) -> Self {
+        return Self {
+            media_type: cssom::MediaType::Screen,
+            viewport: *viewport,
+            resolution: 1.0,
+            color_scheme: ColorScheme::Light,
+        };
+    }
+
+    pub fn media_type(mut self, media_type: cssom::MediaType) -> Self {
+        self.media_type = media_type;
+
+        return self;
+    }
+
+    pub fn resolution(mut self, resolution: f64) -> Self {
+        self.resolution = resolution;
+
+        return self;
+    }
+
+    pub fn color_scheme(mut self, color_scheme: ColorScheme) -> Self {
+        self.color_scheme = color_scheme;
+
+        return self;
+    }
+
+    /// Checks whether a ruleset nested in `@media` rules with these media
+    /// query lists applies.
+    pub fn matches(&self, media: &[cssom::MediaQueryList]) -> bool {
+        return media.iter().all(|list| self.matches_list(list));
+    }
+
+    pub fn matches_list(&self, list: &cssom::MediaQueryList) -> bool {
+        return list.is_empty() || list.iter().any(|query| self.matches_query(query));
+    }
+
+    /// Unknown conditions are treated as false, before a `not` in front of
+    /// the query is applied.
+    fn matches_query(&self, query: &cssom::MediaQuery) -> bool {
+        let media_type = match query.media_type {
+            cssom::MediaType::All => true,
+            ref media_type => *media_type == self.media_type,
+        };
+
+        let condition = match query.condition {
+            Some(ref condition) => self.evaluate(condition) == Some(true),
+            None => true,
+        };
+
+        return (media_type && condition) != query.negated;
+    }
+
+    /// Evaluates a condition, returning `None` if its result is unknown,
+    /// like for unknown features or values of the wrong type.
+    fn evaluate(&self, condition: &cssom::MediaCondition) -> Option<bool> {
+        return match condition {
+            cssom::MediaCondition::Feature(feature) => self.evaluate_feature(feature),
+            cssom::MediaCondition::Not(condition) => self.evaluate(condition).map(|result| !result),
+            cssom::MediaCondition::And(conditions) => {
+                let results: Vec<Option<bool>> = conditions
+                    .iter()
+                    .map(|condition| self.evaluate(condition))
+                    .collect();
+
+                match results.contains(&Some(false)) {
+                    true => Some(false),
+                    false => results
+                        .into_iter()
+                        .collect::<Option<Vec<_>>>()
+                        .map(|_| true),
+                }
+            }
+            cssom::MediaCondition::Or(conditions) => {
+                let results: Vec<Option<bool>> = conditions
+                    .iter()
+                    .map(|condition| self.evaluate(condition))
+                    .collect();
+
+                match results.contains(&Some(true)) {
+                    true => Some(true),
+                    false => results
+                        .into_iter()
+                        .collect::<Option<Vec<_>>>()
+                        .map(|_| false),
+                }
+            }
+            cssom::MediaCondition::Unknown(_) => None,
+        };
+    }
+
+    fn evaluate_feature(&self, feature: &cssom::MediaFeature) -> Option<bool> {
+        let (name, comparison, value) = match feature {
+            cssom::MediaFeature::Boolean(name) => {
+                return match name.as_ref() {
+                    "width" => Some(self.viewport.width != 0),
+                    "height" => Some(self.viewport.height != 0),
+                    "resolution" => Some(self.resolution != 0.0),
+                    "aspect-ratio" | "orientation" | "prefers-color-scheme" | "color" => Some(true),
+                    _ => None,
+                };
+            }
+            cssom::MediaFeature::Range(name, comparison, value) => (name, *comparison, value),
+        };
+
+        let width = self.viewport.width as f64;
+        let height = self.viewport.height as f64;
+
+        let (actual, expected) = match name.as_ref() {
+            "width" => (width, self.resolve_length(value)?),
+            "height" => (height, self.resolve_length(value)?),
+            "aspect-ratio" => match value {
+                cssom::MediaValue::Ratio(numerator, denominator) => {
+                    (width / height, numerator / denominator)
+                }
+                cssom::MediaValue::Number(ratio) => (width / height, *ratio),
+                _ => return None,
+            },
+            "resolution" => match value {
+                cssom::MediaValue::Resolution(resolution) => (self.resolution, *resolution),
+                _ => return None,
+            },
+            // Colors are painted with eight bits per channel
+            "color" => match value {
+                cssom::MediaValue::Number(bits) => (8.0, *bits),
+                cssom::MediaValue::Length(cssom::NumericValue::Zero) => (8.0, 0.0),
+                _ => return None,
+            },
+            "orientation" => {
+                let orientation = match height >= width {
+                    true => "portrait",
+                    false => "landscape",
+                };
+
+                return self.evaluate_keyword(
+                    comparison,
+                    value,
+                    &["portrait", "landscape"],
+                    orientation,
+                );
+            }
+            "prefers-color-scheme" => {
+                let color_scheme = match self.color_scheme {
+                    ColorScheme::Light => "light",
+                    ColorScheme::Dark => "dark",
+                };
+
+                return self.evaluate_keyword(comparison, value, &["light", "dark"], color_scheme);
+            }
+            _ => return None,
+        };
+
+        return Some(match comparison {
+            cssom::Comparison::Equal => actual == expected,
+            cssom::Comparison::Less => actual < expected,
+            cssom::Comparison::LessOrEqual => actual <= expected,
+            cssom::Comparison::Greater => actual > expected,
+            cssom::Comparison::GreaterOrEqual => actual >= expected,
+        });
+    }
+
+    /// Evaluates a discrete feature, which only has a few keywords as
+    /// values and can’t be used in ranges.
+    fn evaluate_keyword(
+        &self,
+        comparison: cssom::Comparison,
+        value: &cssom::MediaValue,
+        keywords: &[&str],
+        actual: &str,
+    ) -> Option<bool> {
+        return match value {
+            cssom::MediaValue::Ident(ident)
+                if comparison == cssom::Comparison::Equal && keywords.contains(&&ident[..]) =>
+            {
+                Some(ident == actual)
+            }
+            _ => None,
+        };
+    }
+
+    /// Resolves a length in a media query. Relative lengths refer to the
+    /// initial font size and the viewport.
+    fn resolve_length(&self, value: &cssom::MediaValue) -> Option<f64> {
+        return match value {
+            cssom::MediaValue::Length(length) => layout::LengthContext::new(&self.viewport)
+                .resolve_length(length, 0.0, layout::INITIAL_FONT_SIZE),
+            _ => None,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css;
+
+    fn matches(device: &Device, media: &str) -> bool {
+        let rulesets = css::Parser::parse(&format!("@media {} {{ a {{}} }}", media));
+
+        return device.matches(&rulesets[0].media);
+    }
+
+    #[test]
+    fn test_device_matches_media_types() {
+        let device = Device::new(&layout::Dimensions::new(800, 600));
+        let print = device.clone().media_type(cssom::MediaType::Print);
+
+        assert!(matches(&device, "all") == true);
+        assert!(matches(&device, "screen") == true);
+        assert!(matches(&device, "print") == false);
+        assert!(matches(&device, "tv") == false);
+        assert!(matches(&device, "not print") == true);
+        assert!(matches(&device, "print, screen") == true);
+        assert!(matches(&print, "print") == true);
+        assert!(matches(&print, "only screen") == false);
+        assert!(matches(&device, "screen and, print") == false);
+    }
+
+    #[test]
+    fn test_device_matches_media_features() {
+        let device = Device::new(&layout::Dimensions::new(800, 600))
+            .resolution(2.0)
+            .color_scheme(ColorScheme::Dark);
+
+        assert!(matches(&device, "(width)") == true);
+        assert!(matches(&device, "(width: 800px)") == true);
+        assert!(matches(&device, "(min-width: 600px)") == true);
+        assert!(matches(&device, "(max-width: 600px)") == false);
+        assert!(matches(&device, "(max-width: 50em)") == true);
+        assert!(matches(&device, "(width > 100vw)") == false);
+        assert!(matches(&device, "(500px < height <= 600px)") == true);
+        assert!(matches(&device, "(aspect-ratio: 4/3)") == true);
+        assert!(matches(&device, "(min-aspect-ratio: 16/9)") == false);
+        assert!(matches(&device, "(orientation: landscape)") == true);
+        assert!(matches(&device, "(min-resolution: 192dpi)") == true);
+        assert!(matches(&device, "(resolution: 1x)") == false);
+        assert!(matches(&device, "(prefers-color-scheme: dark)") == true);
+        assert!(matches(&device, "(color)") == true);
+        assert!(matches(&device, "(min-color: 8)") == true);
+        assert!(matches(&device, "(min-width: 600px) and (max-width: 700px)") == false);
+        assert!(matches(&device, "(max-width: 600px) or (orientation: landscape)") == true);
+        assert!(matches(&device, "not (orientation: portrait)") == true);
+        assert!(matches(&device, "screen and (max-height: 500px)") == false);
+    }
+
+    #[test]
+    fn test_device_matches_unknown() {
+        let device = Device::new(&layout::Dimensions::new(800, 600));
+
+        // Unknown features, values of the wrong type and anything else in
+        // parentheses never match, not even when negated
+        assert!(matches(&device, "(hover)") == false);
+        assert!(matches(&device, "not (hover)") == false);
+        assert!(matches(&device, "(width: landscape)") == false);
+        assert!(matches(&device, "(orientation > portrait)") == false);
+        assert!(matches(&device, "not (width > 1px > 2px)") == false);
+        assert!(matches(&device, "(hover) or (color)") == true);
+        assert!(matches(&device, "(hover) and (monochrome: 0)") == false);
+
+        // The `not` of a query is applied after unknown results are false
+        assert!(matches(&device, "not all and (hover)") == true);
+    }
+}
@@ -20,13 +20,24 @@ impl WindowBuffer {
         return &self.inner;
     }
 
+    pub fn clear(&mut self) -> () {
+        self.inner.fill(0xffffff);
+    }
+
     pub fn paint_node(&mut self, node: &layout::LayoutNode) -> () {
-        let background_color = node.node.declarations.get("background-color");
+        let declarations = &node.node.declarations;
+
+        let background_color = match declarations.get("background-color") {
+            Some(cssom::Value::Color(color)) => Some(color.clone()),
+            Some(cssom::Value::CurrentColor) => match declarations.get("color") {
+                Some(cssom::Value::Color(color)) => Some(color.clone()),
+                _ => Some(cssom::Color::new(0, 0, 0)),
+            },
+            _ => None,
+        };
 
-        if background_color.is_some() {
-            if let cssom::Value::Color(background_color) = background_color.unwrap() {
-                self.paint_rect(&node.position, &node.dimensions, background_color.as_u32());
-            }
+        if let Some(background_color) = background_color {
+            self.paint_rect(&node.position, &node.dimensions, &background_color);
         }
 
         for child in &node.children {
@@ -38,16 +49,18 @@ impl WindowBuffer {
         &mut self,
         position: &layout::Point,
         dimensions: &layout::Dimensions,
-        color: u32,
+        color: &cssom::Color,
     ) -> () {
-        let x1 = position.x;
-        let x2 = position.x + dimensions.width;
-        let y1 = position.y;
-        let y2 = position.y + dimensions.height;
+        // Only paint the part of the rectangle inside the window
+        let x1 = position.x.max(0) as usize;
+        let x2 = (position.x + dimensions.width as isize).clamp(0, self.width as isize) as usize;
+        let y1 = position.y.max(0) as usize;
+        let y2 = (position.y + dimensions.height as isize).clamp(0, self.height as isize) as usize;
 
         for x in x1..x2 {
             for y in y1..y2 {
-                self.paint_pixel(x, y, color);
+                let background = self.inner[y * self.width + x];
+                self.paint_pixel(x, y, color.blend(background));
             }
         }
     }
@@ -103,7 +116,11 @@ mod tests {
     #[test]
     fn test_paint_rect() {
         let mut buffer = WindowBuffer::new(5, 5);
-        buffer.paint_rect(&layout::Point::new(2, 2), &layout::Dimensions::new(2, 2), 0);
+        buffer.paint_rect(
+            &layout::Point::new(2, 2),
+            &layout::Dimensions::new(2, 2),
+            &cssom::Color::new(0, 0, 0),
+        );
 
         #[rustfmt::skip]
         let expected = vec![
@@ -117,6 +134,36 @@ mod tests {
         assert!(*buffer.as_vec() == expected);
     }
 
+    #[test]
+    fn test_paint_node_colors() {
+        let dom = html::Parser::parse("<div><p></p><span></span></div>");
+        let rulesets = css::Parser::parse(
+            "
+            div { width: 100%; height: 100%; background-color: rgb(0 0 255); }
+            p { width: 2px; height: 1px; color: red; background-color: currentColor; }
+            span { width: 2px; height: 1px; background-color: #ff000080; }
+        ",
+        );
+
+        let viewport = layout::Dimensions::new(2, 3);
+        let anchor = layout::Point::new(0, 0);
+
+        let render_node = render::RenderNode::from(&dom[0], &rulesets);
+        let layout_node = layout::LayoutNode::from(&render_node, &viewport, &anchor);
+
+        let mut buffer = WindowBuffer::new(2, 3);
+        buffer.paint_node(&layout_node);
+
+        #[rustfmt::skip]
+        let expected = vec![
+            0xff0000, 0xff0000,
+            0x80007f, 0x80007f,
+            0x0000ff, 0x0000ff,
+        ];
+
+        assert!(*buffer.as_vec() == expected);
+    }
+
     #[test]
     fn test_paint_pixel() {
         let mut buffer = WindowBuffer::new(5, 5);
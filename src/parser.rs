@@ -41,6 +41,12 @@ impl Parser {
         return self.input[self.pos..].chars().next().unwrap();
     }
 
+    /// Looks ahead without consuming anything. `peek_char(0)` is the next
+    /// char, returns `None` past the end of the input.
+    pub fn peek_char(&self, offset: usize) -> Option<char> {
+        return self.input[self.pos..].chars().nth(offset);
+    }
+
     pub fn starts_with(&self, prefix: &str) -> bool {
         return self.input[self.pos..].starts_with(prefix);
     }
@@ -111,6 +117,17 @@ mod tests {
         assert!(parser.next_char() == 'h');
     }
 
+    #[test]
+    fn test_parser_peek_char() {
+        let mut parser = Parser::new("aä!");
+        parser.consume_char();
+
+        assert!(parser.peek_char(0) == Some('ä'));
+        assert!(parser.peek_char(1) == Some('!'));
+        assert!(parser.peek_char(2) == None);
+        assert!(parser.pos == 1);
+    }
+
     #[test]
     fn test_parser_starts_with() {
         let parser = Parser::new("<html></html>");
@@ -1,5 +1,7 @@
 use crate::cssom;
 use crate::dom;
+use crate::media;
+use crate::variables;
 use std::collections::HashMap;
 use std::vec::Vec;
 
@@ -29,39 +31,172 @@ const BLOCK_ELEMENTS: [&str; 18] = [
     "xmp",
 ];
 
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Clone)]
 pub struct RenderNode<'a> {
+    /// For generated content, this is the originating element.
     pub node: &'a dom::Node,
-    pub declarations: cssom::Declarations,
+    pub pseudo_element: Option<cssom::PseudoElement>,
+    /// The text of generated content.
+    pub content: Option<String>,
+    pub declarations: cssom::ComputedStyle,
     pub children: RenderNodes<'a>,
 }
 
 impl<'a> RenderNode<'a> {
     pub fn from(node: &'a dom::Node, rulesets: &'a cssom::Rulesets) -> Self {
+        let mut context = RenderContext::new(rulesets, None, None);
+
+        return Self::from_context(node, &mut context, None, std::slice::from_ref(node), 0);
+    }
+
+    /// Builds the render node for the document’s root element, matching
+    /// dynamic pseudo-classes like `:hover` against the document’s
+    /// element states and `@media` rules against the device.
+    pub fn from_document(
+        document: &'a dom::Document,
+        rulesets: &'a cssom::Rulesets,
+        device: &'a media::Device,
+    ) -> Option<Self> {
+        let root = document.root()?;
+        let index = document
+            .nodes
+            .iter()
+            .position(|node| std::ptr::eq(node, root))
+            .unwrap();
+
+        let mut context = RenderContext::new(rulesets, Some(&document.states), Some(device));
+
+        return Some(Self::from_context(
+            root,
+            &mut context,
+            None,
+            &document.nodes,
+            index,
+        ));
+    }
+
+    /// Builds the render node for `siblings[index]`, `parent` being the
+    /// context of the element containing it. Nodes are built in tree
+    /// order, so counters and quotes carry over from earlier elements.
+    fn from_context(
+        node: &'a dom::Node,
+        context: &mut RenderContext<'a>,
+        parent: Option<&ElementContext>,
+        siblings: &'a [dom::Node],
+        index: usize,
+    ) -> Self {
         if let None = node.element() {
             return RenderNode {
                 node: node,
+                pseudo_element: None,
+                content: None,
                 declarations: HashMap::new(),
                 children: Vec::new(),
             };
         }
 
         let element = node.element().unwrap();
-        let children = element
-            .children
-            .iter()
-            .map(|child| RenderNode::from(child, rulesets))
-            .collect();
+        let element_context = ElementContext {
+            element: element,
+            states: context.states,
+            device: context.device,
+            parent: parent,
+            siblings: siblings,
+            index: index,
+        };
 
-        let declarations = declarations_for_element(&element, &rulesets);
+        let declarations = variables::substitute_variables(
+            declarations_for_element(&element_context, context.rulesets, None),
+            &context.custom_properties,
+        );
+        context.update_counters(&declarations);
+
+        // Counters created by descendants go out of scope with the element
+        let counters = context.counters.len();
+        let mut children = RenderNodes::new();
+
+        let before = Self::generated(
+            node,
+            &element_context,
+            &declarations,
+            context,
+            cssom::PseudoElement::Before,
+        );
+        children.extend(before);
+
+        // Custom properties are inherited by the element’s children
+        let custom_properties = std::mem::replace(
+            &mut context.custom_properties,
+            declarations
+                .iter()
+                .filter(|(property, _)| variables::is_custom_property(property))
+                .map(|(property, value)| (property.clone(), value.clone()))
+                .collect(),
+        );
+
+        for (index, child) in element.children.iter().enumerate() {
+            children.push(RenderNode::from_context(
+                child,
+                context,
+                Some(&element_context),
+                &element.children,
+                index,
+            ));
+        }
+
+        context.custom_properties = custom_properties;
+
+        let after = Self::generated(
+            node,
+            &element_context,
+            &declarations,
+            context,
+            cssom::PseudoElement::After,
+        );
+        children.extend(after);
+
+        context.counters.truncate(counters);
 
         return RenderNode {
             node: node,
+            pseudo_element: None,
+            content: None,
             children: children,
             declarations: declarations,
         };
     }
 
+    /// Builds the render node for a `::before` or `::after` pseudo-element,
+    /// if its `content` generates one.
+    fn generated(
+        node: &'a dom::Node,
+        element: &ElementContext,
+        element_declarations: &cssom::ComputedStyle,
+        context: &mut RenderContext<'a>,
+        pseudo_element: cssom::PseudoElement,
+    ) -> Option<Self> {
+        let declarations = variables::substitute_variables(
+            declarations_for_element(element, context.rulesets, Some(pseudo_element)),
+            element_declarations,
+        );
+        context.update_counters(&declarations);
+
+        // `quotes` is inherited from the originating element
+        let quotes = declarations
+            .get("quotes")
+            .or(element_declarations.get("quotes"));
+
+        let content = context.generated_content(element.element, &declarations, quotes)?;
+
+        return Some(RenderNode {
+            node: node,
+            pseudo_element: Some(pseudo_element),
+            content: Some(content),
+            children: Vec::new(),
+            declarations: declarations,
+        });
+    }
+
     fn display_type(&self) -> DisplayType {
         let element = self.node.element();
 
@@ -69,17 +204,22 @@ impl<'a> RenderNode<'a> {
             return DisplayType::Inline;
         }
 
+        // Generated content doesn’t take the defaults of the originating
+        // element’s tag
+        let tag = match self.pseudo_element {
+            Some(_) => "",
+            None => element.unwrap().tag.as_ref(),
+        };
+
         if let Some(value) = self.declarations.get("display") {
             match value {
-                cssom::Value::String(value) if value == "none" => return DisplayType::None,
-                cssom::Value::String(value) if value == "block" => return DisplayType::Block,
-                cssom::Value::String(value) if value == "inline" => return DisplayType::Inline,
+                cssom::Value::Ident(value) if value == "none" => return DisplayType::None,
+                cssom::Value::Ident(value) if value == "block" => return DisplayType::Block,
+                cssom::Value::Ident(value) if value == "inline" => return DisplayType::Inline,
                 _ => (),
             }
         }
 
-        let tag = element.unwrap().tag.as_ref();
-
         if HIDDEN_ELEMENTS.contains(&tag) {
             return DisplayType::None;
         }
@@ -94,20 +234,277 @@ impl<'a> RenderNode<'a> {
 
 pub type RenderNodes<'a> = Vec<RenderNode<'a>>;
 
-struct MatchedRuleset<'a> {
-    selector: &'a cssom::Selector,
-    declarations: &'a cssom::Declarations,
+/// State shared while building a render tree.
+struct RenderContext<'a> {
+    rulesets: &'a cssom::Rulesets,
+    states: Option<&'a dom::ElementStates>,
+    device: Option<&'a media::Device>,
+    /// Counters in scope, innermost last.
+    counters: Vec<(String, i32)>,
+    quote_depth: usize,
+    /// Custom properties of the parent element.
+    custom_properties: cssom::ComputedStyle,
 }
 
-impl<'a> MatchedRuleset<'a> {
-    pub fn new(selector: &'a cssom::Selector, declarations: &'a cssom::Declarations) -> Self {
+impl<'a> RenderContext<'a> {
+    pub fn new(
+        rulesets: &'a cssom::Rulesets,
+        states: Option<&'a dom::ElementStates>,
+        device: Option<&'a media::Device>,
+    ) -> Self {
         return Self {
-            selector: selector,
-            declarations: declarations,
+            rulesets: rulesets,
+            states: states,
+            device: device,
+            counters: Vec::new(),
+            quote_depth: 0,
+            custom_properties: cssom::ComputedStyle::new(),
+        };
+    }
+
+    /// Applies `counter-reset` and `counter-increment`, in that order.
+    fn update_counters(&mut self, declarations: &cssom::ComputedStyle) -> () {
+        for (name, value) in parse_counter_list(declarations.get("counter-reset"), 0) {
+            self.counters.push((name, value));
+        }
+
+        for (name, value) in parse_counter_list(declarations.get("counter-increment"), 1) {
+            match self
+                .counters
+                .iter_mut()
+                .rev()
+                .find(|counter| counter.0 == name)
+            {
+                Some(counter) => counter.1 = counter.1.saturating_add(value),
+                None => self.counters.push((name, value)),
+            }
+        }
+    }
+
+    fn counter(&self, name: &str) -> i32 {
+        return self
+            .counters
+            .iter()
+            .rev()
+            .find(|counter| counter.0 == name)
+            .map_or(0, |counter| counter.1);
+    }
+
+    /// Resolves the `content` property of a pseudo-element to its text.
+    /// Returns `None` for `normal`, `none` and invalid values, which
+    /// generate no box.
+    fn generated_content(
+        &mut self,
+        element: &dom::Element,
+        declarations: &cssom::ComputedStyle,
+        quotes: Option<&cssom::Value>,
+    ) -> Option<String> {
+        let values = declarations.get("content")?.space_separated();
+
+        let quotes = parse_quotes(quotes);
+        let quote = |depth: usize, close: bool| {
+            return quotes
+                .get(depth.min(quotes.len().saturating_sub(1)))
+                .map_or(String::new(), |pair| match close {
+                    true => pair.1.clone(),
+                    false => pair.0.clone(),
+                });
+        };
+
+        let mut content = String::new();
+
+        for value in values {
+            match value {
+                cssom::Value::String(string) => content.push_str(string),
+
+                cssom::Value::Function(name, arguments) if name.eq_ignore_ascii_case("attr") => {
+                    let name = arguments.first()?.ident()?;
+                    content.push_str(element.attrs.get(name).map_or("", |value| value));
+                }
+
+                cssom::Value::Function(name, arguments) if name.eq_ignore_ascii_case("counter") => {
+                    let name = arguments.first()?.ident()?;
+                    content.push_str(&self.counter(name).to_string());
+                }
+
+                cssom::Value::Ident(ident) => match ident.to_ascii_lowercase().as_ref() {
+                    "open-quote" => {
+                        content.push_str(&quote(self.quote_depth, false));
+                        self.quote_depth += 1;
+                    }
+                    "close-quote" if self.quote_depth > 0 => {
+                        self.quote_depth -= 1;
+                        content.push_str(&quote(self.quote_depth, true));
+                    }
+                    "no-open-quote" => self.quote_depth += 1,
+                    "close-quote" | "no-close-quote" => {
+                        self.quote_depth = self.quote_depth.saturating_sub(1)
+                    }
+                    _ => return None,
+                },
+
+                _ => return None,
+            }
+        }
+
+        return Some(content);
+    }
+}
+
+/// Parses `counter-reset` and `counter-increment` values, like `item 2`.
+fn parse_counter_list(value: Option<&cssom::Value>, default: i32) -> Vec<(String, i32)> {
+    let values = match value {
+        Some(value) => value.space_separated(),
+        None => return Vec::new(),
+    };
+
+    let mut counters: Vec<(String, i32)> = Vec::new();
+
+    for value in values {
+        let number = match value {
+            cssom::Value::Ident(name) if name.eq_ignore_ascii_case("none") => return Vec::new(),
+            cssom::Value::Ident(name) => {
+                counters.push((name.clone(), default));
+                continue;
+            }
+            cssom::Value::Numeric(cssom::NumericValue::Zero) => 0,
+            // Counters saturate instead of overflowing
+            cssom::Value::Numeric(cssom::NumericValue::Number(number)) if number.fract() == 0.0 => {
+                number.clamp(i32::MIN as f64, i32::MAX as f64) as i32
+            }
+            _ => return Vec::new(),
+        };
+
+        match counters.last_mut() {
+            Some(counter) => counter.1 = number,
+            None => return Vec::new(),
+        }
+    }
+
+    return counters;
+}
+
+/// Parses the `quotes` property into pairs of open and close quotes.
+fn parse_quotes(value: Option<&cssom::Value>) -> Vec<(String, String)> {
+    let default = Vec::from([
+        ("\u{201c}".to_owned(), "\u{201d}".to_owned()),
+        ("\u{2018}".to_owned(), "\u{2019}".to_owned()),
+    ]);
+
+    let values = match value {
+        Some(cssom::Value::Ident(value)) if value == "none" => return Vec::new(),
+        Some(value) => value.space_separated(),
+        None => return default,
+    };
+
+    let strings: Vec<&String> = values
+        .iter()
+        .filter_map(|value| match value {
+            cssom::Value::String(string) => Some(string),
+            _ => None,
+        })
+        .collect();
+
+    if strings.len() == 0 || strings.len() % 2 != 0 {
+        return default;
+    }
+
+    return strings
+        .chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect();
+}
+
+struct MatchedDeclaration<'a> {
+    declaration: &'a cssom::Declaration,
+    level: u8,
+    layer: Vec<usize>,
+    specificity: cssom::Specificity,
+    /// Position of the ruleset and of the declaration within it
+    order: (usize, usize),
+}
+
+impl<'a> MatchedDeclaration<'a> {
+    pub fn new(
+        declaration: &'a cssom::Declaration,
+        ruleset: &cssom::Ruleset,
+        specificity: cssom::Specificity,
+        index: usize,
+    ) -> Self {
+        return Self {
+            declaration: declaration,
+            level: declaration.cascade_level(ruleset.origin),
+            layer: ruleset.layer_rank(declaration.important),
+            specificity: specificity,
+            order: (ruleset.order, index),
         };
     }
 }
 
+/// An element together with its position in the document tree, which is
+/// needed to match selectors with combinators.
+#[derive(Clone, Copy)]
+struct ElementContext<'a> {
+    element: &'a dom::Element,
+    states: Option<&'a dom::ElementStates>,
+    device: Option<&'a media::Device>,
+    parent: Option<&'a ElementContext<'a>>,
+    siblings: &'a [dom::Node],
+    index: usize,
+}
+
+impl<'a> ElementContext<'a> {
+    pub fn ancestors(&self) -> impl Iterator<Item = &'a ElementContext<'a>> {
+        return std::iter::successors(self.parent, |parent| parent.parent);
+    }
+
+    /// Returns the preceding sibling elements, closest first.
+    pub fn previous_siblings(&self) -> impl Iterator<Item = ElementContext<'a>> {
+        let context = *self;
+
+        return (0..self.index)
+            .rev()
+            .filter_map(move |index| context.sibling(index));
+    }
+
+    /// Returns the following sibling elements, closest first.
+    pub fn next_siblings(&self) -> impl Iterator<Item = ElementContext<'a>> {
+        let context = *self;
+
+        return (self.index + 1..self.siblings.len())
+            .filter_map(move |index| context.sibling(index));
+    }
+
+    fn sibling(&self, index: usize) -> Option<ElementContext<'a>> {
+        return self.siblings[index]
+            .element()
+            .map(|element| ElementContext {
+                element: element,
+                states: self.states,
+                device: self.device,
+                parent: self.parent,
+                siblings: self.siblings,
+                index: index,
+            });
+    }
+
+    fn is_same_type(&self, other: &ElementContext) -> bool {
+        return self.element.tag == other.element.tag;
+    }
+
+    fn has_state(&self, state: dom::ElementState) -> bool {
+        return self
+            .states
+            .map_or(false, |states| states.has(self.element, state));
+    }
+
+    /// Rulesets nested in `@media` rules only apply if there is a device
+    /// to match them against.
+    fn matches_media(&self, media: &[cssom::MediaQueryList]) -> bool {
+        return media.is_empty() || self.device.map_or(false, |device| device.matches(media));
+    }
+}
+
 #[derive(PartialEq, Eq)]
 pub enum DisplayType {
     None,
@@ -115,51 +512,177 @@ pub enum DisplayType {
     Inline,
 }
 
+/// Returns the cascaded values for an element, or for one of its
+/// pseudo-elements.
 fn declarations_for_element(
-    element: &dom::Element,
+    element: &ElementContext,
     rulesets: &cssom::Rulesets,
-) -> cssom::Declarations {
+    pseudo_element: Option<cssom::PseudoElement>,
+) -> cssom::ComputedStyle {
     let mut matches = Vec::new();
 
     // Find matching rulesets and expand rulesets with multiple,
     // comma-separated selectors
     for ruleset in rulesets {
+        if !element.matches_media(&ruleset.media) {
+            continue;
+        }
+
         for selector in &ruleset.selectors {
+            if selector.pseudo_element != pseudo_element {
+                continue;
+            }
+
             if !element_matches_selector(element, selector) {
                 continue;
             }
 
-            let matched_ruleset = MatchedRuleset::new(&selector, &ruleset.declarations);
-            matches.push(matched_ruleset);
+            for (index, declaration) in ruleset.declarations.iter().enumerate() {
+                let specificity = selector.specificity();
+                let matched = MatchedDeclaration::new(declaration, ruleset, specificity, index);
+                matches.push(matched);
+            }
         }
     }
 
-    // Sort matching rulesets by selector specificity
+    // Sort by origin and importance first, then by cascade layer, then by
+    // selector specificity and finally by source order
     matches.sort_by(|a, b| {
-        return a.selector.specificity().cmp(&b.selector.specificity());
+        return (a.level, &a.layer, a.specificity, a.order).cmp(&(
+            b.level,
+            &b.layer,
+            b.specificity,
+            b.order,
+        ));
     });
 
     // Merge declarations from all matching rulesets
-    let mut declarations = cssom::Declarations::new();
+    let mut declarations = cssom::ComputedStyle::new();
 
-    for ruleset in matches {
-        for (property, value) in ruleset.declarations.iter() {
-            declarations.insert(String::from(property), value.clone());
-        }
+    for matched in matches {
+        let declaration = matched.declaration;
+        declarations.insert(declaration.property.clone(), declaration.value.clone());
     }
 
     return declarations;
 }
 
-fn element_matches_selector(element: &dom::Element, selector: &cssom::Selector) -> bool {
+/// Matches a selector right to left: the rightmost compound selector
+/// against the element itself, everything to the left of a combinator
+/// against the related elements.
+fn element_matches_selector(element: &ElementContext, selector: &cssom::Selector) -> bool {
+    return element_matches_complex_selector(element, selector, &|_| true);
+}
+
+/// Like `element_matches_selector`, but the element matched by the
+/// leftmost compound selector also has to pass `leftmost`. This is used
+/// to anchor the relative selectors of `:has()`.
+fn element_matches_complex_selector(
+    element: &ElementContext,
+    selector: &cssom::Selector,
+    leftmost: &dyn Fn(&ElementContext) -> bool,
+) -> bool {
+    if !element_matches_compound_selector(element, selector) {
+        return false;
+    }
+
+    let (combinator, left) = match &selector.combinator {
+        Some((combinator, left)) => (combinator, left),
+        None => return leftmost(element),
+    };
+
+    let matches = |element: &ElementContext| {
+        return element_matches_complex_selector(element, left, leftmost);
+    };
+
+    return match combinator {
+        cssom::Combinator::Descendant => element.ancestors().any(|ancestor| matches(ancestor)),
+
+        cssom::Combinator::Child => element.parent.map_or(false, |parent| matches(parent)),
+
+        cssom::Combinator::NextSibling => element
+            .previous_siblings()
+            .next()
+            .map_or(false, |sibling| matches(&sibling)),
+
+        cssom::Combinator::SubsequentSibling => {
+            element.previous_siblings().any(|sibling| matches(&sibling))
+        }
+    };
+}
+
+/// Matches `:has()`: a relative selector matches if some element after the
+/// anchor in tree order matches it and is related to the anchor by the
+/// selector’s combinator.
+fn element_matches_relative_selector(
+    anchor: &ElementContext,
+    relative: &cssom::RelativeSelector,
+) -> bool {
+    let is_anchor = |element: &ElementContext| std::ptr::eq(element.element, anchor.element);
+
+    let leftmost = |element: &ElementContext| match relative.combinator {
+        cssom::Combinator::Descendant => element.ancestors().any(|ancestor| is_anchor(ancestor)),
+        cssom::Combinator::Child => element.parent.map_or(false, |parent| is_anchor(parent)),
+        cssom::Combinator::NextSibling => element
+            .previous_siblings()
+            .next()
+            .map_or(false, |sibling| is_anchor(&sibling)),
+        cssom::Combinator::SubsequentSibling => element
+            .previous_siblings()
+            .any(|sibling| is_anchor(&sibling)),
+    };
+
+    let matches = |element: &ElementContext| {
+        return element_matches_complex_selector(element, &relative.selector, &leftmost);
+    };
+
+    return match relative.combinator {
+        cssom::Combinator::Descendant | cssom::Combinator::Child => {
+            any_descendant_matches(anchor, &matches)
+        }
+        cssom::Combinator::NextSibling | cssom::Combinator::SubsequentSibling => anchor
+            .next_siblings()
+            .any(|sibling| matches(&sibling) || any_descendant_matches(&sibling, &matches)),
+    };
+}
+
+fn any_descendant_matches(
+    element: &ElementContext,
+    matches: &dyn Fn(&ElementContext) -> bool,
+) -> bool {
+    let children = &element.element.children;
+
+    for (index, child) in children.iter().enumerate() {
+        if let dom::Node::Element(child) = child {
+            let context = ElementContext {
+                element: child,
+                states: element.states,
+                device: element.device,
+                parent: Some(element),
+                siblings: children,
+                index: index,
+            };
+
+            if matches(&context) || any_descendant_matches(&context, matches) {
+                return true;
+            }
+        }
+    }
+
+    return false;
+}
+
+fn element_matches_compound_selector(element: &ElementContext, selector: &cssom::Selector) -> bool {
+    let (context, element) = (element, element.element);
+
     if let Some(tag) = &selector.tag {
         if *tag != element.tag {
             return false;
         }
     }
 
-    if let (Some(selector_id), Some(attr_id)) = (&selector.id, element.attrs.get("id")) {
-        if selector_id != attr_id {
+    if let Some(id) = &selector.id {
+        if element.attrs.get("id") != Some(id) {
             return false;
         }
     }
@@ -170,19 +693,136 @@ fn element_matches_selector(element: &dom::Element, selector: &cssom::Selector)
         }
     }
 
-    for (name, value) in &selector.attrs {
-        if let Some(element_attr) = element.attrs.get(name) {
-            if element_attr == value {
-                continue;
-            }
+    for attr in &selector.attrs {
+        if !element_matches_attribute_selector(element, attr) {
+            return false;
         }
+    }
 
-        return false;
+    for pseudo_class in &selector.pseudo_classes {
+        if !element_matches_pseudo_class(context, pseudo_class) {
+            return false;
+        }
     }
 
     return true;
 }
 
+fn element_matches_pseudo_class(
+    element: &ElementContext,
+    pseudo_class: &cssom::PseudoClass,
+) -> bool {
+    // 1-based positions among the element’s siblings that pass a filter
+    let position = |filter: &dyn Fn(&ElementContext) -> bool| {
+        return element
+            .previous_siblings()
+            .filter(|sibling| filter(sibling))
+            .count()
+            + 1;
+    };
+
+    let position_from_end = |filter: &dyn Fn(&ElementContext) -> bool| {
+        return element
+            .next_siblings()
+            .filter(|sibling| filter(sibling))
+            .count()
+            + 1;
+    };
+
+    let any = |_: &ElementContext| true;
+    let same_type = |sibling: &ElementContext| sibling.is_same_type(element);
+
+    let matches_any = |element: &ElementContext, selectors: &cssom::Selectors| {
+        return selectors
+            .iter()
+            .any(|selector| element_matches_selector(element, selector));
+    };
+
+    // Only siblings matching S count for `:nth-child(An+B of S)`
+    let matches_of = |sibling: &ElementContext, of: &Option<cssom::Selectors>| match of {
+        Some(selectors) => matches_any(sibling, selectors),
+        None => true,
+    };
+
+    return match pseudo_class {
+        cssom::PseudoClass::Root => element.parent.is_none(),
+        cssom::PseudoClass::Empty => element.element.children.iter().all(|child| match child {
+            dom::Node::Text(text) => text.is_empty(),
+            dom::Node::Element(_) => false,
+        }),
+
+        cssom::PseudoClass::FirstChild => position(&any) == 1,
+        cssom::PseudoClass::LastChild => position_from_end(&any) == 1,
+        cssom::PseudoClass::OnlyChild => position(&any) == 1 && position_from_end(&any) == 1,
+
+        cssom::PseudoClass::FirstOfType => position(&same_type) == 1,
+        cssom::PseudoClass::LastOfType => position_from_end(&same_type) == 1,
+        cssom::PseudoClass::OnlyOfType => {
+            position(&same_type) == 1 && position_from_end(&same_type) == 1
+        }
+
+        cssom::PseudoClass::NthChild(nth, of) => {
+            let filter = |sibling: &ElementContext| matches_of(sibling, of);
+            filter(element) && nth.matches(position(&filter))
+        }
+        cssom::PseudoClass::NthLastChild(nth, of) => {
+            let filter = |sibling: &ElementContext| matches_of(sibling, of);
+            filter(element) && nth.matches(position_from_end(&filter))
+        }
+
+        cssom::PseudoClass::NthOfType(nth) => nth.matches(position(&same_type)),
+        cssom::PseudoClass::NthLastOfType(nth) => nth.matches(position_from_end(&same_type)),
+
+        cssom::PseudoClass::Not(selectors) => !matches_any(element, selectors),
+        cssom::PseudoClass::Is(selectors) | cssom::PseudoClass::Where(selectors) => {
+            matches_any(element, selectors)
+        }
+        cssom::PseudoClass::Has(relative_selectors) => relative_selectors
+            .iter()
+            .any(|relative| element_matches_relative_selector(element, relative)),
+
+        cssom::PseudoClass::Hover => element.has_state(dom::ElementState::Hover),
+        cssom::PseudoClass::Active => element.has_state(dom::ElementState::Active),
+        cssom::PseudoClass::Focus => element.has_state(dom::ElementState::Focus),
+        cssom::PseudoClass::FocusVisible => element.has_state(dom::ElementState::FocusVisible),
+    };
+}
+
+fn element_matches_attribute_selector(
+    element: &dom::Element,
+    selector: &cssom::AttributeSelector,
+) -> bool {
+    let value = match element.attrs.get(&selector.name) {
+        Some(value) => value,
+        None => return false,
+    };
+
+    let (value, expected) = match selector.case_sensitivity {
+        cssom::CaseSensitivity::Insensitive => (
+            value.to_ascii_lowercase(),
+            selector.value.to_ascii_lowercase(),
+        ),
+        _ => (value.clone(), selector.value.clone()),
+    };
+
+    return match selector.operator {
+        cssom::AttributeOperator::Exists => true,
+        cssom::AttributeOperator::Equals => value == expected,
+        cssom::AttributeOperator::Includes => {
+            // Whitespace can never match, as the value is split by it
+            !expected.is_empty()
+                && !expected.contains(char::is_whitespace)
+                && value.split_whitespace().any(|word| word == expected)
+        }
+        cssom::AttributeOperator::DashMatch => {
+            value == expected || value.starts_with(&format!("{}-", expected))
+        }
+        cssom::AttributeOperator::Prefix => !expected.is_empty() && value.starts_with(&expected),
+        cssom::AttributeOperator::Suffix => !expected.is_empty() && value.ends_with(&expected),
+        cssom::AttributeOperator::Substring => !expected.is_empty() && value.contains(&expected),
+    };
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,6 +830,20 @@ mod tests {
     use crate::cssom;
     use crate::dom::{Element, Node};
     use crate::html;
+    use crate::layout;
+
+    /// Creates the context for an element without parent, siblings,
+    /// dynamic state or device.
+    fn element_context(element: &dom::Element) -> ElementContext<'_> {
+        return ElementContext {
+            element: element,
+            states: None,
+            device: None,
+            parent: None,
+            siblings: &[],
+            index: 0,
+        };
+    }
 
     #[test]
     fn test_render_node_from() {
@@ -200,7 +854,7 @@ mod tests {
 
         assert!(h1.node.element().unwrap().tag == "h1");
         assert!(h1.declarations.len() == 2);
-        assert!(h1.declarations["font-family"] == cssom::Value::String("sans-serif".to_owned()));
+        assert!(h1.declarations["font-family"] == cssom::Value::Ident("sans-serif".to_owned()));
         assert!(h1.declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 0)));
 
         let nodes = html::Parser::parse("<p>Hello World!</p>");
@@ -208,10 +862,10 @@ mod tests {
 
         assert!(p.node.element().unwrap().tag == "p");
         assert!(p.declarations.len() == 3);
-        assert!(p.declarations["font-family"] == cssom::Value::String("sans-serif".to_owned()));
+        assert!(p.declarations["font-family"] == cssom::Value::Ident("sans-serif".to_owned()));
         assert!(p.declarations["color"] == cssom::Value::Color(cssom::Color::new(255, 0, 0)));
         assert!(
-            p.declarations["line-height"] == cssom::Value::Numeric(cssom::NumericValue::Px(20))
+            p.declarations["line-height"] == cssom::Value::Numeric(cssom::NumericValue::Px(20.0))
         );
     }
 
@@ -224,7 +878,10 @@ mod tests {
         let p = &div.children[0];
 
         assert!(div.node.element().unwrap().tag == "div");
-        assert!(div.declarations.len() == 1);
+        assert!(
+            div.declarations["background-color"]
+                == cssom::Value::Color(cssom::Color::new(255, 0, 0))
+        );
 
         assert!(p.node.element().unwrap().tag == "p");
         assert!(p.declarations.len() == 1);
@@ -234,7 +891,7 @@ mod tests {
     fn test_declarations_for_element() {
         let element = &dom::Element::new("p");
         let rulesets = &css::Parser::parse("h1 { color: red; } p { color: #000; }");
-        let declarations = declarations_for_element(element, rulesets);
+        let declarations = declarations_for_element(&element_context(element), rulesets, None);
 
         assert!(declarations.len() == 1);
         assert!(declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 0)));
@@ -245,20 +902,122 @@ mod tests {
         let element = &dom::Element::new("p").attr("id", "foo");
         let css = "p#foo { color: green; } #foo { color: red; } p { color: pink; }";
         let rulesets = &css::Parser::parse(css);
-        let declarations = declarations_for_element(element, rulesets);
+        let declarations = declarations_for_element(&element_context(element), rulesets, None);
 
         assert!(declarations.len() == 1);
-        assert!(declarations["color"] == cssom::Value::String("green".to_owned()));
+        assert!(declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 128, 0)));
+    }
+
+    #[test]
+    fn test_declarations_for_element_origins_and_importance() {
+        let element = &dom::Element::new("p").attr("id", "foo");
+
+        let mut stylesheet = css::Parser::parse_with_layers(
+            "p { color: black; display: block !important; margin-top: 1px; }",
+        )
+        .origin(cssom::Origin::UserAgent);
+        stylesheet.append(
+            css::Parser::parse_with_layers(
+                "p { color: blue !important; width: 1px !important; margin-top: 2px; }",
+            )
+            .origin(cssom::Origin::User),
+        );
+        stylesheet.append(css::Parser::parse_with_layers(
+            "
+            p { width: 2px !important; height: 2px !important; }
+            #foo { color: red; display: inline !important; height: 1px; }
+        ",
+        ));
+
+        let declarations =
+            declarations_for_element(&element_context(element), &stylesheet.rulesets, None);
+        let value = |property: &str| match &declarations[property] {
+            cssom::Value::Ident(value) => value.clone(),
+            cssom::Value::Numeric(cssom::NumericValue::Px(value)) => value.to_string(),
+            cssom::Value::Color(color) => format!("#{:06x}", color.as_u32()),
+            _ => unreachable!(),
+        };
+
+        // Author styles win over user and user-agent styles...
+        assert!(value("margin-top") == "2");
+        assert!(value("height") == "2");
+
+        // ...unless the others are important
+        assert!(value("color") == "#0000ff");
+        assert!(value("width") == "1");
+        assert!(value("display") == "block");
+    }
+
+    #[test]
+    fn test_declarations_for_element_layers() {
+        let element = &dom::Element::new("p").attr("id", "foo");
+        let mut stylesheet = css::Parser::parse_with_layers(
+            "
+            @layer reset, components;
+            @layer components {
+                p { color: #000001; width: 1px !important; }
+                @layer buttons { #foo { color: #000002; height: 2px !important; } }
+            }
+            @layer reset {
+                #foo { color: #000003; margin-top: 3px; width: 3px !important; }
+            }
+            p { margin-top: 4px; height: 4px !important; }
+        ",
+        );
+        stylesheet.append(css::Parser::parse_with_layers(
+            "@layer base { p { margin-left: 5px; } } @layer reset { p { margin-left: 6px; } }",
+        ));
+
+        let declarations =
+            declarations_for_element(&element_context(element), &stylesheet.rulesets, None);
+        let px = |value: f64| cssom::Value::Numeric(cssom::NumericValue::Px(value));
+
+        // Later layers win over specificity, rulesets outside of layers
+        // over all layers, and rulesets directly in a layer over those in
+        // its sublayers
+        assert!(declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 1)));
+        assert!(declarations["margin-top"] == px(4.0));
+
+        // Important declarations reverse the order
+        assert!(declarations["width"] == px(3.0));
+        assert!(declarations["height"] == px(2.0));
+
+        // Layers keep their position across stylesheets
+        assert!(declarations["margin-left"] == px(5.0));
+    }
+
+    #[test]
+    fn test_declarations_for_element_source_order() {
+        let element = &dom::Element::new("p");
+
+        let mut stylesheet = css::Parser::parse_with_layers(
+            "p { color: red; width: 1px; } p { color: green; color: blue; height: 1px; }",
+        );
+        stylesheet.append(css::Parser::parse_with_layers(
+            "p { height: 2px; width: 2px !important; width: 3px; }",
+        ));
+
+        // Later stylesheets can’t win by being parsed first
+        stylesheet.rulesets.reverse();
+
+        let declarations =
+            declarations_for_element(&element_context(element), &stylesheet.rulesets, None);
+
+        assert!(declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 255)));
+        assert!(declarations["height"] == cssom::Value::Numeric(cssom::NumericValue::Px(2.0)));
+        assert!(declarations["width"] == cssom::Value::Numeric(cssom::NumericValue::Px(2.0)));
     }
 
     #[test]
     fn test_declarations_for_element_multiple_selectors() {
-        let element = &dom::Element::new("p").attr("class", "foo");
+        let element = &dom::Element::new("p")
+            .attr("class", "foo")
+            .attr("id", "bar");
         let rulesets = &css::Parser::parse(".foo { color: green; } p, #bar { color: red; }");
-        let declarations = declarations_for_element(element, rulesets);
+        let declarations = declarations_for_element(&element_context(element), rulesets, None);
 
         assert!(declarations.len() == 1);
-        assert!(declarations["color"] == cssom::Value::String("red".to_owned()));
+        assert!(declarations["color"] == cssom::Value::Color(cssom::Color::new(255, 0, 0)));
     }
 
     #[test]
@@ -267,8 +1026,8 @@ mod tests {
         let p = &dom::Element::new("p");
         let div = &dom::Element::new("div");
 
-        assert!(element_matches_selector(p, selector) == true);
-        assert!(element_matches_selector(div, selector) == false);
+        assert!(element_matches_selector(&element_context(p), selector) == true);
+        assert!(element_matches_selector(&element_context(div), selector) == false);
     }
 
     #[test]
@@ -276,10 +1035,10 @@ mod tests {
         let selector = &cssom::Selector::new().class("foo").class("bar");
 
         let element = &dom::Element::new("div").attr("class", "foo bar baz");
-        assert!(element_matches_selector(element, selector) == true);
+        assert!(element_matches_selector(&element_context(element), selector) == true);
 
         let element = &dom::Element::new("div").attr("class", "foo baz");
-        assert!(element_matches_selector(element, selector) == false);
+        assert!(element_matches_selector(&element_context(element), selector) == false);
     }
 
     #[test]
@@ -287,10 +1046,10 @@ mod tests {
         let selector = &cssom::Selector::new().id("foo");
 
         let element = &dom::Element::new("div").attr("id", "foo");
-        assert!(element_matches_selector(element, selector) == true);
+        assert!(element_matches_selector(&element_context(element), selector) == true);
 
         let element = &dom::Element::new("div").attr("id", "bar");
-        assert!(element_matches_selector(element, selector) == false);
+        assert!(element_matches_selector(&element_context(element), selector) == false);
     }
 
     #[test]
@@ -302,13 +1061,450 @@ mod tests {
             .attr("bar", "baz")
             .attr("lorem", "ipsum");
 
-        assert!(element_matches_selector(element, selector) == true);
+        assert!(element_matches_selector(&element_context(element), selector) == true);
 
         let element = &dom::Element::new("div")
             .attr("bar", "baz")
             .attr("lorem", "ipsum");
 
-        assert!(element_matches_selector(element, selector) == false);
+        assert!(element_matches_selector(&element_context(element), selector) == false);
+    }
+
+    #[test]
+    fn test_element_matches_selector_id_missing() {
+        let selector = &cssom::Selector::new().id("foo");
+        let element = &dom::Element::new("div");
+
+        assert!(element_matches_selector(&element_context(element), selector) == false);
+    }
+
+    #[test]
+    fn test_element_matches_selector_attr_operators() {
+        let element = &dom::Element::new("a")
+            .attr("href", "https://example.org/file.PDF")
+            .attr("lang", "en-US")
+            .attr("rel", "noopener external");
+        let context = &element_context(element);
+
+        let matches = |css: &str| {
+            let rulesets = css::Parser::parse(&format!("{} {{}}", css));
+
+            return element_matches_selector(context, &rulesets[0].selectors[0]);
+        };
+
+        assert!(matches("*") == true);
+        assert!(matches("*[href]") == true);
+        assert!(matches("[title]") == false);
+        assert!(matches("[lang=en-US]") == true);
+        assert!(matches("[lang=en]") == false);
+        assert!(matches("[rel~=external]") == true);
+        assert!(matches("[rel~=\"noopener external\"]") == false);
+        assert!(matches("[rel~=\"\"]") == false);
+        assert!(matches("[lang|=en]") == true);
+        assert!(matches("[lang|=e]") == false);
+        assert!(matches("[href^=\"https://\"]") == true);
+        assert!(matches("[href^=\"\"]") == false);
+        assert!(matches("[href$=\".pdf\"]") == false);
+        assert!(matches("[href$=\".pdf\" i]") == true);
+        assert!(matches("[href$=\".PDF\" s]") == true);
+        assert!(matches("[href*=example]") == true);
+        assert!(matches("[href*=EXAMPLE]") == false);
+        assert!(matches("[href*=EXAMPLE I]") == true);
+    }
+
+    #[test]
+    fn test_element_matches_selector_structural_pseudo_classes() {
+        let nodes = html::Parser::parse(
+            "<ul><li class=\"a\"></li><p></p><li>text</li><li class=\"a\"></li><li></li></ul>",
+        );
+        let ul = nodes[0].element().unwrap();
+        let root = &element_context(ul);
+        let child = |index: usize| ElementContext {
+            element: ul.children[index].element().unwrap(),
+            states: None,
+            device: None,
+            parent: Some(root),
+            siblings: &ul.children,
+            index: index,
+        };
+
+        let matching = |css: &str| {
+            let rulesets = css::Parser::parse(&format!("{} {{}}", css));
+            let selector = &rulesets[0].selectors[0];
+
+            return (0..5)
+                .filter(|&index| element_matches_selector(&child(index), selector))
+                .collect::<Vec<_>>();
+        };
+
+        assert!(element_matches_selector(
+            root,
+            &cssom::Selector::new().pseudo_class(cssom::PseudoClass::Root)
+        ));
+        assert!(matching(":root") == []);
+        assert!(matching(":empty") == [0, 1, 3, 4]);
+        assert!(matching(":first-child") == [0]);
+        assert!(matching(":last-child") == [4]);
+        assert!(matching(":only-child") == []);
+        assert!(matching("p:only-of-type") == [1]);
+        assert!(matching("li:first-of-type") == [0]);
+        assert!(matching("li:last-of-type") == [4]);
+        assert!(matching(":nth-child(odd)") == [0, 2, 4]);
+        assert!(matching(":nth-child(2n)") == [1, 3]);
+        assert!(matching(":nth-child(-n+2)") == [0, 1]);
+        assert!(matching(":nth-last-child(2)") == [3]);
+        assert!(matching(":nth-of-type(2)") == [2]);
+        assert!(matching(":nth-last-of-type(1)") == [1, 4]);
+        assert!(matching(":nth-child(2 of .a)") == [3]);
+        assert!(matching(":nth-last-child(1 of li)") == [4]);
+        assert!(matching("p + li:nth-child(3)") == [2]);
+    }
+
+    #[test]
+    fn test_declarations_for_element_structural_specificity() {
+        let nodes = html::Parser::parse("<ul><li></li><li></li></ul>");
+        let rulesets = css::Parser::parse(
+            "li:nth-child(1 of li) { color: #000001; } li.x, li:first-child { color: #000002; }",
+        );
+
+        let ul = RenderNode::from(&nodes[0], &rulesets);
+
+        assert!(
+            ul.children[0].declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 1))
+        );
+        assert!(ul.children[1].declarations.len() == 0);
+    }
+
+    #[test]
+    fn test_element_matches_selector_combinators() {
+        let nodes = html::Parser::parse(
+            "<nav><ul><li><a></a></li></ul><h1></h1><p></p>text<p></p><p class=\"last\"></p></nav>",
+        );
+
+        let css = "
+            nav a { color: #000001; }
+            nav > a { color: #000002; }
+            ul > li > a { height: 1px; }
+            h1 + p { width: 1px; }
+            h1 ~ p { margin-top: 1px; }
+            h1 + p + p { margin-bottom: 1px; }
+            h1 ~ .last, ul + p { padding-top: 1px; }
+        ";
+
+        let rulesets = css::Parser::parse(css);
+        let nav = RenderNode::from(&nodes[0], &rulesets);
+        let a = &nav.children[0].children[0].children[0];
+        let (h1, p1, p2, p3) = (
+            &nav.children[1],
+            &nav.children[2],
+            &nav.children[4],
+            &nav.children[5],
+        );
+
+        assert!(a.declarations.len() == 2);
+        assert!(a.declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 1)));
+        assert!(a.declarations.contains_key("height"));
+
+        assert!(h1.declarations.len() == 0);
+
+        assert!(p1.declarations.len() == 2);
+        assert!(p1.declarations.contains_key("width"));
+        assert!(p1.declarations.contains_key("margin-top"));
+
+        // Text nodes between elements are ignored
+        assert!(p2.declarations.len() == 2);
+        assert!(p2.declarations.contains_key("margin-top"));
+        assert!(p2.declarations.contains_key("margin-bottom"));
+
+        assert!(p3.declarations.len() == 2);
+        assert!(p3.declarations.contains_key("margin-top"));
+        assert!(p3.declarations.contains_key("padding-top"));
+    }
+
+    #[test]
+    fn test_declarations_for_element_combinator_specificity() {
+        let nodes = html::Parser::parse("<div class=\"box\"><p></p></div>");
+        let rulesets = css::Parser::parse(".box p { color: #000001; } p { color: #000002; }");
+
+        let div = RenderNode::from(&nodes[0], &rulesets);
+        let p = &div.children[0];
+
+        assert!(p.declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 1)));
+    }
+
+    #[test]
+    fn test_element_matches_selector_logical_pseudo_classes() {
+        let nodes = html::Parser::parse(
+            "<main><section><h1></h1><p class=\"a\"></p></section><section><p><img></img></p></section><hr></hr><div></div></main>",
+        );
+
+        let css = "
+            p:not(.a) { color: #000001; }
+            :is(h1, .a) { width: 1px; }
+            section :where(section > p) { height: 1px; }
+            section:has(> h1) { margin-top: 1px; }
+            section:has(img) { margin-bottom: 1px; }
+            section:has(> img) { padding-top: 1px; }
+            section:has(+ hr, ~ div) { padding-bottom: 1px; }
+            :has(:not(h1, p, img)) { padding-left: 1px; }
+        ";
+
+        let rulesets = css::Parser::parse(css);
+        let main = RenderNode::from(&nodes[0], &rulesets);
+        let (section1, section2) = (&main.children[0], &main.children[1]);
+        let (h1, p1, p2) = (
+            &section1.children[0],
+            &section1.children[1],
+            &section2.children[0],
+        );
+
+        assert!(main.declarations.len() == 1);
+        assert!(main.declarations.contains_key("padding-left"));
+
+        assert!(section1.declarations.len() == 2);
+        assert!(section1.declarations.contains_key("margin-top"));
+        assert!(section1.declarations.contains_key("padding-bottom"));
+
+        assert!(section2.declarations.len() == 2);
+        assert!(section2.declarations.contains_key("margin-bottom"));
+        assert!(section2.declarations.contains_key("padding-bottom"));
+
+        assert!(h1.declarations.len() == 1);
+        assert!(h1.declarations.contains_key("width"));
+
+        assert!(p1.declarations.len() == 2);
+        assert!(p1.declarations.contains_key("width"));
+        assert!(p1.declarations.contains_key("height"));
+
+        assert!(p2.declarations.len() == 2);
+        assert!(p2.declarations.contains_key("color"));
+        assert!(p2.declarations.contains_key("height"));
+    }
+
+    #[test]
+    fn test_declarations_for_element_logical_specificity() {
+        let nodes = html::Parser::parse("<p id=\"x\" class=\"a\"></p>");
+        let rulesets = css::Parser::parse(
+            ":is(#x, p) { color: #000001; } .a.a { color: #000002; } :where(#x) { color: #000003; }",
+        );
+
+        let p = RenderNode::from(&nodes[0], &rulesets);
+
+        assert!(p.declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 1)));
+    }
+
+    #[test]
+    fn test_render_node_from_document_media() {
+        let document = dom::Document::new(html::Parser::parse("<div></div>"));
+        let rulesets = css::Parser::parse(
+            "
+            div { color: #000001; }
+            @media (max-width: 600px) { div { color: #000002; } }
+            @media print { div { width: 1px; } }
+        ",
+        );
+
+        let wide = media::Device::new(&layout::Dimensions::new(800, 600));
+        let narrow = media::Device::new(&layout::Dimensions::new(400, 600));
+
+        let render_node = RenderNode::from_document(&document, &rulesets, &wide).unwrap();
+        assert!(
+            render_node.declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 1))
+        );
+        assert!(render_node.declarations.get("width").is_none());
+
+        let render_node = RenderNode::from_document(&document, &rulesets, &narrow).unwrap();
+        assert!(
+            render_node.declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 2))
+        );
+
+        // Without a device, rulesets in `@media` rules never apply
+        let render_node = RenderNode::from(&document.nodes[0], &rulesets);
+        assert!(
+            render_node.declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 1))
+        );
+    }
+
+    #[test]
+    fn test_render_node_from_document_states() {
+        let document = dom::Document::new(html::Parser::parse(
+            "<div><a href=\"/\"></a><button></button></div>",
+        ));
+        let rulesets = css::Parser::parse(
+            "
+            div:hover { color: #000001; }
+            a:hover:active { color: #000002; }
+            :focus { width: 1px; }
+            :focus-visible { height: 1px; }
+        ",
+        );
+
+        let device = media::Device::new(&layout::Dimensions::new(640, 480));
+        let div = document.nodes[0].element().unwrap();
+        let (a, button) = (
+            div.children[0].element().unwrap(),
+            div.children[1].element().unwrap(),
+        );
+
+        let render_node = RenderNode::from_document(&document, &rulesets, &device).unwrap();
+        assert!(render_node.declarations.len() == 0);
+
+        document.states.set(dom::ElementState::Hover, &[div, a]);
+        document.states.set(dom::ElementState::Active, &[div, a]);
+        document.states.set(dom::ElementState::Focus, &[button]);
+
+        let render_node = RenderNode::from_document(&document, &rulesets, &device).unwrap();
+        let (a, button) = (&render_node.children[0], &render_node.children[1]);
+
+        assert!(render_node.declarations.len() == 1);
+        assert!(a.declarations["color"] == cssom::Value::Color(cssom::Color::new(0, 0, 2)));
+        assert!(button.declarations.len() == 1);
+        assert!(button.declarations.contains_key("width"));
+    }
+
+    #[test]
+    fn test_render_node_custom_properties() {
+        let nodes = html::Parser::parse("<div><p><a></a></p><span></span></div>");
+        let rulesets = css::Parser::parse(
+            "
+            div { --brand: #36c; --label: \"brand\"; }
+            p { --brand: var(--accent, red); color: var(--brand, black); }
+            p::before { content: var(--label); color: var(--brand); }
+            a, span { color: var(--brand); background-color: var(--missing); }
+        ",
+        );
+
+        let div = RenderNode::from(&nodes[0], &rulesets);
+        let p = &div.children[0];
+        let a = &p.children[1];
+        let span = &div.children[1];
+
+        let red = cssom::Value::Color(cssom::Color::new(255, 0, 0));
+        let brand = cssom::Value::Color(cssom::Color::new(0x33, 0x66, 0xcc));
+
+        assert!(p.declarations["color"] == red);
+        assert!(p.children[0].content == Some("brand".to_owned()));
+        assert!(p.children[0].declarations["color"] == red);
+        assert!(a.declarations["color"] == red);
+        assert!(a.declarations.contains_key("--label"));
+        assert!(a.declarations.contains_key("background-color") == false);
+        assert!(span.declarations["color"] == brand);
+    }
+
+    #[test]
+    fn test_render_node_nested_rules() {
+        let nodes = html::Parser::parse("<div class=\"a\"><p></p></div>");
+        let rulesets = css::Parser::parse(
+            "
+            .a::before { content: \"\"; & { color: red; } p { color: red; } }
+            .a, .b::after { & { width: 1px; } p { height: 1px; } }
+        ",
+        );
+
+        let div = RenderNode::from(&nodes[0], &rulesets);
+        let p = &div.children[1];
+
+        assert!(div.declarations.contains_key("color") == false);
+        assert!(div.declarations.contains_key("width"));
+        assert!(div.children[0].pseudo_element == Some(cssom::PseudoElement::Before));
+        assert!(div.children[0].declarations.contains_key("color") == false);
+        assert!(p.declarations.contains_key("color") == false);
+        assert!(p.declarations.contains_key("height"));
+    }
+
+    #[test]
+    fn test_render_node_generated_content() {
+        let nodes =
+            html::Parser::parse("<div><a href=\"/about\">About</a><p></p><span></span></div>");
+        let rulesets = css::Parser::parse(
+            "
+            a::before { content: \"[\" attr(href) \"] \"; }
+            a::after { content: none; }
+            p:before { content: \"\"; color: #000001; }
+            p::after, span::after { content: open-quote counter(item) close-quote; }
+            div { counter-reset: item 5; }
+            p, span { counter-increment: item; }
+        ",
+        );
+
+        let div = RenderNode::from(&nodes[0], &rulesets);
+        let (a, p, span) = (&div.children[0], &div.children[1], &div.children[2]);
+
+        assert!(div.children.len() == 3);
+
+        assert!(a.children.len() == 2);
+        assert!(a.children[0].pseudo_element == Some(cssom::PseudoElement::Before));
+        assert!(a.children[0].content == Some("[/about] ".to_owned()));
+        assert!(a.children[1].content == None);
+        assert!(a.children[1].node.text() == Some("About".to_owned()));
+
+        assert!(p.children.len() == 2);
+        assert!(p.children[0].content == Some("".to_owned()));
+        assert!(p.children[0].declarations.contains_key("color"));
+        assert!(p.declarations.contains_key("color") == false);
+        assert!(p.children[1].pseudo_element == Some(cssom::PseudoElement::After));
+        assert!(p.children[1].content == Some("\u{201c}6\u{201d}".to_owned()));
+
+        assert!(span.children[0].content == Some("\u{201c}7\u{201d}".to_owned()));
+    }
+
+    #[test]
+    fn test_render_node_generated_content_counter_scope() {
+        let nodes = html::Parser::parse("<ol><li><ol><li></li><li></li></ol></li><li></li></ol>");
+        let rulesets = css::Parser::parse(
+            "
+            ol { counter-reset: item; }
+            li { counter-increment: item; }
+            li::before { content: counter(item); }
+        ",
+        );
+
+        let ol = RenderNode::from(&nodes[0], &rulesets);
+        let content = |li: &RenderNode| li.children[0].content.clone().unwrap();
+        let (first, second) = (&ol.children[0], &ol.children[1]);
+        let nested = &first.children[1];
+
+        assert!(content(first) == "1");
+        assert!(content(&nested.children[0]) == "1");
+        assert!(content(&nested.children[1]) == "2");
+        assert!(content(second) == "2");
+    }
+
+    #[test]
+    fn test_render_node_generated_content_counter_overflow() {
+        let nodes = html::Parser::parse("<ol><li></li><li></li><li></li></ol>");
+        let rulesets = css::Parser::parse(
+            "
+            li { counter-increment: a 2000000000 b -2000000000; }
+            li::before { content: counter(a) \" \" counter(b); }
+            ol::after { counter-reset: c 1e20; counter-increment: c -1; content: counter(c); }
+        ",
+        );
+
+        let ol = RenderNode::from(&nodes[0], &rulesets);
+
+        assert!(ol.children[2].children[0].content == Some("2147483647 -2147483648".to_owned()));
+        assert!(ol.children[3].content == Some("2147483646".to_owned()));
+    }
+
+    #[test]
+    fn test_render_node_generated_content_quotes() {
+        let nodes = html::Parser::parse("<q><q></q></q>");
+        let rulesets = css::Parser::parse(
+            "
+            q { quotes: \"«\" \"»\" \"‹\" \"›\"; }
+            q::before { content: open-quote; }
+            q::after { content: close-quote; }
+        ",
+        );
+
+        let outer = RenderNode::from(&nodes[0], &rulesets);
+        let inner = &outer.children[1];
+
+        assert!(outer.children[0].content == Some("«".to_owned()));
+        assert!(inner.children[0].content == Some("‹".to_owned()));
+        assert!(inner.children[1].content == Some("›".to_owned()));
+        assert!(outer.children[2].content == Some("»".to_owned()));
     }
 
     #[test]
@@ -0,0 +1,813 @@
+use crate::tokenizer::{self, ComponentValue, ComponentValues, Token};
+use std::vec::Vec;
+
+/// Longhand properties with the component values assigned to them.
+pub type Longhands = Vec<(String, ComponentValues)>;
+
+const SIDES: [&str; 4] = ["top", "right", "bottom", "left"];
+
+const LINE_STYLES: [&str; 10] = [
+    "none", "hidden", "dotted", "dashed", "solid", "double", "groove", "ridge", "inset", "outset",
+];
+
+const LINE_WIDTHS: [&str; 3] = ["thin", "medium", "thick"];
+
+const MATH_FUNCTIONS: [&str; 4] = ["calc", "min", "max", "clamp"];
+
+const BACKGROUND_KEYWORDS: [&str; 18] = [
+    "repeat",
+    "repeat-x",
+    "repeat-y",
+    "space",
+    "round",
+    "no-repeat",
+    "scroll",
+    "fixed",
+    "local",
+    "border-box",
+    "padding-box",
+    "content-box",
+    "none",
+    "left",
+    "center",
+    "right",
+    "top",
+    "bottom",
+];
+
+const CSS_WIDE_KEYWORDS: [&str; 5] = ["inherit", "initial", "unset", "revert", "revert-layer"];
+
+pub fn is_shorthand(property: &str) -> bool {
+    return longhand_names(property).len() > 0;
+}
+
+/// Expands a shorthand declaration into its longhands. Longhands that the
+/// value leaves out are reset to their initial values. Returns `None` if
+/// the value doesn’t match the shorthand’s grammar.
+pub fn expand(property: &str, values: &[ComponentValue]) -> Option<Longhands> {
+    let components: Vec<(usize, &ComponentValue)> = values
+        .iter()
+        .enumerate()
+        .filter(|(_, value)| !value.is_whitespace())
+        .collect();
+
+    if components.is_empty() {
+        return None;
+    }
+
+    // `inherit` and friends apply to every longhand, but can’t be
+    // combined with other values
+    if components
+        .iter()
+        .any(|(_, value)| is_css_wide_keyword(value))
+    {
+        if components.len() > 1 {
+            return None;
+        }
+
+        let longhands = longhand_names(property)
+            .into_iter()
+            .map(|name| (name, Vec::from([components[0].1.clone()])))
+            .collect();
+
+        return Some(longhands);
+    }
+
+    let items: Vec<&ComponentValue> = components.iter().map(|(_, value)| *value).collect();
+
+    return match property {
+        "margin" | "padding" | "inset" | "border-width" | "border-style" | "border-color" => {
+            expand_sides(&longhand_names(property), &items)
+        }
+        "border" => expand_border(&items),
+        "background" => expand_background(&items),
+        "font" => expand_font(values, &components),
+        "list-style" => expand_list_style(&items),
+        "flex" => expand_flex(&items),
+        _ => None,
+    };
+}
+
+/// Returns the longhands of a shorthand, or nothing for other properties.
+pub fn longhand_names(property: &str) -> Vec<String> {
+    let sides = |name: &dyn Fn(&str) -> String| SIDES.iter().map(|side| name(side)).collect();
+
+    return match property {
+        "margin" | "padding" => sides(&|side| format!("{}-{}", property, side)),
+        "inset" => sides(&|side| String::from(side)),
+        "border-width" | "border-style" | "border-color" => {
+            let kind = &property["border-".len()..];
+            sides(&|side| format!("border-{}-{}", side, kind))
+        }
+        "border" => ["width", "style", "color"]
+            .iter()
+            .flat_map(|kind| longhand_names(&format!("border-{}", kind)))
+            .collect(),
+        "background" => to_strings(&[
+            "background-color",
+            "background-image",
+            "background-repeat",
+            "background-attachment",
+            "background-position",
+            "background-size",
+            "background-origin",
+            "background-clip",
+        ]),
+        "font" => to_strings(&[
+            "font-style",
+            "font-variant",
+            "font-weight",
+            "font-stretch",
+            "font-size",
+            "line-height",
+            "font-family",
+        ]),
+        "list-style" => to_strings(&["list-style-type", "list-style-position", "list-style-image"]),
+        "flex" => to_strings(&["flex-grow", "flex-shrink", "flex-basis"]),
+        _ => Vec::new(),
+    };
+}
+
+/// Assigns one to four values to the top, right, bottom and left sides.
+/// Missing sides are copied from the opposite side, or from the top.
+fn expand_sides(names: &[String], items: &[&ComponentValue]) -> Option<Longhands> {
+    let indices = match items.len() {
+        1 => [0, 0, 0, 0],
+        2 => [0, 1, 0, 1],
+        3 => [0, 1, 2, 1],
+        4 => [0, 1, 2, 3],
+        _ => return None,
+    };
+
+    let longhands = names
+        .iter()
+        .zip(indices)
+        .map(|(name, index)| (name.clone(), Vec::from([items[index].clone()])))
+        .collect();
+
+    return Some(longhands);
+}
+
+/// `border: <width> || <style> || <color>`, applied to all four sides.
+fn expand_border(items: &[&ComponentValue]) -> Option<Longhands> {
+    let (mut width, mut style, mut color) = (None, None, None);
+
+    for &item in items {
+        let slot = if is_line_style(item) {
+            &mut style
+        } else if is_line_width(item) {
+            &mut width
+        } else {
+            &mut color
+        };
+
+        if slot.is_some() {
+            return None;
+        }
+
+        *slot = Some(Vec::from([item.clone()]));
+    }
+
+    let width = width.unwrap_or(initial("medium"));
+    let style = style.unwrap_or(initial("none"));
+    let color = color.unwrap_or(initial("currentcolor"));
+
+    let mut longhands = Longhands::new();
+
+    for (kind, value) in [("width", width), ("style", style), ("color", color)] {
+        for side in SIDES {
+            longhands.push((format!("border-{}-{}", side, kind), value.clone()));
+        }
+    }
+
+    return Some(longhands);
+}
+
+/// `background` with a single layer. Multiple, comma-separated layers
+/// aren’t supported.
+fn expand_background(items: &[&ComponentValue]) -> Option<Longhands> {
+    let mut color = None;
+    let mut image = None;
+    let mut repeat = None;
+    let mut attachment = None;
+    let mut position = None;
+    let mut size = None;
+    let mut boxes: Vec<&ComponentValue> = Vec::new();
+
+    let mut index = 0;
+
+    while index < items.len() {
+        let item = items[index];
+        let ident = item.ident().map(|ident| ident.to_ascii_lowercase());
+
+        match ident.as_deref() {
+            Some("repeat-x" | "repeat-y") if repeat.is_none() => {
+                repeat = Some(Vec::from([item.clone()]));
+            }
+
+            Some("repeat" | "space" | "round" | "no-repeat") if repeat.is_none() => {
+                // One or two keywords, for both axes or each axis
+                let count = items[index..]
+                    .iter()
+                    .take(2)
+                    .take_while(|item| {
+                        let ident = item.ident().unwrap_or("").to_ascii_lowercase();
+                        return ["repeat", "space", "round", "no-repeat"].contains(&ident.as_ref());
+                    })
+                    .count();
+
+                repeat = Some(join(&items[index..index + count]));
+                index += count - 1;
+            }
+
+            Some("scroll" | "fixed" | "local") if attachment.is_none() => {
+                attachment = Some(Vec::from([item.clone()]));
+            }
+
+            Some("border-box" | "padding-box" | "content-box") if boxes.len() < 2 => {
+                boxes.push(item);
+            }
+
+            Some("none") if image.is_none() => {
+                image = Some(Vec::from([item.clone()]));
+            }
+
+            _ if is_image(item) && image.is_none() => {
+                image = Some(Vec::from([item.clone()]));
+            }
+
+            _ if is_position(item) && position.is_none() => {
+                let count = items[index..]
+                    .iter()
+                    .take(4)
+                    .take_while(|item| is_position(item))
+                    .count();
+
+                position = Some(join(&items[index..index + count]));
+                index += count;
+
+                // The size follows the position, separated by a slash
+                if index < items.len() && items[index].is_delim('/') {
+                    let count = items[index + 1..]
+                        .iter()
+                        .take(2)
+                        .take_while(|item| is_size(item))
+                        .count();
+
+                    if count == 0 {
+                        return None;
+                    }
+
+                    size = Some(join(&items[index + 1..index + 1 + count]));
+                    index += count + 1;
+                }
+
+                continue;
+            }
+
+            _ if is_color(item) && !is_ident_in(item, &BACKGROUND_KEYWORDS) && color.is_none() => {
+                color = Some(Vec::from([item.clone()]));
+            }
+
+            _ => return None,
+        }
+
+        index += 1;
+    }
+
+    // A single box sets both the origin and the clip
+    let origin = boxes.first().map(|item| Vec::from([(*item).clone()]));
+    let clip = boxes.last().map(|item| Vec::from([(*item).clone()]));
+
+    return Some(Vec::from([
+        property("background-color", color, "transparent"),
+        property("background-image", image, "none"),
+        property("background-repeat", repeat, "repeat"),
+        property("background-attachment", attachment, "scroll"),
+        property("background-position", position, "0% 0%"),
+        property("background-size", size, "auto"),
+        property("background-origin", origin, "padding-box"),
+        property("background-clip", clip, "border-box"),
+    ]));
+}
+
+/// `font: [<style> || <variant> || <weight> || <stretch>]? <size>
+/// [/ <line-height>]? <family>`
+fn expand_font(
+    values: &[ComponentValue],
+    components: &[(usize, &ComponentValue)],
+) -> Option<Longhands> {
+    let (mut style, mut variant, mut weight, mut stretch) = (None, None, None, None);
+    let mut index = 0;
+
+    // Up to four keywords before the size, `normal` leaves one unset
+    while index < components.len() && index < 4 && !is_font_size(components[index].1) {
+        let item = components[index].1;
+        let ident = item.ident().map(|ident| ident.to_ascii_lowercase());
+
+        let slot = match ident.as_deref() {
+            Some("normal") => None,
+            Some("italic" | "oblique") => Some(&mut style),
+            Some("small-caps") => Some(&mut variant),
+            Some("bold" | "bolder" | "lighter") => Some(&mut weight),
+            Some(
+                "ultra-condensed" | "extra-condensed" | "condensed" | "semi-condensed"
+                | "semi-expanded" | "expanded" | "extra-expanded" | "ultra-expanded",
+            ) => Some(&mut stretch),
+            None if is_font_weight_number(item) => Some(&mut weight),
+            _ => return None,
+        };
+
+        if let Some(slot) = slot {
+            if slot.is_some() {
+                return None;
+            }
+
+            *slot = Some(Vec::from([item.clone()]));
+        }
+
+        index += 1;
+    }
+
+    let size = components
+        .get(index)
+        .filter(|(_, item)| is_font_size(item))?
+        .1;
+    index += 1;
+
+    let mut line_height = None;
+
+    if components
+        .get(index)
+        .map_or(false, |(_, item)| item.is_delim('/'))
+    {
+        line_height = Some(Vec::from([components.get(index + 1)?.1.clone()]));
+        index += 2;
+    }
+
+    // The family is everything after the size, including whitespace
+    // between unquoted names
+    let family_start = components.get(index)?.0;
+    let family_end = components.last().unwrap().0 + 1;
+    let family = values[family_start..family_end].to_vec();
+
+    return Some(Vec::from([
+        property("font-style", style, "normal"),
+        property("font-variant", variant, "normal"),
+        property("font-weight", weight, "normal"),
+        property("font-stretch", stretch, "normal"),
+        (String::from("font-size"), Vec::from([size.clone()])),
+        property("line-height", line_height, "normal"),
+        (String::from("font-family"), family),
+    ]));
+}
+
+/// `list-style: <type> || <position> || <image>`. A single `none` sets
+/// whichever of the type and image is left unset.
+fn expand_list_style(items: &[&ComponentValue]) -> Option<Longhands> {
+    let (mut list_type, mut position, mut image) = (None, None, None);
+    let mut nones = 0;
+
+    for &item in items {
+        let ident = item.ident().map(|ident| ident.to_ascii_lowercase());
+
+        let slot = match ident.as_deref() {
+            Some("none") => {
+                nones += 1;
+                continue;
+            }
+            Some("inside" | "outside") => &mut position,
+            Some(_) => &mut list_type,
+            None if matches!(item, ComponentValue::Token(Token::String(_))) => &mut list_type,
+            None if is_image(item) => &mut image,
+            None => return None,
+        };
+
+        if slot.is_some() {
+            return None;
+        }
+
+        *slot = Some(Vec::from([item.clone()]));
+    }
+
+    let none = || Some(initial("none"));
+
+    match (nones, list_type.is_some(), image.is_some()) {
+        (0, _, _) => (),
+        (1, false, _) | (2, false, false) => {
+            list_type = none();
+            image = image.or(none());
+        }
+        (1, true, false) => image = none(),
+        _ => return None,
+    }
+
+    return Some(Vec::from([
+        property("list-style-type", list_type, "disc"),
+        property("list-style-position", position, "outside"),
+        property("list-style-image", image, "none"),
+    ]));
+}
+
+/// `flex: none | <grow> <shrink>? || <basis>`
+fn expand_flex(items: &[&ComponentValue]) -> Option<Longhands> {
+    let longhands = |grow: &str, shrink: &str, basis: ComponentValues| {
+        return Vec::from([
+            (String::from("flex-grow"), initial(grow)),
+            (String::from("flex-shrink"), initial(shrink)),
+            (String::from("flex-basis"), basis),
+        ]);
+    };
+
+    if items.len() == 1 {
+        match items[0]
+            .ident()
+            .map(|ident| ident.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("none") => return Some(longhands("0", "0", initial("auto"))),
+            Some("auto") => return Some(longhands("1", "1", initial("auto"))),
+            _ => (),
+        }
+    }
+
+    let (mut grow, mut shrink, mut basis) = (None, None, None);
+    let mut after_grow = false;
+
+    for &item in items {
+        let is_number = matches!(item, ComponentValue::Token(Token::Number(_)));
+
+        // Grow and shrink have to be next to each other
+        if is_number && grow.is_none() {
+            grow = Some(Vec::from([item.clone()]));
+            after_grow = true;
+            continue;
+        }
+
+        if is_number && shrink.is_none() && after_grow {
+            shrink = Some(Vec::from([item.clone()]));
+            after_grow = false;
+            continue;
+        }
+
+        after_grow = false;
+
+        if basis.is_none() && is_flex_basis(item) {
+            basis = Some(Vec::from([item.clone()]));
+            continue;
+        }
+
+        return None;
+    }
+
+    // A basis is implied to be 0% if only numbers are given
+    let basis = match (basis, grow.is_some()) {
+        (Some(basis), _) => basis,
+        (None, true) => initial("0%"),
+        (None, false) => return None,
+    };
+
+    return Some(Vec::from([
+        property("flex-grow", grow, "1"),
+        property("flex-shrink", shrink, "1"),
+        (String::from("flex-basis"), basis),
+    ]));
+}
+
+fn property(
+    name: &str,
+    value: Option<ComponentValues>,
+    initial_value: &str,
+) -> (String, ComponentValues) {
+    return (String::from(name), value.unwrap_or(initial(initial_value)));
+}
+
+fn initial(value: &str) -> ComponentValues {
+    return tokenizer::parse_component_values(value);
+}
+
+fn to_strings(names: &[&str]) -> Vec<String> {
+    return names.iter().map(|name| String::from(*name)).collect();
+}
+
+/// Joins values with whitespace, like `left top`.
+fn join(items: &[&ComponentValue]) -> ComponentValues {
+    let mut values = ComponentValues::new();
+
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            values.push(ComponentValue::Token(Token::Whitespace));
+        }
+
+        values.push((*item).clone());
+    }
+
+    return values;
+}
+
+fn is_ident_in(value: &ComponentValue, idents: &[&str]) -> bool {
+    return value.ident().map_or(false, |ident| {
+        return idents.iter().any(|other| ident.eq_ignore_ascii_case(other));
+    });
+}
+
+fn is_function_in(value: &ComponentValue, names: &[&str]) -> bool {
+    return match value {
+        ComponentValue::Function(name, _) => {
+            names.iter().any(|other| name.eq_ignore_ascii_case(other))
+        }
+        _ => false,
+    };
+}
+
+fn is_css_wide_keyword(value: &ComponentValue) -> bool {
+    return is_ident_in(value, &CSS_WIDE_KEYWORDS);
+}
+
+/// Lengths, percentages and math functions.
+fn is_numeric(value: &ComponentValue) -> bool {
+    return match value {
+        ComponentValue::Token(Token::Number(_) | Token::Percentage(_) | Token::Dimension(..)) => {
+            true
+        }
+        _ => is_function_in(value, &MATH_FUNCTIONS),
+    };
+}
+
+fn is_line_style(value: &ComponentValue) -> bool {
+    return is_ident_in(value, &LINE_STYLES);
+}
+
+fn is_line_width(value: &ComponentValue) -> bool {
+    return is_numeric(value) || is_ident_in(value, &LINE_WIDTHS);
+}
+
+fn is_image(value: &ComponentValue) -> bool {
+    return match value {
+        ComponentValue::Token(Token::Url(_)) => true,
+        ComponentValue::Function(name, _) => {
+            name.eq_ignore_ascii_case("url") || name.to_ascii_lowercase().ends_with("gradient")
+        }
+        _ => false,
+    };
+}
+
+fn is_position(value: &ComponentValue) -> bool {
+    return is_numeric(value) || is_ident_in(value, &["left", "center", "right", "top", "bottom"]);
+}
+
+fn is_size(value: &ComponentValue) -> bool {
+    return is_numeric(value) || is_ident_in(value, &["auto", "cover", "contain"]);
+}
+
+fn is_color(value: &ComponentValue) -> bool {
+    return match value {
+        ComponentValue::Token(Token::Hash(..) | Token::Ident(_)) => true,
+        ComponentValue::Function(..) => !is_function_in(value, &MATH_FUNCTIONS),
+        _ => false,
+    };
+}
+
+/// Unitless numbers are only valid as a basis if they are zero.
+fn is_flex_basis(value: &ComponentValue) -> bool {
+    return match value {
+        ComponentValue::Token(Token::Number(number)) => number.value == 0.0,
+        _ => is_size(value) || is_ident_in(value, &["content"]),
+    };
+}
+
+fn is_font_size(value: &ComponentValue) -> bool {
+    let keywords = [
+        "xx-small",
+        "x-small",
+        "small",
+        "medium",
+        "large",
+        "x-large",
+        "xx-large",
+        "xxx-large",
+        "larger",
+        "smaller",
+    ];
+
+    // Unitless numbers are font weights
+    return match value {
+        ComponentValue::Token(Token::Number(number)) => number.value == 0.0,
+        _ => is_numeric(value) || is_ident_in(value, &keywords),
+    };
+}
+
+fn is_font_weight_number(value: &ComponentValue) -> bool {
+    return match value {
+        ComponentValue::Token(Token::Number(number)) => {
+            number.value >= 1.0 && number.value <= 1000.0
+        }
+        _ => false,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_str(property: &str, value: &str) -> Option<Vec<(String, String)>> {
+        let values = tokenizer::parse_component_values(value);
+
+        return expand(property, &values).map(|longhands| {
+            return longhands
+                .iter()
+                .map(|(name, values)| (name.clone(), tokenizer::serialize(values)))
+                .collect();
+        });
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Option<Vec<(String, String)>> {
+        return Some(
+            expected
+                .iter()
+                .map(|(name, value)| (String::from(*name), String::from(*value)))
+                .collect(),
+        );
+    }
+
+    /// Expands a shorthand, keeping only the values of the longhands.
+    fn values(property: &str, value: &str) -> Option<Vec<String>> {
+        return expand_str(property, value).map(|longhands| {
+            return longhands.into_iter().map(|(_, value)| value).collect();
+        });
+    }
+
+    #[test]
+    fn test_expand_sides() {
+        let margin = |value: &str| values("margin", value);
+
+        assert!(margin("1px") == Some(Vec::from(["1px", "1px", "1px", "1px"].map(String::from))));
+        assert!(
+            margin("1px 2px") == Some(Vec::from(["1px", "2px", "1px", "2px"].map(String::from)))
+        );
+        assert!(
+            margin("1px 2px 3px")
+                == Some(Vec::from(["1px", "2px", "3px", "2px"].map(String::from)))
+        );
+        assert!(
+            margin("1px auto 3px 4%")
+                == Some(Vec::from(["1px", "auto", "3px", "4%"].map(String::from)))
+        );
+        assert!(margin("1px 2px 3px 4px 5px") == None);
+
+        assert!(
+            expand_str("border-color", "red blue")
+                == pairs(&[
+                    ("border-top-color", "red"),
+                    ("border-right-color", "blue"),
+                    ("border-bottom-color", "red"),
+                    ("border-left-color", "blue"),
+                ])
+        );
+        assert!(
+            expand_str("inset", "0")
+                == pairs(&[("top", "0"), ("right", "0"), ("bottom", "0"), ("left", "0")])
+        );
+    }
+
+    #[test]
+    fn test_expand_css_wide_keywords() {
+        assert!(
+            expand_str("flex", "inherit")
+                == pairs(&[
+                    ("flex-grow", "inherit"),
+                    ("flex-shrink", "inherit"),
+                    ("flex-basis", "inherit"),
+                ])
+        );
+        assert!(expand_str("margin", "1px inherit") == None);
+    }
+
+    #[test]
+    fn test_expand_border() {
+        let border = expand_str("border", "solid 1px #000").unwrap();
+
+        assert!(border.len() == 12);
+        assert!(border[0] == (String::from("border-top-width"), String::from("1px")));
+        assert!(border[4] == (String::from("border-top-style"), String::from("solid")));
+        assert!(border[11] == (String::from("border-left-color"), String::from("#000")));
+
+        let border = expand_str("border", "thick").unwrap();
+
+        assert!(border[3] == (String::from("border-left-width"), String::from("thick")));
+        assert!(border[7] == (String::from("border-left-style"), String::from("none")));
+        assert!(
+            border[8]
+                == (
+                    String::from("border-top-color"),
+                    String::from("currentcolor")
+                )
+        );
+
+        assert!(expand_str("border", "1px 2px") == None);
+        assert!(expand_str("border", "solid dashed") == None);
+    }
+
+    #[test]
+    fn test_expand_background() {
+        assert!(
+            expand_str(
+                "background",
+                "#fff url(bg.png) no-repeat fixed right 10px top / cover content-box"
+            ) == pairs(&[
+                ("background-color", "#fff"),
+                ("background-image", "url(bg.png)"),
+                ("background-repeat", "no-repeat"),
+                ("background-attachment", "fixed"),
+                ("background-position", "right 10px top"),
+                ("background-size", "cover"),
+                ("background-origin", "content-box"),
+                ("background-clip", "content-box"),
+            ])
+        );
+        assert!(
+            expand_str("background", "red")
+                == pairs(&[
+                    ("background-color", "red"),
+                    ("background-image", "none"),
+                    ("background-repeat", "repeat"),
+                    ("background-attachment", "scroll"),
+                    ("background-position", "0% 0%"),
+                    ("background-size", "auto"),
+                    ("background-origin", "padding-box"),
+                    ("background-clip", "border-box"),
+                ])
+        );
+        assert!(expand_str("background", "red blue") == None);
+        assert!(expand_str("background", "url(a.png), url(b.png)") == None);
+    }
+
+    #[test]
+    fn test_expand_font() {
+        assert!(
+            expand_str(
+                "font",
+                "italic bold 12px/20px \"Helvetica Neue\", Times New Roman, serif"
+            ) == pairs(&[
+                ("font-style", "italic"),
+                ("font-variant", "normal"),
+                ("font-weight", "bold"),
+                ("font-stretch", "normal"),
+                ("font-size", "12px"),
+                ("line-height", "20px"),
+                ("font-family", "\"Helvetica Neue\", Times New Roman, serif"),
+            ])
+        );
+
+        let font = expand_str("font", "normal 700 large serif").unwrap();
+        assert!(font[2] == (String::from("font-weight"), String::from("700")));
+        assert!(font[4] == (String::from("font-size"), String::from("large")));
+
+        assert!(expand_str("font", "12px") == None);
+        assert!(expand_str("font", "bold serif") == None);
+        assert!(expand_str("font", "bold bold 12px serif") == None);
+        assert!(expand_str("font", "12px/ serif") == None);
+    }
+
+    #[test]
+    fn test_expand_list_style() {
+        let list_style = |value: &str| values("list-style", value);
+
+        assert!(
+            list_style("square inside")
+                == Some(Vec::from(["square", "inside", "none"].map(String::from)))
+        );
+        assert!(
+            list_style("none") == Some(Vec::from(["none", "outside", "none"].map(String::from)))
+        );
+        assert!(
+            list_style("none url(dot.png)")
+                == Some(Vec::from(
+                    ["none", "outside", "url(dot.png)"].map(String::from)
+                ))
+        );
+        assert!(
+            list_style("\"-\" none")
+                == Some(Vec::from(["\"-\"", "outside", "none"].map(String::from)))
+        );
+        assert!(list_style("square circle") == None);
+        assert!(list_style("none none none") == None);
+    }
+
+    #[test]
+    fn test_expand_flex() {
+        let flex = |value: &str| values("flex", value);
+
+        assert!(flex("none") == Some(Vec::from(["0", "0", "auto"].map(String::from))));
+        assert!(flex("auto") == Some(Vec::from(["1", "1", "auto"].map(String::from))));
+        assert!(flex("2") == Some(Vec::from(["2", "1", "0%"].map(String::from))));
+        assert!(flex("2 3") == Some(Vec::from(["2", "3", "0%"].map(String::from))));
+        assert!(flex("10px") == Some(Vec::from(["1", "1", "10px"].map(String::from))));
+        assert!(flex("2 3 10px") == Some(Vec::from(["2", "3", "10px"].map(String::from))));
+        assert!(flex("10px 2") == Some(Vec::from(["2", "1", "10px"].map(String::from))));
+        assert!(flex("2 10px 3") == None);
+        assert!(flex("10px 2 3") == Some(Vec::from(["2", "3", "10px"].map(String::from))));
+        assert!(flex("2 3 0") == Some(Vec::from(["2", "3", "0"].map(String::from))));
+        assert!(flex("1 2 3") == None);
+    }
+}
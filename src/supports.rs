@@ -0,0 +1,175 @@
+use crate::cssom;
+use crate::variables;
+
+/// Evaluates an `@supports` condition against the properties and values
+/// the engine implements.
+pub fn evaluate(condition: &cssom::SupportsCondition) -> bool {
+    return match condition {
+        cssom::SupportsCondition::Declaration(Some(declarations)) => {
+            declarations.iter().all(is_supported)
+        }
+        cssom::SupportsCondition::Declaration(None) => false,
+        cssom::SupportsCondition::Selector(selector) => selector.is_some(),
+        cssom::SupportsCondition::Not(condition) => !evaluate(condition),
+        cssom::SupportsCondition::And(conditions) => conditions.iter().all(evaluate),
+        cssom::SupportsCondition::Or(conditions) => conditions.iter().any(evaluate),
+        cssom::SupportsCondition::Unknown(_) => false,
+    };
+}
+
+/// Checks whether a declaration has an effect on rendering. Values using
+/// `var()` can only be checked once they are substituted, so any property
+/// the engine implements supports them.
+pub fn is_supported(declaration: &cssom::Declaration) -> bool {
+    let property = declaration.property.as_ref();
+
+    if variables::is_custom_property(property) {
+        return true;
+    }
+
+    let supports_value = match supported_values(property) {
+        Some(supports_value) => supports_value,
+        None => return false,
+    };
+
+    return match declaration.value {
+        cssom::Value::Pending(_, _) => true,
+        ref value => supports_value(value),
+    };
+}
+
+/// Returns a check for the values the engine supports for a property, or
+/// `None` if it doesn’t implement the property at all.
+fn supported_values(property: &str) -> Option<fn(&cssom::Value) -> bool> {
+    return match property {
+        "display" => Some(|value| is_keyword(value, &["none", "block", "inline"])),
+        "position" => Some(|value| is_keyword(value, &["static", "relative"])),
+        "width" | "height" | "margin-top" | "margin-right" | "margin-bottom" | "margin-left"
+        | "top" | "right" | "bottom" | "left" => {
+            Some(|value| is_length_percentage(value) || is_keyword(value, &["auto"]))
+        }
+        "font-size" => Some(is_length_percentage),
+        "color" | "background-color" => {
+            Some(|value| matches!(value, cssom::Value::Color(_) | cssom::Value::CurrentColor))
+        }
+        "content" => Some(|value| is_keyword(value, &["normal", "none"]) || is_content_list(value)),
+        "counter-reset" | "counter-increment" => {
+            Some(|value| is_keyword(value, &["none"]) || is_counter_list(value))
+        }
+        "quotes" => Some(|value| is_keyword(value, &["none"]) || is_quote_pairs(value)),
+        _ => None,
+    };
+}
+
+fn is_keyword(value: &cssom::Value, keywords: &[&str]) -> bool {
+    return match value {
+        cssom::Value::Ident(ident) => keywords
+            .iter()
+            .any(|keyword| ident.eq_ignore_ascii_case(keyword)),
+        _ => false,
+    };
+}
+
+fn is_length_percentage(value: &cssom::Value) -> bool {
+    return match value {
+        cssom::Value::Numeric(cssom::NumericValue::Number(_)) => false,
+        cssom::Value::Numeric(cssom::NumericValue::Calc(calculation)) => {
+            calculation.resolved_type() != Some(cssom::CalculationType::Number)
+        }
+        cssom::Value::Numeric(_) => true,
+        _ => false,
+    };
+}
+
+/// Strings, `attr()`, `counter()` and quotes, as `content` generates them.
+fn is_content_list(value: &cssom::Value) -> bool {
+    return value.space_separated().iter().all(|value| match value {
+        cssom::Value::String(_) => true,
+        cssom::Value::Function(name, arguments)
+            if name.eq_ignore_ascii_case("attr") || name.eq_ignore_ascii_case("counter") =>
+        {
+            arguments.len() == 1 && arguments[0].ident().is_some()
+        }
+        value => is_keyword(
+            value,
+            &[
+                "open-quote",
+                "close-quote",
+                "no-open-quote",
+                "no-close-quote",
+            ],
+        ),
+    });
+}
+
+/// Counter names, each optionally followed by an integer.
+fn is_counter_list(value: &cssom::Value) -> bool {
+    let values = value.space_separated();
+
+    return values.iter().enumerate().all(|(index, value)| match value {
+        cssom::Value::Ident(_) => !is_keyword(value, &["none"]),
+        cssom::Value::Numeric(cssom::NumericValue::Zero) => {
+            index > 0 && values[index - 1].ident().is_some()
+        }
+        cssom::Value::Numeric(cssom::NumericValue::Number(number)) => {
+            index > 0 && number.fract() == 0.0 && values[index - 1].ident().is_some()
+        }
+        _ => false,
+    });
+}
+
+fn is_quote_pairs(value: &cssom::Value) -> bool {
+    let values = value.space_separated();
+
+    return values.len() % 2 == 0
+        && values
+            .iter()
+            .all(|value| matches!(value, cssom::Value::String(_)));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::css;
+
+    fn supports(condition: &str) -> bool {
+        let rulesets = css::Parser::parse(&format!("@supports {} {{ a {{}} }}", condition));
+
+        return rulesets.len() == 1;
+    }
+
+    #[test]
+    fn test_supports_declarations() {
+        assert!(supports("(display: block)") == true);
+        assert!(supports("(display: grid)") == false);
+        assert!(supports("(DISPLAY: Inline)") == true);
+        assert!(supports("(width: calc(100% - 2em))") == true);
+        assert!(supports("(width: 2)") == false);
+        assert!(supports("(margin: 0 auto)") == true);
+        assert!(supports("(color: hsl(120deg 100% 50% / 50%))") == true);
+        assert!(supports("(color: 10px)") == false);
+        assert!(supports("(content: open-quote counter(item) \". \")") == true);
+        assert!(supports("(counter-reset: item 2 other)") == true);
+        assert!(supports("(counter-reset: 2 item)") == false);
+        assert!(supports("(quotes: \"<\" \">\")") == true);
+        assert!(supports("(width: var(--width))") == true);
+        assert!(supports("(--anything: {})") == true);
+        assert!(supports("(float: left)") == false);
+        assert!(supports("(border-top: 1px solid red)") == false);
+        assert!(supports("(color: red; width: 1px)") == false);
+    }
+
+    #[test]
+    fn test_supports_conditions() {
+        assert!(supports("not (display: grid)") == true);
+        assert!(supports("(display: block) and (position: relative)") == true);
+        assert!(supports("(display: block) and (position: sticky)") == false);
+        assert!(supports("(display: grid) or (display: block)") == true);
+        assert!(supports("((display: grid) or (display: block)) and (color: red)") == true);
+        assert!(supports("selector(ul > li:nth-child(2n + 1))") == true);
+        assert!(supports("selector(a:unknown)") == false);
+        assert!(supports("selector(a, b)") == false);
+        assert!(supports("not selector(:has(> img))") == false);
+        assert!(supports("font-tech(color-colrv1)") == false);
+        assert!(supports("not (unknown syntax)") == true);
+    }
+}
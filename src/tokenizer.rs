@@ -0,0 +1,989 @@
+use crate::parser;
+use std::fmt;
+use std::vec::Vec;
+
+/// Tokens as defined by CSS Syntax Level 3, section 4. Comments are not
+/// tokens, the tokenizer drops them.
+#[derive(PartialEq, Clone)]
+pub enum Token {
+    Ident(String),
+    Function(String),
+    AtKeyword(String),
+    Hash(String, HashType),
+    String(String),
+    BadString,
+    Url(String),
+    BadUrl,
+    Delim(char),
+    Number(Number),
+    Percentage(Number),
+    Dimension(Number, String),
+    Whitespace,
+    CDO,
+    CDC,
+    Colon,
+    Semicolon,
+    Comma,
+    OpenSquare,
+    CloseSquare,
+    OpenParen,
+    CloseParen,
+    OpenCurly,
+    CloseCurly,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum HashType {
+    Id,
+    Unrestricted,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub struct Number {
+    pub value: f64,
+    pub integer: bool,
+    pub signed: bool,
+}
+
+impl Number {
+    pub fn new(value: f64, integer: bool, signed: bool) -> Self {
+        return Self {
+            value: value,
+            integer: integer,
+            signed: signed,
+        };
+    }
+
+    pub fn integer(value: i64) -> Self {
+        return Self::new(value as f64, true, false);
+    }
+}
+
+/// Component values as defined by CSS Syntax Level 3, section 5: tokens
+/// with functions and blocks already grouped into nested lists.
+#[derive(PartialEq, Clone)]
+pub enum ComponentValue {
+    Token(Token),
+    Function(String, ComponentValues),
+    /// A simple block, the token is the opening `(`, `[` or `{`.
+    Block(Token, ComponentValues),
+}
+
+impl ComponentValue {
+    pub fn is_whitespace(&self) -> bool {
+        return *self == ComponentValue::Token(Token::Whitespace);
+    }
+
+    pub fn is_token(&self, token: &Token) -> bool {
+        return match self {
+            ComponentValue::Token(other) => other == token,
+            _ => false,
+        };
+    }
+
+    pub fn is_delim(&self, delim: char) -> bool {
+        return self.is_token(&Token::Delim(delim));
+    }
+
+    pub fn ident(&self) -> Option<&str> {
+        return match self {
+            ComponentValue::Token(Token::Ident(ident)) => Some(ident),
+            _ => None,
+        };
+    }
+}
+
+pub type Tokens = Vec<Token>;
+pub type ComponentValues = Vec<ComponentValue>;
+
+pub struct Tokenizer {
+    parser: parser::Parser,
+}
+
+impl Tokenizer {
+    pub fn new(input: &str) -> Self {
+        return Self {
+            parser: parser::Parser::new(&preprocess(input)),
+        };
+    }
+
+    pub fn tokenize(input: &str) -> Tokens {
+        let mut tokenizer = Self::new(input);
+        let mut tokens = Tokens::new();
+
+        while let Some(token) = tokenizer.consume_token() {
+            tokens.push(token);
+        }
+
+        return tokens;
+    }
+
+    /// Returns the next token, or `None` at the end of the input.
+    pub fn consume_token(&mut self) -> Option<Token> {
+        self.consume_comments();
+
+        if self.parser.eof() {
+            return None;
+        }
+
+        let token = match self.parser.next_char() {
+            c if is_whitespace(c) => {
+                self.parser
+                    .consume_while(&|next_char| is_whitespace(next_char));
+                Token::Whitespace
+            }
+
+            '"' | '\'' => {
+                let ending = self.parser.consume_char();
+                self.consume_string(ending)
+            }
+
+            '#' => {
+                self.parser.consume_char();
+
+                if self.peek(0).map_or(false, is_ident_char)
+                    || is_valid_escape(self.peek(0), self.peek(1))
+                {
+                    let hash_type = if self.would_start_ident(0) {
+                        HashType::Id
+                    } else {
+                        HashType::Unrestricted
+                    };
+
+                    Token::Hash(self.consume_name(), hash_type)
+                } else {
+                    Token::Delim('#')
+                }
+            }
+
+            '+' | '.' if self.would_start_number(0) => self.consume_numeric(),
+
+            '-' => {
+                if self.would_start_number(0) {
+                    self.consume_numeric()
+                } else if self.parser.starts_with("-->") {
+                    self.consume_chars(3);
+                    Token::CDC
+                } else if self.would_start_ident(0) {
+                    self.consume_ident_like()
+                } else {
+                    Token::Delim(self.parser.consume_char())
+                }
+            }
+
+            '<' if self.parser.starts_with("<!--") => {
+                self.consume_chars(4);
+                Token::CDO
+            }
+
+            '@' => {
+                self.parser.consume_char();
+
+                if self.would_start_ident(0) {
+                    Token::AtKeyword(self.consume_name())
+                } else {
+                    Token::Delim('@')
+                }
+            }
+
+            '\\' if is_valid_escape(self.peek(0), self.peek(1)) => self.consume_ident_like(),
+            c if c.is_ascii_digit() => self.consume_numeric(),
+            c if is_ident_start(c) => self.consume_ident_like(),
+
+            c => {
+                self.parser.consume_char();
+
+                match c {
+                    '(' => Token::OpenParen,
+                    ')' => Token::CloseParen,
+                    '[' => Token::OpenSquare,
+                    ']' => Token::CloseSquare,
+                    '{' => Token::OpenCurly,
+                    '}' => Token::CloseCurly,
+                    ',' => Token::Comma,
+                    ':' => Token::Colon,
+                    ';' => Token::Semicolon,
+                    _ => Token::Delim(c),
+                }
+            }
+        };
+
+        return Some(token);
+    }
+
+    fn consume_comments(&mut self) {
+        while self.parser.starts_with("/*") {
+            self.consume_chars(2);
+
+            while !self.parser.eof() && !self.parser.starts_with("*/") {
+                self.parser.consume_char();
+            }
+
+            // Unterminated comments run until the end of the input
+            if !self.parser.eof() {
+                self.consume_chars(2);
+            }
+        }
+    }
+
+    fn consume_string(&mut self, ending: char) -> Token {
+        let mut value = String::new();
+
+        while !self.parser.eof() {
+            match self.parser.next_char() {
+                c if c == ending => {
+                    self.parser.consume_char();
+                    break;
+                }
+
+                // Unescaped newlines end the string, but aren’t part of it
+                '\n' => return Token::BadString,
+
+                '\\' => {
+                    self.parser.consume_char();
+
+                    match self.peek(0) {
+                        None => (),
+                        Some('\n') => {
+                            self.parser.consume_char();
+                        }
+                        Some(_) => value.push(self.consume_escaped()),
+                    }
+                }
+
+                _ => value.push(self.parser.consume_char()),
+            }
+        }
+
+        return Token::String(value);
+    }
+
+    fn consume_numeric(&mut self) -> Token {
+        let number = self.consume_number();
+
+        if self.would_start_ident(0) {
+            return Token::Dimension(number, self.consume_name());
+        }
+
+        if self.peek(0) == Some('%') {
+            self.parser.consume_char();
+            return Token::Percentage(number);
+        }
+
+        return Token::Number(number);
+    }
+
+    fn consume_number(&mut self) -> Number {
+        let mut repr = String::new();
+        let mut integer = true;
+        let mut signed = false;
+
+        if let Some('+') | Some('-') = self.peek(0) {
+            signed = true;
+            repr.push(self.parser.consume_char());
+        }
+
+        repr.push_str(&self.consume_digits());
+
+        if self.peek(0) == Some('.') && self.peek(1).map_or(false, |c| c.is_ascii_digit()) {
+            integer = false;
+            repr.push(self.parser.consume_char());
+            repr.push_str(&self.consume_digits());
+        }
+
+        if let Some('e') | Some('E') = self.peek(0) {
+            let exponent = match (self.peek(1), self.peek(2)) {
+                (Some('+') | Some('-'), Some(c)) if c.is_ascii_digit() => 3,
+                (Some(c), _) if c.is_ascii_digit() => 2,
+                _ => 0,
+            };
+
+            if exponent > 0 {
+                integer = false;
+
+                for _ in 1..exponent {
+                    repr.push(self.parser.consume_char());
+                }
+
+                repr.push_str(&self.consume_digits());
+            }
+        }
+
+        // Numbers too large for a double are clamped to the largest finite
+        // one, so they stay usable in comparisons and layout.
+        let value = repr
+            .parse::<f64>()
+            .unwrap_or(0.0)
+            .clamp(-f64::MAX, f64::MAX);
+
+        return Number::new(value, integer, signed);
+    }
+
+    fn consume_digits(&mut self) -> String {
+        return self
+            .parser
+            .consume_while(&|next_char| next_char.is_ascii_digit());
+    }
+
+    fn consume_ident_like(&mut self) -> Token {
+        let name = self.consume_name();
+
+        if self.peek(0) != Some('(') {
+            return Token::Ident(name);
+        }
+
+        self.parser.consume_char();
+
+        if !name.eq_ignore_ascii_case("url") {
+            return Token::Function(name);
+        }
+
+        // Leave a single whitespace token in front of quoted urls
+        while self.peek(0).map_or(false, is_whitespace) && self.peek(1).map_or(false, is_whitespace)
+        {
+            self.parser.consume_char();
+        }
+
+        let quoted = match (self.peek(0), self.peek(1)) {
+            (Some('"' | '\''), _) => true,
+            (Some(c), Some('"' | '\'')) if is_whitespace(c) => true,
+            _ => false,
+        };
+
+        if quoted {
+            return Token::Function(name);
+        }
+
+        return self.consume_url();
+    }
+
+    fn consume_url(&mut self) -> Token {
+        let mut value = String::new();
+
+        self.parser
+            .consume_while(&|next_char| is_whitespace(next_char));
+
+        while !self.parser.eof() {
+            match self.parser.next_char() {
+                ')' => {
+                    self.parser.consume_char();
+                    return Token::Url(value);
+                }
+
+                c if is_whitespace(c) => {
+                    self.parser
+                        .consume_while(&|next_char| is_whitespace(next_char));
+
+                    if self.parser.eof() || self.parser.next_char() == ')' {
+                        continue;
+                    }
+
+                    self.consume_bad_url_remnants();
+                    return Token::BadUrl;
+                }
+
+                '"' | '\'' | '(' => {
+                    self.consume_bad_url_remnants();
+                    return Token::BadUrl;
+                }
+
+                c if is_non_printable(c) => {
+                    self.consume_bad_url_remnants();
+                    return Token::BadUrl;
+                }
+
+                '\\' => {
+                    if !is_valid_escape(self.peek(0), self.peek(1)) {
+                        self.consume_bad_url_remnants();
+                        return Token::BadUrl;
+                    }
+
+                    self.parser.consume_char();
+                    value.push(self.consume_escaped());
+                }
+
+                _ => value.push(self.parser.consume_char()),
+            }
+        }
+
+        return Token::Url(value);
+    }
+
+    fn consume_bad_url_remnants(&mut self) {
+        while !self.parser.eof() {
+            if is_valid_escape(self.peek(0), self.peek(1)) {
+                self.parser.consume_char();
+                self.consume_escaped();
+                continue;
+            }
+
+            if self.parser.consume_char() == ')' {
+                return;
+            }
+        }
+    }
+
+    /// Consumes an escaped code point, the backslash must already be
+    /// consumed.
+    fn consume_escaped(&mut self) -> char {
+        if self.parser.eof() {
+            return char::REPLACEMENT_CHARACTER;
+        }
+
+        if !self.parser.next_char().is_ascii_hexdigit() {
+            return self.parser.consume_char();
+        }
+
+        let mut hex = String::new();
+
+        while hex.len() < 6 && self.peek(0).map_or(false, |c| c.is_ascii_hexdigit()) {
+            hex.push(self.parser.consume_char());
+        }
+
+        if self.peek(0).map_or(false, is_whitespace) {
+            self.parser.consume_char();
+        }
+
+        let code_point = u32::from_str_radix(&hex, 16).unwrap();
+
+        return match char::from_u32(code_point) {
+            Some('\0') | None => char::REPLACEMENT_CHARACTER,
+            Some(c) => c,
+        };
+    }
+
+    fn consume_name(&mut self) -> String {
+        let mut name = String::new();
+
+        loop {
+            if self.peek(0).map_or(false, is_ident_char) {
+                name.push(self.parser.consume_char());
+            } else if is_valid_escape(self.peek(0), self.peek(1)) {
+                self.parser.consume_char();
+                name.push(self.consume_escaped());
+            } else {
+                return name;
+            }
+        }
+    }
+
+    fn consume_chars(&mut self, count: usize) {
+        for _ in 0..count {
+            self.parser.consume_char();
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<char> {
+        return self.parser.peek_char(offset);
+    }
+
+    fn would_start_ident(&self, offset: usize) -> bool {
+        let (first, second, third) = (
+            self.peek(offset),
+            self.peek(offset + 1),
+            self.peek(offset + 2),
+        );
+
+        return match first {
+            Some('-') => {
+                second.map_or(false, |c| is_ident_start(c) || c == '-')
+                    || is_valid_escape(second, third)
+            }
+            Some('\\') => is_valid_escape(first, second),
+            Some(c) => is_ident_start(c),
+            None => false,
+        };
+    }
+
+    fn would_start_number(&self, offset: usize) -> bool {
+        let is_digit = |c: Option<char>| c.map_or(false, |c| c.is_ascii_digit());
+        let (first, second, third) = (
+            self.peek(offset),
+            self.peek(offset + 1),
+            self.peek(offset + 2),
+        );
+
+        return match first {
+            Some('+') | Some('-') => is_digit(second) || (second == Some('.') && is_digit(third)),
+            Some('.') => is_digit(second),
+            _ => is_digit(first),
+        };
+    }
+}
+
+/// Parses a list of component values, see CSS Syntax Level 3, section 5.3.10.
+pub fn parse_component_values(input: &str) -> ComponentValues {
+    let tokens = Tokenizer::tokenize(input);
+    let mut pos = 0;
+    let mut values = ComponentValues::new();
+
+    while pos < tokens.len() {
+        values.push(consume_component_value(&tokens, &mut pos));
+    }
+
+    return values;
+}
+
+fn consume_component_value(tokens: &[Token], pos: &mut usize) -> ComponentValue {
+    let token = tokens[*pos].clone();
+    *pos += 1;
+
+    let ending = match token {
+        Token::OpenCurly => Token::CloseCurly,
+        Token::OpenSquare => Token::CloseSquare,
+        Token::OpenParen | Token::Function(_) => Token::CloseParen,
+        _ => return ComponentValue::Token(token),
+    };
+
+    let mut values = ComponentValues::new();
+
+    // Unclosed blocks and functions are closed at the end of the input
+    while *pos < tokens.len() {
+        if tokens[*pos] == ending {
+            *pos += 1;
+            break;
+        }
+
+        values.push(consume_component_value(tokens, pos));
+    }
+
+    return match token {
+        Token::Function(name) => ComponentValue::Function(name, values),
+        _ => ComponentValue::Block(token, values),
+    };
+}
+
+/// Serializes component values back into CSS text.
+pub fn serialize(values: &[ComponentValue]) -> String {
+    return values.iter().map(|value| value.to_string()).collect();
+}
+
+/// Removes leading and trailing whitespace.
+pub fn trim_whitespace(values: &[ComponentValue]) -> &[ComponentValue] {
+    let start = values
+        .iter()
+        .position(|value| !value.is_whitespace())
+        .unwrap_or(values.len());
+    let end = values
+        .iter()
+        .rposition(|value| !value.is_whitespace())
+        .map_or(start, |index| index + 1);
+
+    return &values[start..end];
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Token::Ident(ident) => write!(f, "{}", escape_identifier(ident)),
+            Token::Function(name) => write!(f, "{}(", escape_identifier(name)),
+            Token::AtKeyword(name) => write!(f, "@{}", escape_identifier(name)),
+            Token::Hash(name, HashType::Id) => write!(f, "#{}", escape_identifier(name)),
+            Token::Hash(name, HashType::Unrestricted) => write!(f, "#{}", escape_name(name)),
+            Token::String(value) => write!(f, "\"{}\"", escape_string(value)),
+            Token::BadString | Token::BadUrl => Ok(()),
+            Token::Url(value) => write!(f, "url({})", escape_url(value)),
+            Token::Delim(c) => write!(f, "{}", c),
+            Token::Number(number) => write!(f, "{}", number),
+            Token::Percentage(number) => write!(f, "{}%", number),
+            Token::Dimension(number, unit) => write!(f, "{}{}", number, unit),
+            Token::Whitespace => write!(f, " "),
+            Token::CDO => write!(f, "<!--"),
+            Token::CDC => write!(f, "-->"),
+            Token::Colon => write!(f, ":"),
+            Token::Semicolon => write!(f, ";"),
+            Token::Comma => write!(f, ","),
+            Token::OpenSquare => write!(f, "["),
+            Token::CloseSquare => write!(f, "]"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+            Token::OpenCurly => write!(f, "{{"),
+            Token::CloseCurly => write!(f, "}}"),
+        };
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Keep explicit plus signs, they are significant in `An+B`
+        if self.signed && self.value >= 0.0 {
+            return write!(f, "+{}", self.value);
+        }
+
+        return write!(f, "{}", self.value);
+    }
+}
+
+impl fmt::Display for ComponentValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            ComponentValue::Token(token) => write!(f, "{}", token),
+            ComponentValue::Function(name, values) => {
+                write!(f, "{}({})", escape_identifier(name), serialize(values))
+            }
+            ComponentValue::Block(token, values) => {
+                let ending = match token {
+                    Token::OpenCurly => '}',
+                    Token::OpenSquare => ']',
+                    _ => ')',
+                };
+
+                write!(f, "{}{}{}", token, serialize(values), ending)
+            }
+        };
+    }
+}
+
+fn escape_string(value: &str) -> String {
+    let mut result = String::new();
+
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                result.push('\\');
+                result.push(c);
+            }
+            '\n' => result.push_str("\\a "),
+            _ => result.push(c),
+        }
+    }
+
+    return result;
+}
+
+/// Escapes the value of an unquoted `url()`.
+fn escape_url(value: &str) -> String {
+    let mut result = String::new();
+
+    for c in value.chars() {
+        match c {
+            '"' | '\'' | '(' | ')' | '\\' => {
+                result.push('\\');
+                result.push(c);
+            }
+            c if c.is_whitespace() || c.is_control() => {
+                result.push_str(&format!("\\{:x} ", c as u32));
+            }
+            _ => result.push(c),
+        }
+    }
+
+    return result;
+}
+
+/// Escapes an identifier, so it is tokenized as one again, see CSSOM,
+/// section 2.1.
+pub fn escape_identifier(value: &str) -> String {
+    if value == "-" {
+        return "\\-".to_owned();
+    }
+
+    // Identifiers can’t start with a digit, or with `-` and a digit
+    let dashes = value.chars().take_while(|c| *c == '-').count().min(1);
+
+    return match value.chars().nth(dashes) {
+        Some(c) if c.is_ascii_digit() => format!(
+            "{}\\{:x} {}",
+            &value[..dashes],
+            c as u32,
+            escape_name(&value[dashes + 1..])
+        ),
+        _ => escape_name(value),
+    };
+}
+
+/// Escapes a name, like that of an unrestricted hash, which unlike an
+/// identifier may start with a digit.
+fn escape_name(value: &str) -> String {
+    let mut result = String::new();
+
+    for c in value.chars() {
+        if c.is_control() {
+            result.push_str(&format!("\\{:x} ", c as u32));
+        } else if is_ident_char(c) {
+            result.push(c);
+        } else {
+            result.push('\\');
+            result.push(c);
+        }
+    }
+
+    return result;
+}
+
+/// Normalizes newlines and replaces NULL chars, see CSS Syntax Level 3,
+/// section 3.3.
+fn preprocess(input: &str) -> String {
+    return input
+        .replace("\r\n", "\n")
+        .replace(['\r', '\x0c'], "\n")
+        .replace('\0', "\u{fffd}");
+}
+
+fn is_whitespace(c: char) -> bool {
+    return c == '\n' || c == '\t' || c == ' ';
+}
+
+fn is_ident_start(c: char) -> bool {
+    return c.is_ascii_alphabetic() || c == '_' || !c.is_ascii();
+}
+
+fn is_ident_char(c: char) -> bool {
+    return is_ident_start(c) || c.is_ascii_digit() || c == '-';
+}
+
+fn is_non_printable(c: char) -> bool {
+    return matches!(c, '\0'..='\x08' | '\x0b' | '\x0e'..='\x1f' | '\x7f');
+}
+
+fn is_valid_escape(first: Option<char>, second: Option<char>) -> bool {
+    return first == Some('\\') && second.map_or(false, |c| c != '\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(value: f64) -> Number {
+        return Number::new(value, value.fract() == 0.0, false);
+    }
+
+    #[test]
+    fn test_tokenizer_ident() {
+        let tokens = Tokenizer::tokenize("color -webkit-box --brand _a\\31 b");
+
+        assert!(
+            tokens
+                == [
+                    Token::Ident("color".to_owned()),
+                    Token::Whitespace,
+                    Token::Ident("-webkit-box".to_owned()),
+                    Token::Whitespace,
+                    Token::Ident("--brand".to_owned()),
+                    Token::Whitespace,
+                    Token::Ident("_a1b".to_owned()),
+                ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_function_and_url() {
+        let tokens = Tokenizer::tokenize("rgb( url(a;b) url( \"c.png\" ) url(d e)");
+
+        assert!(
+            tokens
+                == [
+                    Token::Function("rgb".to_owned()),
+                    Token::Whitespace,
+                    Token::Url("a;b".to_owned()),
+                    Token::Whitespace,
+                    Token::Function("url".to_owned()),
+                    Token::Whitespace,
+                    Token::String("c.png".to_owned()),
+                    Token::Whitespace,
+                    Token::CloseParen,
+                    Token::Whitespace,
+                    Token::BadUrl,
+                ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_at_keyword_and_hash() {
+        let tokens = Tokenizer::tokenize("@media #intro #0af @ #");
+
+        assert!(
+            tokens
+                == [
+                    Token::AtKeyword("media".to_owned()),
+                    Token::Whitespace,
+                    Token::Hash("intro".to_owned(), HashType::Id),
+                    Token::Whitespace,
+                    Token::Hash("0af".to_owned(), HashType::Unrestricted),
+                    Token::Whitespace,
+                    Token::Delim('@'),
+                    Token::Whitespace,
+                    Token::Delim('#'),
+                ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_string() {
+        let tokens = Tokenizer::tokenize("\"Helvetica Neue\" 'it\\'s' \"\\26 \" \"broken\n");
+
+        assert!(
+            tokens
+                == [
+                    Token::String("Helvetica Neue".to_owned()),
+                    Token::Whitespace,
+                    Token::String("it's".to_owned()),
+                    Token::Whitespace,
+                    Token::String("&".to_owned()),
+                    Token::Whitespace,
+                    Token::BadString,
+                    Token::Whitespace,
+                ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_numeric() {
+        let tokens = Tokenizer::tokenize("10px 50% 1.5 -.5em +2 1e3");
+
+        assert!(
+            tokens
+                == [
+                    Token::Dimension(number(10.0), "px".to_owned()),
+                    Token::Whitespace,
+                    Token::Percentage(number(50.0)),
+                    Token::Whitespace,
+                    Token::Number(number(1.5)),
+                    Token::Whitespace,
+                    Token::Dimension(Number::new(-0.5, false, true), "em".to_owned()),
+                    Token::Whitespace,
+                    Token::Number(Number::new(2.0, true, true)),
+                    Token::Whitespace,
+                    Token::Number(Number::new(1000.0, false, false)),
+                ]
+        );
+
+        let tokens = Tokenizer::tokenize("1e400px -1e400 1e400%");
+
+        assert!(
+            tokens
+                == [
+                    Token::Dimension(Number::new(f64::MAX, false, false), "px".to_owned()),
+                    Token::Whitespace,
+                    Token::Number(Number::new(-f64::MAX, false, true)),
+                    Token::Whitespace,
+                    Token::Percentage(Number::new(f64::MAX, false, false)),
+                ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_punctuation() {
+        let tokens = Tokenizer::tokenize("<!-- a{b:c;}[d], -->");
+
+        assert!(
+            tokens
+                == [
+                    Token::CDO,
+                    Token::Whitespace,
+                    Token::Ident("a".to_owned()),
+                    Token::OpenCurly,
+                    Token::Ident("b".to_owned()),
+                    Token::Colon,
+                    Token::Ident("c".to_owned()),
+                    Token::Semicolon,
+                    Token::CloseCurly,
+                    Token::OpenSquare,
+                    Token::Ident("d".to_owned()),
+                    Token::CloseSquare,
+                    Token::Comma,
+                    Token::Whitespace,
+                    Token::CDC,
+                ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_comments() {
+        let tokens = Tokenizer::tokenize("a/* b */c /* unterminated");
+
+        assert!(
+            tokens
+                == [
+                    Token::Ident("a".to_owned()),
+                    Token::Ident("c".to_owned()),
+                    Token::Whitespace,
+                ]
+        );
+    }
+
+    #[test]
+    fn test_parse_component_values() {
+        let values = parse_component_values("a { b: calc(1px + (2px)); } [c");
+
+        let calc = ComponentValue::Function(
+            "calc".to_owned(),
+            ComponentValues::from([
+                ComponentValue::Token(Token::Dimension(number(1.0), "px".to_owned())),
+                ComponentValue::Token(Token::Whitespace),
+                ComponentValue::Token(Token::Delim('+')),
+                ComponentValue::Token(Token::Whitespace),
+                ComponentValue::Block(
+                    Token::OpenParen,
+                    ComponentValues::from([ComponentValue::Token(Token::Dimension(
+                        number(2.0),
+                        "px".to_owned(),
+                    ))]),
+                ),
+            ]),
+        );
+
+        assert!(values.len() == 5);
+        assert!(values[0] == ComponentValue::Token(Token::Ident("a".to_owned())));
+        assert!(
+            values[2]
+                == ComponentValue::Block(
+                    Token::OpenCurly,
+                    ComponentValues::from([
+                        ComponentValue::Token(Token::Whitespace),
+                        ComponentValue::Token(Token::Ident("b".to_owned())),
+                        ComponentValue::Token(Token::Colon),
+                        ComponentValue::Token(Token::Whitespace),
+                        calc,
+                        ComponentValue::Token(Token::Semicolon),
+                        ComponentValue::Token(Token::Whitespace),
+                    ])
+                )
+        );
+
+        // Unclosed blocks are closed at the end of the input
+        assert!(
+            values[4]
+                == ComponentValue::Block(
+                    Token::OpenSquare,
+                    ComponentValues::from([ComponentValue::Token(Token::Ident("c".to_owned()))])
+                )
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let css = "\"Helvetica Neue\", sans-serif url(a;b) calc(100% - 2 * 1.5em) #fff [x] -n+3";
+
+        assert!(serialize(&parse_component_values(css)) == css);
+    }
+
+    #[test]
+    fn test_serialize_escapes() {
+        let css = "\\31 a a\\:b url(a\\)\\ b) f\\(x(1) @\\@ \\-";
+        let values = parse_component_values(css);
+
+        assert!(serialize(&values) == "\\31 a a\\:b url(a\\)\\20 b) f\\(x(1) @\\@ \\-");
+        assert!(parse_component_values(&serialize(&values)) == values);
+
+        let css = "#a\\ b #\\31 x #1\\.5";
+        let values = parse_component_values(css);
+
+        assert!(serialize(&values) == "#a\\ b #\\31 x #1\\.5");
+        assert!(parse_component_values(&serialize(&values)) == values);
+    }
+
+    #[test]
+    fn test_escape_identifier() {
+        assert!(escape_identifier("sans-serif") == "sans-serif");
+        assert!(escape_identifier("a:b") == "a\\:b");
+        assert!(escape_identifier("1a") == "\\31 a");
+        assert!(escape_identifier("-2") == "-\\32 ");
+        assert!(escape_identifier("-") == "\\-");
+
+        for ident in ["a:b", "1a", "-2", "-", "a b"] {
+            let tokens = Tokenizer::tokenize(&escape_identifier(ident));
+            assert!(tokens == [Token::Ident(ident.to_owned())]);
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use crate::css;
+use crate::cssom;
+use crate::tokenizer::{self, ComponentValue, ComponentValues, Token};
+use std::collections::{HashMap, HashSet};
+use std::vec::Vec;
+
+pub fn is_custom_property(property: &str) -> bool {
+    return property.starts_with("--");
+}
+
+/// Checks whether any of the values, or the values nested in them, is a
+/// `var()` function.
+pub fn contains_var(values: &[ComponentValue]) -> bool {
+    return values.iter().any(|value| match value {
+        ComponentValue::Function(name, _) if name.eq_ignore_ascii_case("var") => true,
+        ComponentValue::Function(_, values) | ComponentValue::Block(_, values) => {
+            contains_var(values)
+        }
+        ComponentValue::Token(_) => false,
+    });
+}
+
+/// Computes the custom properties of an element, inheriting those of its
+/// parent, and substitutes `var()` in all other properties. Properties
+/// referring to a custom property that is missing or part of a cycle,
+/// without a fallback, are invalid and dropped.
+pub fn substitute_variables(
+    declarations: cssom::ComputedStyle,
+    parent: &cssom::ComputedStyle,
+) -> cssom::ComputedStyle {
+    let mut properties = CustomProperties::new(parent);
+    let mut computed = cssom::ComputedStyle::new();
+    let mut pending = Vec::new();
+
+    for (property, value) in declarations {
+        match value {
+            cssom::Value::Tokens(values) if is_custom_property(&property) => {
+                properties.declared.insert(property, values);
+            }
+            cssom::Value::Pending(declared, values) => pending.push((property, declared, values)),
+            value => {
+                computed.insert(property, value);
+            }
+        }
+    }
+
+    for (property, declared, values) in pending {
+        let value = substitute(&values, &mut properties).and_then(|values| {
+            return css::Parser::parse_substituted_value(&property, &declared, values);
+        });
+
+        if let Some(value) = value {
+            computed.insert(property, value);
+        }
+    }
+
+    let names: HashSet<String> = properties
+        .declared
+        .keys()
+        .chain(properties.inherited.keys())
+        .cloned()
+        .collect();
+
+    for name in names {
+        if let Some(values) = properties.get(&name) {
+            computed.insert(name, cssom::Value::Tokens(values));
+        }
+    }
+
+    return computed;
+}
+
+/// The custom properties of an element. Declared ones are resolved when
+/// they are first used, inherited ones already are.
+struct CustomProperties {
+    declared: HashMap<String, ComponentValues>,
+    inherited: HashMap<String, ComponentValues>,
+    /// Resolved declared properties, `None` if they are invalid.
+    resolved: HashMap<String, Option<ComponentValues>>,
+    /// Properties being resolved, to detect cycles.
+    resolving: Vec<String>,
+    cyclic: HashSet<String>,
+}
+
+impl CustomProperties {
+    pub fn new(parent: &cssom::ComputedStyle) -> Self {
+        let inherited = parent
+            .iter()
+            .filter_map(|(property, value)| match value {
+                cssom::Value::Tokens(values) if is_custom_property(property) => {
+                    Some((property.clone(), values.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        return Self {
+            declared: HashMap::new(),
+            inherited: inherited,
+            resolved: HashMap::new(),
+            resolving: Vec::new(),
+            cyclic: HashSet::new(),
+        };
+    }
+
+    fn get(&mut self, name: &str) -> Option<ComponentValues> {
+        if let Some(values) = self.resolved.get(name) {
+            return values.clone();
+        }
+
+        let values = match self.declared.get(name) {
+            Some(values) => values.clone(),
+            None => return self.inherited.get(name).cloned(),
+        };
+
+        // All properties in a cycle are invalid, even if they have a
+        // fallback for the property that closes it
+        if let Some(start) = self.resolving.iter().position(|other| other == name) {
+            self.cyclic.extend(self.resolving[start..].iter().cloned());
+            return None;
+        }
+
+        self.resolving.push(name.to_owned());
+        let values = substitute(&values, self);
+        self.resolving.pop();
+
+        let values = match self.cyclic.contains(name) {
+            true => None,
+            false => values,
+        };
+
+        self.resolved.insert(name.to_owned(), values.clone());
+
+        return values;
+    }
+}
+
+/// Replaces `var()` functions with the values of custom properties, or
+/// their fallbacks. Returns `None` if neither is available.
+fn substitute(
+    values: &[ComponentValue],
+    properties: &mut CustomProperties,
+) -> Option<ComponentValues> {
+    let mut result = ComponentValues::new();
+
+    for value in values {
+        match value {
+            ComponentValue::Function(name, arguments) if name.eq_ignore_ascii_case("var") => {
+                let (name, fallback) = parse_var_arguments(arguments)?;
+
+                match (properties.get(name), fallback) {
+                    (Some(values), _) => result.extend(values),
+                    (None, Some(fallback)) => result.extend(substitute(fallback, properties)?),
+                    (None, None) => return None,
+                }
+            }
+            ComponentValue::Function(name, arguments) => {
+                let arguments = substitute(arguments, properties)?;
+                result.push(ComponentValue::Function(name.clone(), arguments));
+            }
+            ComponentValue::Block(token, values) => {
+                let values = substitute(values, properties)?;
+                result.push(ComponentValue::Block(token.clone(), values));
+            }
+            ComponentValue::Token(_) => result.push(value.clone()),
+        }
+    }
+
+    return Some(result);
+}
+
+/// Splits the arguments of `var()` into the custom property name and the
+/// fallback after the first comma, if there is one.
+fn parse_var_arguments(arguments: &[ComponentValue]) -> Option<(&str, Option<&[ComponentValue]>)> {
+    let comma = arguments
+        .iter()
+        .position(|value| value.is_token(&Token::Comma));
+
+    let (name, fallback) = match comma {
+        Some(comma) => (
+            &arguments[..comma],
+            Some(tokenizer::trim_whitespace(&arguments[comma + 1..])),
+        ),
+        None => (arguments, None),
+    };
+
+    let mut name = name.iter().filter(|value| !value.is_whitespace());
+
+    return match (name.next().and_then(|value| value.ident()), name.next()) {
+        (Some(name), None) if is_custom_property(name) => Some((name, fallback)),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style(declarations: &[(&str, &str)]) -> cssom::ComputedStyle {
+        let css = declarations
+            .iter()
+            .map(|(property, value)| format!("{}: {};", property, value))
+            .collect::<String>();
+
+        return css::Parser::parse(&format!("a {{ {} }}", css))[0]
+            .declarations
+            .iter()
+            .map(|declaration| (declaration.property.clone(), declaration.value.clone()))
+            .collect();
+    }
+
+    fn tokens(css: &str) -> cssom::Value {
+        return cssom::Value::Tokens(tokenizer::parse_component_values(css));
+    }
+
+    #[test]
+    fn test_contains_var() {
+        assert!(contains_var(&tokenizer::parse_component_values("var(--a)")) == true);
+        assert!(contains_var(&tokenizer::parse_component_values("calc(1px + VAR(--a))")) == true);
+        assert!(contains_var(&tokenizer::parse_component_values("1px (var(--a))")) == true);
+        assert!(contains_var(&tokenizer::parse_component_values("--a variable")) == false);
+    }
+
+    #[test]
+    fn test_substitute_variables() {
+        let parent = substitute_variables(
+            style(&[("--brand", "#36c"), ("--gap", "4px")]),
+            &HashMap::new(),
+        );
+        let computed = substitute_variables(
+            style(&[
+                ("--gap", "calc(var(--brand-gap, 2px) * 2)"),
+                ("color", "var(--brand, black)"),
+                (
+                    "background-color",
+                    "var(--missing, var(--also-missing, red))",
+                ),
+                ("width", "var(--gap)"),
+                ("height", "var(--missing)"),
+                ("margin", "var(--gap) 0"),
+            ]),
+            &parent,
+        );
+
+        assert!(computed["--brand"] == tokens("#36c"));
+        assert!(computed["--gap"] == tokens("calc(2px * 2)"));
+        assert!(computed["color"] == cssom::Value::Color(cssom::Color::new(0x33, 0x66, 0xcc)));
+        assert!(computed["background-color"] == cssom::Value::Color(cssom::Color::new(255, 0, 0)));
+        assert!(matches!(
+            computed["width"],
+            cssom::Value::Numeric(cssom::NumericValue::Calc(_))
+        ));
+        assert!(computed.get("height").is_none());
+        assert!(computed["margin-top"] == computed["width"]);
+        assert!(computed["margin-left"] == cssom::Value::Numeric(cssom::NumericValue::Zero));
+    }
+
+    #[test]
+    fn test_substitute_variables_invalid() {
+        let computed = substitute_variables(
+            style(&[
+                ("--a", "var(--b)"),
+                ("--b", "var(--a, 1px)"),
+                ("--c", "var(--c)"),
+                ("--d", "var(--a, 2px)"),
+                ("--e", "a:b {c}"),
+                ("width", "var(--d)"),
+                ("height", "var(--e)"),
+                ("color", "var(nope, red)"),
+            ]),
+            &HashMap::new(),
+        );
+
+        assert!(computed.get("--a").is_none());
+        assert!(computed.get("--b").is_none());
+        assert!(computed.get("--c").is_none());
+        assert!(computed["--d"] == tokens("2px"));
+        assert!(computed["--e"] == tokens("a:b {c}"));
+        assert!(computed["width"] == cssom::Value::Numeric(cssom::NumericValue::Px(2.0)));
+        assert!(computed.get("height").is_none());
+        assert!(computed.get("color").is_none());
+    }
+}
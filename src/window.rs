@@ -8,19 +8,25 @@ const HEIGHT: usize = 480;
 
 pub struct Window {
     buffer: painting::WindowBuffer,
+    /// The size of the buffer, which follows the window size.
+    size: layout::Dimensions,
     inner: minifb::Window,
 }
 
 impl Window {
     pub fn new() -> Self {
-        let mut inner =
-            minifb::Window::new("Title", WIDTH, HEIGHT, minifb::WindowOptions::default()).unwrap();
+        let options = minifb::WindowOptions {
+            resize: true,
+            ..minifb::WindowOptions::default()
+        };
+        let mut inner = minifb::Window::new("Title", WIDTH, HEIGHT, options).unwrap();
 
         // Limit updates to 60fps
         inner.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
 
         return Self {
             buffer: painting::WindowBuffer::new(WIDTH, HEIGHT),
+            size: layout::Dimensions::new(WIDTH, HEIGHT),
             inner: inner,
         };
     }
@@ -29,6 +35,45 @@ impl Window {
         return self.inner.is_open();
     }
 
+    /// Returns the mouse position, if the mouse is inside the window.
+    pub fn mouse_position(&self) -> Option<layout::Point> {
+        return self
+            .inner
+            .get_mouse_pos(minifb::MouseMode::Discard)
+            .map(|(x, y)| layout::Point::new(x as isize, y as isize));
+    }
+
+    pub fn is_mouse_down(&self) -> bool {
+        return self.inner.get_mouse_down(minifb::MouseButton::Left);
+    }
+
+    pub fn is_tab_pressed(&self) -> bool {
+        return self
+            .inner
+            .is_key_pressed(minifb::Key::Tab, minifb::KeyRepeat::Yes);
+    }
+
+    /// Returns the current size of the window’s content area.
+    pub fn viewport(&self) -> layout::Dimensions {
+        let (width, height) = self.inner.get_size();
+
+        return layout::Dimensions::new(width, height);
+    }
+
+    /// Clears the buffer, resizing it if the window was resized.
+    pub fn clear(&mut self) -> &Self {
+        let viewport = self.viewport();
+
+        if viewport != self.size {
+            self.buffer = painting::WindowBuffer::new(viewport.width, viewport.height);
+            self.size = viewport;
+        }
+
+        self.buffer.clear();
+
+        return self;
+    }
+
     pub fn paint_node(&mut self, node: &layout::LayoutNode) -> &Self {
         self.buffer.paint_node(node);
 
@@ -37,7 +82,7 @@ impl Window {
 
     pub fn update(&mut self) -> &Self {
         self.inner
-            .update_with_buffer(&self.buffer.as_vec(), WIDTH, HEIGHT)
+            .update_with_buffer(&self.buffer.as_vec(), self.size.width, self.size.height)
             .unwrap();
 
         return self;